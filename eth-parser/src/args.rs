@@ -1,22 +1,164 @@
-use clap::Parser;
+use std::{net::SocketAddr, time::Duration};
+
+use clap::{ArgEnum, Parser};
+
+use crate::sync::{CommitStrategy, FailurePolicy, FillOrder, SyncOptions, ValidatorsCountCheck};
+
+/// Chain layers stored in database
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexedLayers {
+	/// Slots, along with the validators
+	Consensus,
+	/// Blocks and their transactions
+	Execution,
+	Both,
+}
+
+impl IndexedLayers {
+	pub fn consensus(&self) -> bool {
+		matches!(self, IndexedLayers::Consensus | IndexedLayers::Both)
+	}
+
+	pub fn execution(&self) -> bool {
+		matches!(self, IndexedLayers::Execution | IndexedLayers::Both)
+	}
+}
 
 #[derive(Parser, Debug)]
 #[clap(version, about)]
 pub struct Args {
-	/// Height from where the consensus layer will be synced
+	/// Chain layers to index. Both heads are derived from the consensus node head: the execution
+	/// one is the block of the highest slot holding one, so the consensus node is always needed
+	#[clap(long, arg_enum, default_value = "both")]
+	layers: IndexedLayers,
+
+	/// Slot from where the consensus layer will be synced
 	#[clap(long)]
 	from_slot: Option<u64>,
 
-	/// Height from where the execution layer will be synced
+	/// Block number from where the execution layer will be synced
 	#[clap(long)]
 	from_block: Option<u64>,
 
-	/// Height of the consensus slot at which the database will be fronzen
+	/// Slot at which the database will be fronzen, whatever the layers indexed
 	#[clap(long)]
 	freeze_at: u64,
+
+	/// Address on which the Prometheus metrics are served. Not served if omitted
+	#[clap(long)]
+	metrics_address: Option<SocketAddr>,
+
+	/// Number of times a height that failed to be indexed is retried
+	#[clap(long, default_value_t = 0)]
+	retries: u32,
+
+	/// What to do with a height still failing after its retries.
+	/// `skip` leaves a hole in database and goes on.
+	/// `halt` exits with an error, to be restarted by an orchestrator
+	#[clap(long, arg_enum, default_value = "skip")]
+	on_permanent_failure: FailurePolicy,
+
+	/// Order in which missing heights are synced.
+	/// `newest-first` makes recent data available sooner on a cold start, but an interruption
+	/// leaves holes below the stored head
+	#[clap(long, arg_enum, default_value = "oldest-first")]
+	fill_order: FillOrder,
+
+	/// How often the synced heights are committed: `per-row` commits every write on its own,
+	/// `per-batch` every `--execution-batch-size` blocks, or every slot, and `per-chunk:<n>` every
+	/// n heights. Heights committed together are faster to sync, but are all lost on a crash, to
+	/// be synced again
+	#[clap(long, default_value = "per-row")]
+	commit_strategy: CommitStrategy,
+
+	/// Number of times a failing node head query is retried before the indexer stops, for errors
+	/// that are not waited out such as a malformed answer
+	#[clap(long, default_value_t = 2)]
+	head_retries: u32,
+
+	/// Maximum number of requests sent at once to each node, shared by the syncers and the
+	/// background tasks, to stay under its rate limit. Unlimited if omitted
+	#[clap(long)]
+	max_node_requests: Option<usize>,
+
+	/// Seconds the consensus node has to answer a request, such as a block fetch
+	#[clap(long, default_value_t = 10)]
+	node_timeout: u64,
+
+	/// Seconds each node has to answer a lightweight request: head and finalized heights, chain id
+	/// and spec. A node timing out is failed over, then waited out like an unreachable one
+	#[clap(long, default_value_t = 2)]
+	node_request_timeout: u64,
+
+	/// Maximum number of slots the node head can be above the previous one, or above the slot
+	/// going on by the wall clock if later, e.g. after a downtime.
+	/// A higher head is considered bogus, e.g. a node on the wrong network, and is not synced
+	#[clap(long, default_value_t = 100_000)]
+	max_head_jump: u64,
+
+	/// Store the blocks fetched from the nodes as is, so they can be reprocessed without the
+	/// nodes. Increases the database size a lot
+	#[clap(long)]
+	store_raw_payloads: bool,
+
+	/// Trace the blocks to index the calls made by contracts. Needs a node providing the `trace`
+	/// namespace, e.g. nethermind or erigon, and slows the sync down a lot
+	#[clap(long)]
+	trace_internal_calls: bool,
+
+	/// Number of execution blocks fetched with a single JSON-RPC batch request, at most 100.
+	/// Blocks are fetched one at a time if 1, or if the node fails a batch
+	#[clap(long, default_value_t = 1)]
+	execution_batch_size: usize,
+
+	/// Number of slots kept below the highest stored one, the older ones being pruned along with
+	/// the execution data of their blocks. Nothing is pruned if omitted.
+	/// With a `--from-slot` below the retained slots, the pruned ones are indexed again
+	#[clap(long)]
+	retained_slots: Option<u64>,
+
+	/// Number of the highest stored slots compared with the node's after every bump, to detect
+	/// reorgs. The reorged slots, along with their blocks, are fetched again. Disabled if 0
+	#[clap(long, default_value_t = 0)]
+	reorg_check_depth: u64,
+
+	/// Number of reorg rewinds in a row, without `--reorg-rewind-cooldown` seconds between two of
+	/// them, after which rewinds are paused for the cooldown. The reorged slots found meanwhile
+	/// are only reported
+	#[clap(long, default_value_t = 3)]
+	max_reorg_rewinds: u32,
+
+	/// Seconds reorg rewinds are paused for once `--max-reorg-rewinds` is reached, and without
+	/// rewind after which the count starts over
+	#[clap(long, default_value_t = 600)]
+	reorg_rewind_cooldown: u64,
+
+	/// Seconds a layer's database height can stay still while the node head moves before the sync
+	/// is reported as stalled. Disabled if 0
+	#[clap(long, default_value_t = 600)]
+	stall_threshold: u64,
+
+	/// Exit when the sync is stalled, to be restarted by an orchestrator, instead of only
+	/// reporting it
+	#[clap(long)]
+	exit_on_stall: bool,
+
+	/// Largest drop of the validators count from one slot to the next considered normal. A larger
+	/// one is logged as an anomaly. Not checked if omitted
+	#[clap(long)]
+	max_validators_drop: Option<u64>,
+
+	/// Store the slots whose validators count drops too much without it, instead of only logging
+	/// the anomaly. Ignored without `--max-validators-drop`
+	#[clap(long)]
+	reject_validators_drop: bool,
 }
 
 impl Args {
+	pub fn layers(&self) -> IndexedLayers {
+		self.layers
+	}
+
 	pub fn first_slot(&self) -> Option<u64> {
 		self.from_slot
 	}
@@ -28,4 +170,83 @@ impl Args {
 	pub fn freeze_at(&self) -> u64 {
 		self.freeze_at
 	}
+
+	pub fn metrics_address(&self) -> Option<SocketAddr> {
+		self.metrics_address
+	}
+
+	pub fn head_retries(&self) -> u32 {
+		self.head_retries
+	}
+
+	pub fn max_node_requests(&self) -> Option<usize> {
+		self.max_node_requests
+	}
+
+	pub fn node_timeout(&self) -> Duration {
+		Duration::from_secs(self.node_timeout)
+	}
+
+	pub fn node_request_timeout(&self) -> Duration {
+		Duration::from_secs(self.node_request_timeout)
+	}
+
+	pub fn max_head_jump(&self) -> u64 {
+		self.max_head_jump
+	}
+
+	pub fn store_raw_payloads(&self) -> bool {
+		self.store_raw_payloads
+	}
+
+	pub fn trace_internal_calls(&self) -> bool {
+		self.trace_internal_calls
+	}
+
+	pub fn execution_batch_size(&self) -> usize {
+		self.execution_batch_size
+	}
+
+	pub fn retained_slots(&self) -> Option<u64> {
+		self.retained_slots
+	}
+
+	pub fn reorg_check_depth(&self) -> u64 {
+		self.reorg_check_depth
+	}
+
+	pub fn max_reorg_rewinds(&self) -> u32 {
+		self.max_reorg_rewinds
+	}
+
+	pub fn reorg_rewind_cooldown(&self) -> Duration {
+		Duration::from_secs(self.reorg_rewind_cooldown)
+	}
+
+	pub fn stall_threshold(&self) -> Option<Duration> {
+		match self.stall_threshold {
+			0 => None,
+			secs => Some(Duration::from_secs(secs)),
+		}
+	}
+
+	pub fn exit_on_stall(&self) -> bool {
+		self.exit_on_stall
+	}
+
+	pub fn validators_count_check(&self) -> Option<ValidatorsCountCheck> {
+		self.max_validators_drop.map(|max_drop| ValidatorsCountCheck {
+			max_drop,
+			reject: self.reject_validators_drop,
+		})
+	}
+
+	pub fn sync_options(&self) -> SyncOptions {
+		SyncOptions {
+			retries: self.retries,
+			on_permanent_failure: self.on_permanent_failure,
+			fill_order: self.fill_order,
+			commit_strategy: self.commit_strategy,
+		}
+	}
 }
@@ -1,67 +1,249 @@
-use std::{env, time::Duration};
+use std::{
+	ops::Deref,
+	time::{Duration, SystemTime},
+};
 
 use eth2::{
-	types::{ConfigAndPreset, MainnetEthSpec, SignedBeaconBlock, Slot, StateId, ValidatorData},
+	reqwest::{self, header::RETRY_AFTER, StatusCode},
+	types::{
+		ConfigAndPreset, EthSpec, GenericResponse, Hash256, MainnetEthSpec, SignedBeaconBlock,
+		Slot, StateId, SyncingData, ValidatorData,
+	},
 	BeaconNodeHttpClient, Timeouts,
 };
+use futures::future::join_all;
 use sensitive_url::SensitiveUrl;
 
-use crate::Error;
+use crate::{
+	failover::{self, FailoverClient},
+	node_health::parse_retry_after,
+	sync::SyncError,
+	Error,
+};
+
+/// Number of slots a node can lag behind the chain head while syncing and still be used
+const SYNCING_TOLERANCE: u64 = 32;
 
-/// Create a new Beacon client
+/// A Beacon client failing over its fallback endpoints
+pub type Client = FailoverClient<BeaconNode>;
+
+/// A Beacon node endpoint, dereferencing to its Beacon API client
+///
+/// The API client drops the headers of the responses, so the head query, the one polled
+/// continuously and so the one rate limited first, is sent with `http` to read `Retry-After`.
+#[derive(Clone)]
+pub struct BeaconNode {
+	api: BeaconNodeHttpClient,
+	http: reqwest::Client,
+	url: SensitiveUrl,
+}
+
+impl Deref for BeaconNode {
+	type Target = BeaconNodeHttpClient;
+
+	fn deref(&self) -> &BeaconNodeHttpClient {
+		&self.api
+	}
+}
+
+impl BeaconNode {
+	fn new(url: SensitiveUrl, timeout: Duration) -> Result<Self, Error> {
+		let http = reqwest::Client::builder()
+			.timeout(timeout)
+			.build()
+			.map_err(eth2::Error::Reqwest)?;
+
+		Ok(BeaconNode {
+			api: BeaconNodeHttpClient::new(url.clone(), Timeouts::set_all(timeout)),
+			http,
+			url,
+		})
+	}
+
+	/// Same as `BeaconNodeHttpClient::get_node_syncing`, but fail with `Error::RateLimited` on a
+	/// 429, along with the delay the node asked for
+	async fn get_syncing_status(&self) -> Result<SyncingData, Error> {
+		let mut url = self.url.full.clone();
+		url.path_segments_mut()
+			.map_err(|()| eth2::Error::InvalidUrl(self.url.clone()))?
+			.pop_if_empty()
+			.extend(&["eth", "v1", "node", "syncing"]);
+
+		let response = self.http.get(url).send().await.map_err(eth2::Error::Reqwest)?;
+		let status = response.status();
+		if status == StatusCode::TOO_MANY_REQUESTS {
+			let retry_after = response
+				.headers()
+				.get(RETRY_AFTER)
+				.and_then(|value| value.to_str().ok())
+				.and_then(|value| parse_retry_after(value, SystemTime::now()));
+			return Err(Error::RateLimited { retry_after })
+		}
+		if !status.is_success() {
+			return Err(eth2::Error::StatusCode(status).into())
+		}
+
+		let syncing: GenericResponse<SyncingData> =
+			response.json().await.map_err(eth2::Error::Reqwest)?;
+		Ok(syncing.data)
+	}
+}
+
+/// Create a new Beacon client, whose requests time out after `timeout`
 ///
 /// # Environment requirement
 /// `CONSENSUS_LAYER_URL`: "http://<node_url>:<port>"
-pub fn new_client() -> Result<BeaconNodeHttpClient, Error> {
-	let raw_url = env::var("CONSENSUS_LAYER_URL")?;
-	let url = SensitiveUrl::parse(&raw_url)?;
-
-	Ok(BeaconNodeHttpClient::new(
-		url,
-		Timeouts::set_all(Duration::from_secs(1)),
-	))
+///
+/// # Optional environment
+/// `CONSENSUS_LAYER_FALLBACK_URLS`: "http://<node_url>:<port>,http://<node_url>:<port>"
+pub fn new_client(timeout: Duration) -> Result<Client, Error> {
+	let raw_urls = failover::urls_from_env("CONSENSUS_LAYER_URL", "CONSENSUS_LAYER_FALLBACK_URLS")?;
+
+	let clients = raw_urls
+		.iter()
+		.map(|raw_url| BeaconNode::new(SensitiveUrl::parse(raw_url)?, timeout))
+		.collect::<Result<Vec<_>, Error>>()?;
+
+	Ok(FailoverClient::new(clients))
 }
 
 /// Return the id of the highest slot synced by the node
 ///
+/// Fail with `SyncError::NodeSyncing` if the node is more than `SYNCING_TOLERANCE` slots behind
+/// the chain head, so a fallback is used if there is one.
+///
 /// https://ethereum.github.io/beacon-APIs/#/Node/getSyncingStatus response.head_slot
-pub async fn get_head_height(client: &BeaconNodeHttpClient) -> Result<u64, Error> {
-	let ret = client.get_node_syncing().await?;
+pub async fn get_head_height(client: &Client) -> Result<u64, Error> {
+	client
+		.call_timed("head height", |c| async move {
+			c.get_syncing_status().await.and_then(|data| {
+				if data.is_syncing && data.sync_distance.as_u64() > SYNCING_TOLERANCE {
+					Err(SyncError::NodeSyncing.into())
+				} else {
+					Ok(data.head_slot.as_u64())
+				}
+			})
+		})
+		.await
+}
 
-	Ok(ret.data.head_slot.as_u64())
+/// Return the id of the first slot of the node's finalized checkpoint
+///
+/// Return `None` if the node has no state for the head.
+///
+/// https://ethereum.github.io/beacon-APIs/#/Beacon/getStateFinalityCheckpoints
+pub async fn get_finalized_height(client: &Client) -> Result<Option<u64>, Error> {
+	client
+		.call_timed("finalized height", |c| async move {
+			c.get_beacon_states_finality_checkpoints(StateId::Head)
+				.await
+				.map(|opt_r| {
+					opt_r.map(|r| {
+						r.data
+							.finalized
+							.epoch
+							.start_slot(MainnetEthSpec::slots_per_epoch())
+							.as_u64()
+					})
+				})
+				.map_err(Error::from)
+		})
+		.await
 }
 
 /// Return the list of validators at `slot`
 ///
 /// https://ethereum.github.io/beacon-APIs/#/Beacon/getStateValidators
 pub async fn get_validators_at_slot(
-	client: &BeaconNodeHttpClient,
+	client: &Client,
 	slot: u64,
 ) -> Result<Option<Vec<ValidatorData>>, Error> {
-	let state_id = StateId::Slot(Slot::new(slot));
-	let opt_r = client.get_beacon_states_validators(state_id, None, None).await?;
+	client
+		.call(|c| async move {
+			let state_id = StateId::Slot(Slot::new(slot));
+
+			c.get_beacon_states_validators(state_id, None, None)
+				.await
+				.map(|opt_r| opt_r.map(|r| r.data))
+				.map_err(Error::from)
+		})
+		.await
+}
 
-	Ok(opt_r.map(|r| r.data))
+/// Return the number of validators at `slot`
+///
+/// Fetch the whole validator set, this is an expensive call.
+pub async fn get_validators_count_at_slot(
+	client: &Client,
+	slot: u64,
+) -> Result<Option<u64>, Error> {
+	get_validators_at_slot(client, slot)
+		.await
+		.map(|opt_validators| opt_validators.map(|v| v.len() as u64))
 }
 
 /// Return the chain spec
 ///
 /// https://ethereum.github.io/beacon-APIs/#/Config/getSpec
-pub async fn get_config_spec(client: &BeaconNodeHttpClient) -> Result<ConfigAndPreset, Error> {
-	let r = client.get_config_spec().await?;
+pub async fn get_config_spec(client: &Client) -> Result<ConfigAndPreset, Error> {
+	client
+		.call_timed("config spec", |c| async move {
+			c.get_config_spec().await.map(|r| r.data).map_err(Error::from)
+		})
+		.await
+}
+
+/// Return the unix timestamp of the chain genesis, in seconds
+///
+/// https://ethereum.github.io/beacon-APIs/#/Beacon/getGenesis
+pub async fn get_genesis_time(client: &Client) -> Result<u64, Error> {
+	client
+		.call_timed("genesis", |c| async move {
+			c.get_beacon_genesis().await.map(|r| r.data.genesis_time).map_err(Error::from)
+		})
+		.await
+}
+
+/// Return the execution block hash of the block at every one of `heights`, in the same order
+///
+/// None for a missed slot or a block without execution payload. The blocks are fetched
+/// concurrently, the Beacon API having no batch endpoint. Each height gets its own result, a
+/// block that cannot be fetched does not fail the others.
+pub async fn get_block_hashes(
+	client: &Client,
+	heights: &[u64],
+) -> Vec<(u64, Result<Option<Hash256>, Error>)> {
+	let blocks = join_all(heights.iter().map(|height| get_block(client, *height))).await;
 
-	Ok(r.data)
+	heights
+		.iter()
+		.zip(blocks)
+		.map(|(height, block)| {
+			let hash = block.map(|opt_block| {
+				opt_block.and_then(|b| {
+					b.message().body().execution_payload().ok().map(|p| p.block_hash.into_root())
+				})
+			});
+			(*height, hash)
+		})
+		.collect()
 }
 
 /// Return the block at `slot_height`
 ///
 /// https://ethereum.github.io/beacon-APIs/#/Beacon/getBlockV2
 pub async fn get_block(
-	client: &BeaconNodeHttpClient,
+	client: &Client,
 	slot_height: u64,
 ) -> Result<Option<SignedBeaconBlock<MainnetEthSpec>>, Error> {
-	let block_id = eth2::types::BlockId::Slot(Slot::new(slot_height));
-	let opt_r = client.get_beacon_blocks::<MainnetEthSpec>(block_id).await?;
+	client
+		.call(|c| async move {
+			let block_id = eth2::types::BlockId::Slot(Slot::new(slot_height));
 
-	Ok(opt_r.map(|r| r.data))
+			c.get_beacon_blocks::<MainnetEthSpec>(block_id)
+				.await
+				.map(|opt_r| opt_r.map(|r| r.data))
+				.map_err(Error::from)
+		})
+		.await
 }
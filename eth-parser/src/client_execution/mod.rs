@@ -1,45 +1,132 @@
-use std::env;
-
+use futures::future::try_join_all;
 use web3::{
-	transports::Http,
-	types::{Block, BlockId, BlockNumber, Transaction, TransactionReceipt, H256},
+	transports::{Batch, Http},
+	types::{Block, BlockId, BlockNumber, Trace, Transaction, TransactionReceipt, H256},
 	Web3,
 };
 
-use crate::Error;
+use crate::{
+	failover::{self, FailoverClient},
+	Error,
+};
+
+/// A Web3 client failing over its fallback endpoints
+pub type Client = FailoverClient<Web3<Http>>;
 
 /// Create a new Web3 client
 ///
 /// # Environment requirement
 /// `EXECUTION_LAYER_URL`: "http://<node_url>:<port>"
-pub fn new_client() -> Result<Web3<Http>, Error> {
-	let raw_url = env::var("EXECUTION_LAYER_URL")?;
-	let url = web3::transports::Http::new(&raw_url)?;
+///
+/// # Optional environment
+/// `EXECUTION_LAYER_FALLBACK_URLS`: "http://<node_url>:<port>,http://<node_url>:<port>"
+pub fn new_client() -> Result<Client, Error> {
+	let raw_urls = failover::urls_from_env("EXECUTION_LAYER_URL", "EXECUTION_LAYER_FALLBACK_URLS")?;
+
+	let clients = raw_urls
+		.iter()
+		.map(|raw_url| -> Result<_, Error> {
+			let url = web3::transports::Http::new(raw_url)?;
+			Ok(web3::Web3::new(url))
+		})
+		.collect::<Result<Vec<_>, Error>>()?;
 
-	Ok(web3::Web3::new(url))
+	Ok(FailoverClient::new(clients))
+}
+
+/// Return the id of the chain the node is on
+///
+/// https://eips.ethereum.org/EIPS/eip-695
+pub async fn get_chain_id(client: &Client) -> Result<u64, Error> {
+	client
+		.call_timed("chain id", |c| async move {
+			c.eth().chain_id().await.map(|id| id.as_u64()).map_err(Error::from)
+		})
+		.await
 }
 
 /// Get the block at `height`
 ///
 /// https://eth.wiki/json-rpc/API#eth_getblockbynumber
-pub async fn get_block(
-	client: Web3<Http>,
-	height: u64,
-) -> Result<Option<Block<Transaction>>, Error> {
-	let block_id = BlockId::Number(BlockNumber::Number(height.into()));
-	let opt_r = client.eth().block_with_txs(block_id).await?;
-
-	Ok(opt_r)
+pub async fn get_block(client: &Client, height: u64) -> Result<Option<Block<Transaction>>, Error> {
+	client
+		.call(|c| async move {
+			let block_id = BlockId::Number(BlockNumber::Number(height.into()));
+
+			c.eth().block_with_txs(block_id).await.map_err(Error::from)
+		})
+		.await
+}
+
+/// Maximum number of blocks requested in a single batch by `get_blocks`
+pub const MAX_BLOCKS_PER_BATCH: usize = 100;
+
+/// Get the blocks at `heights`, in the same order, with a single JSON-RPC batch request
+///
+/// Fails as a whole if any of the blocks cannot be fetched, or if the node does not handle batch
+/// requests. Meant for at most `MAX_BLOCKS_PER_BATCH` heights.
+///
+/// https://www.jsonrpc.org/specification#batch
+pub async fn get_blocks(
+	client: &Client,
+	heights: &[u64],
+) -> Result<Vec<Option<Block<Transaction>>>, Error> {
+	client
+		.call(|c| async move {
+			let batch = Web3::new(Batch::new(c.transport().clone()));
+			let blocks: Vec<_> = heights
+				.iter()
+				.map(|height| {
+					let block_id = BlockId::Number(BlockNumber::Number((*height).into()));
+					batch.eth().block_with_txs(block_id)
+				})
+				.collect();
+
+			// The calls resolve once the whole batch is answered
+			batch.transport().submit_batch().await?;
+			try_join_all(blocks).await.map_err(Error::from)
+		})
+		.await
+}
+
+/// JSON-RPC error code of a method the node does not provide
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Get the traces of every call made by the transactions of the block at `height`
+///
+/// Return `None` if none of the endpoints provide the `trace` namespace, e.g. geth.
+///
+/// https://openethereum.github.io/JSONRPC-trace-module#trace_block
+pub async fn get_block_traces(client: &Client, height: u64) -> Result<Option<Vec<Trace>>, Error> {
+	let traces = client
+		.call(|c| async move {
+			match c.trace().block(BlockNumber::Number(height.into())).await {
+				Ok(traces) => Ok(traces),
+				// Not a node failure, the other endpoints may provide it
+				Err(web3::Error::Rpc(err)) if err.code.code() == METHOD_NOT_FOUND =>
+					Err(Error::Unsupported {
+						call: "trace_block",
+					}),
+				Err(err) => Err(err.into()),
+			}
+		})
+		.await;
+
+	match traces {
+		Ok(traces) => Ok(Some(traces)),
+		Err(Error::Unsupported { .. }) => Ok(None),
+		Err(err) => Err(err),
+	}
 }
 
 /// Get the receipt of transaction `hash`
 ///
 /// https://eth.wiki/json-rpc/API#eth_gettransactionreceipt
 pub async fn get_transaction_receipt(
-	client: Web3<Http>,
+	client: &Client,
 	hash: H256,
 ) -> Result<Option<TransactionReceipt>, Error> {
-	let receip = client.eth().transaction_receipt(hash).await?;
-
-	Ok(receip)
+	client
+		.call(|c| async move { c.eth().transaction_receipt(hash).await.map_err(Error::from) })
+		.await
 }
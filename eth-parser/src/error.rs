@@ -1,5 +1,6 @@
-use std::{env::VarError, fmt::Display};
+use std::{env::VarError, fmt::Display, io, time::Duration};
 
+use kiln_postgres::{redact_urls_in, PoolError, SchemaError};
 use sensitive_url::SensitiveError;
 use tokio::task::JoinError;
 
@@ -13,7 +14,12 @@ pub enum Error {
 	Sensitive(SensitiveError),
 	Join(JoinError),
 	Diesel(diesel::result::Error),
+	/// No database connection could be established
+	DbPool(PoolError),
 	Sync(SyncError),
+	Io(io::Error),
+	/// The database is not migrated up to the version this binary expects
+	Schema(SchemaError),
 	/// Chain preset not supported
 	InvalidChainPreset(String),
 	/// Config name is missing from chain config
@@ -22,17 +28,50 @@ pub enum Error {
 	InvalidChainName,
 	/// Cannot use a pre merge slot (height < 29151) as freeze slot
 	PreMergeFreezeSlot,
+	/// A node did not answer a lightweight call in time
+	Timeout {
+		call: &'static str,
+		after: Duration,
+	},
+	/// A node does not provide the method called
+	///
+	/// Not a node failure: the client is not flagged as unhealthy and the call goes on to the
+	/// next one.
+	Unsupported {
+		call: &'static str,
+	},
+	/// A node answered with a 429, along with the `Retry-After` delay it sent if any
+	RateLimited {
+		retry_after: Option<Duration>,
+	},
+	/// A node is on another chain than the one indexed
+	InvalidChainId {
+		layer: &'static str,
+		expected: u64,
+		found: u64,
+	},
 }
 
+// Node errors are logged, the urls they quote are redacted as they can hold credentials
+
 impl From<eth2::Error> for Error {
 	fn from(error: eth2::Error) -> Self {
-		Error::Eth2(error)
+		match error {
+			eth2::Error::Reqwest(e) => Error::Eth2(eth2::Error::Reqwest(e.without_url())),
+			e => Error::Eth2(e),
+		}
 	}
 }
 
 impl From<web3::Error> for Error {
 	fn from(error: web3::Error) -> Self {
-		Error::Web3(error)
+		match error {
+			web3::Error::Transport(web3::error::TransportError::Message(message)) =>
+				Error::Web3(web3::Error::Transport(
+					web3::error::TransportError::Message(redact_urls_in(&message)),
+				)),
+			e => Error::Web3(e),
+		}
 	}
 }
 
@@ -60,12 +99,30 @@ impl From<diesel::result::Error> for Error {
 	}
 }
 
+impl From<PoolError> for Error {
+	fn from(error: PoolError) -> Self {
+		Error::DbPool(error)
+	}
+}
+
 impl From<SyncError> for Error {
 	fn from(error: SyncError) -> Self {
 		Error::Sync(error)
 	}
 }
 
+impl From<io::Error> for Error {
+	fn from(error: io::Error) -> Self {
+		Error::Io(error)
+	}
+}
+
+impl From<SchemaError> for Error {
+	fn from(error: SchemaError) -> Self {
+		Error::Schema(error)
+	}
+}
+
 impl Display for Error {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		match self {
@@ -75,6 +132,24 @@ impl Display for Error {
 				p
 			),
 			Self::MissingChainName => write!(f, "Invalid config. 'config_name' is required."),
+			Self::InvalidChainId {
+				layer,
+				expected,
+				found,
+			} => write!(
+				f,
+				"The {layer} node is on chain {found} instead of {expected}. Is it on the right network?"
+			),
+			Self::Timeout { call, after } => write!(f, "{call} call timed out after {after:?}"),
+			Self::Schema(error) => write!(f, "{error}"),
+			Self::Unsupported { call } => write!(f, "{call} is not supported by the node"),
+			Self::RateLimited {
+				retry_after: Some(after),
+			} => write!(
+				f,
+				"rate limited by the node, asked to retry after {after:?}"
+			),
+			Self::RateLimited { retry_after: None } => write!(f, "rate limited by the node"),
 			_ => write!(f, "{:?}", self),
 		}
 	}
@@ -0,0 +1,385 @@
+use std::{
+	env,
+	future::Future,
+	sync::{Arc, Mutex},
+	time::{Duration, Instant},
+};
+
+use log::{info, warn};
+use tokio::{sync::Semaphore, time::timeout};
+
+use crate::Error;
+
+/// Time during which a failing endpoint is skipped before being tried again
+const UNHEALTHY_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// A prioritized list of clients to the same node API
+///
+/// The first client is the primary, the others are fallbacks.
+/// Calls go to the first healthy client. When a call fails, its client is flagged as unhealthy
+/// for `UNHEALTHY_COOLDOWN` and the call is retried on the next one.
+/// Once the cooldown is over the primary is tried first again, so it takes back its role as soon as
+/// it recovers.
+///
+/// Health is shared between clones, and so is the concurrency limit: every task calling the node
+/// through a clone shares it.
+#[derive(Clone)]
+pub struct FailoverClient<C> {
+	clients: Vec<C>,
+	unhealthy_until: Arc<Mutex<Vec<Option<Instant>>>>,
+	/// Permits of the calls running at once, unlimited if None
+	permits: Option<Arc<Semaphore>>,
+	/// Time each client has to answer a `call_timed`, unlimited if None
+	request_timeout: Option<Duration>,
+}
+
+impl<C: Clone> FailoverClient<C> {
+	/// Create a new failover client
+	///
+	/// # Panics
+	/// If `clients` is empty
+	pub fn new(clients: Vec<C>) -> Self {
+		assert!(!clients.is_empty(), "at least one node client is required");
+
+		let unhealthy_until = Arc::new(Mutex::new(vec![None; clients.len()]));
+
+		FailoverClient {
+			clients,
+			unhealthy_until,
+			permits: None,
+			request_timeout: None,
+		}
+	}
+
+	/// Run at most `max` calls at once, the others waiting for one to finish
+	///
+	/// A call holds its permit while it fails over, so it counts as a single request.
+	pub fn limiting_concurrency(self, max: Option<usize>) -> Self {
+		FailoverClient {
+			permits: max.map(|m| Arc::new(Semaphore::new(m))),
+			..self
+		}
+	}
+
+	/// Give each client at most `timeout` to answer a `call_timed`
+	///
+	/// Meant for the lightweight calls, such as the head queries, that must not hang for as long
+	/// as the clients' own timeout allows the heavier ones to.
+	pub fn timing_out_requests(self, timeout: Duration) -> Self {
+		FailoverClient {
+			request_timeout: Some(timeout),
+			..self
+		}
+	}
+
+	/// Same as `call`, failing over with `Error::Timeout` if a client does not answer in time
+	///
+	/// `what` names the call in the error.
+	pub async fn call_timed<T, F, Fut>(&self, what: &'static str, f: F) -> Result<T, Error>
+	where
+		F: Fn(C) -> Fut,
+		Fut: Future<Output = Result<T, Error>>,
+	{
+		let request_timeout = self.request_timeout;
+
+		self.call(|c| {
+			let fut = f(c);
+			async move {
+				match request_timeout {
+					Some(after) => timeout(after, fut)
+						.await
+						.unwrap_or(Err(Error::Timeout { call: what, after })),
+					None => fut.await,
+				}
+			}
+		})
+		.await
+	}
+
+	/// Run `f` against each client, by priority, until one of them succeeds
+	///
+	/// Unhealthy clients are skipped, unless all of them are.
+	/// A client answering `Error::Unsupported` is skipped too, but stays healthy.
+	/// Return the error of the last client that failed if none succeeded, or `Error::Unsupported`
+	/// if every client tried declined the call.
+	pub async fn call<T, F, Fut>(&self, f: F) -> Result<T, Error>
+	where
+		F: Fn(C) -> Fut,
+		Fut: Future<Output = Result<T, Error>>,
+	{
+		let _permit = match &self.permits {
+			// Safe to unwrap because the semaphore is never closed
+			Some(permits) => Some(permits.acquire().await.unwrap()),
+			None => None,
+		};
+		let mut last_error = None;
+		let mut declined = None;
+
+		for index in self.candidates() {
+			match f(self.clients[index].clone()).await {
+				Ok(r) => {
+					self.mark_healthy(index);
+					if last_error.is_some() {
+						info!("failed over to node endpoint #{index}");
+					}
+					return Ok(r)
+				},
+				Err(err @ Error::Unsupported { .. }) => {
+					warn!("node endpoint #{index} declined: {err}");
+					declined = Some(err);
+				},
+				Err(err) => {
+					warn!("node endpoint #{index} failed: {err}");
+					self.mark_unhealthy(index);
+					last_error = Some(err);
+				},
+			}
+		}
+
+		// Safe to unwrap because there is always at least one candidate
+		Err(last_error.or(declined).unwrap())
+	}
+
+	// Return the indexes of the clients to try, by priority
+	fn candidates(&self) -> Vec<usize> {
+		let now = Instant::now();
+		let unhealthy_until = self.unhealthy_until.lock().unwrap();
+
+		let healthy: Vec<usize> = unhealthy_until
+			.iter()
+			.enumerate()
+			.filter(|(_, until)| until.map_or(true, |u| u <= now))
+			.map(|(index, _)| index)
+			.collect();
+
+		if healthy.is_empty() {
+			(0..self.clients.len()).collect()
+		} else {
+			healthy
+		}
+	}
+
+	fn mark_healthy(&self, index: usize) {
+		let mut unhealthy_until = self.unhealthy_until.lock().unwrap();
+		if unhealthy_until[index].take().is_some() {
+			info!("node endpoint #{index} recovered");
+		}
+	}
+
+	fn mark_unhealthy(&self, index: usize) {
+		let mut unhealthy_until = self.unhealthy_until.lock().unwrap();
+		unhealthy_until[index] = Some(Instant::now() + UNHEALTHY_COOLDOWN);
+	}
+}
+
+/// Read the node urls from the environment
+///
+/// `primary` is required and holds the primary url.
+/// `fallbacks` is optional and holds a comma separated list of fallback urls.
+pub fn urls_from_env(primary: &str, fallbacks: &str) -> Result<Vec<String>, Error> {
+	let mut urls = vec![env::var(primary)?];
+
+	if let Ok(raw_fallbacks) = env::var(fallbacks) {
+		urls.extend(
+			raw_fallbacks
+				.split(',')
+				.map(str::trim)
+				.filter(|u| !u.is_empty())
+				.map(String::from),
+		);
+	}
+
+	Ok(urls)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::atomic::{AtomicUsize, Ordering};
+
+	use futures::future::join_all;
+	use tokio::time::sleep;
+
+	use super::*;
+
+	/// Calls started at once by the concurrency tests
+	const CONCURRENT_CALLS: usize = 6;
+
+	fn unsupported() -> Error {
+		Error::Unsupported { call: "test" }
+	}
+
+	fn is_unhealthy(client: &FailoverClient<usize>, index: usize) -> bool {
+		client.unhealthy_until.lock().unwrap()[index].is_some()
+	}
+
+	#[tokio::test]
+	async fn a_declining_client_fails_over_but_stays_healthy() {
+		let client = FailoverClient::new(vec![0, 1]);
+
+		let answered = client
+			.call(|c| async move {
+				match c {
+					0 => Err(unsupported()),
+					c => Ok(c),
+				}
+			})
+			.await
+			.unwrap();
+
+		assert_eq!(answered, 1);
+		assert!(!is_unhealthy(&client, 0));
+	}
+
+	#[tokio::test]
+	async fn unsupported_only_once_every_client_declined() {
+		let client = FailoverClient::new(vec![0, 1]);
+
+		let result: Result<(), _> = client.call(|_| async { Err(unsupported()) }).await;
+
+		assert!(matches!(result, Err(Error::Unsupported { .. })));
+		assert!(!is_unhealthy(&client, 0));
+		assert!(!is_unhealthy(&client, 1));
+	}
+
+	#[tokio::test]
+	async fn a_failure_wins_over_a_decline() {
+		let client = FailoverClient::new(vec![0, 1]);
+
+		let result: Result<(), _> = client
+			.call(|c| async move {
+				match c {
+					0 => Err(Error::MissingChainName),
+					_ => Err(unsupported()),
+				}
+			})
+			.await;
+
+		assert!(matches!(result, Err(Error::MissingChainName)));
+		assert!(is_unhealthy(&client, 0));
+	}
+
+	fn healthy(_: usize) -> Result<(), Error> {
+		Ok(())
+	}
+
+	fn primary_down(c: usize) -> Result<(), Error> {
+		match c {
+			0 => Err(Error::MissingChainName),
+			_ => Ok(()),
+		}
+	}
+
+	// Return the indexes of the clients `f` is run against by a call to `client`, in order
+	async fn called<F>(client: &FailoverClient<usize>, f: F) -> Vec<usize>
+	where
+		F: Fn(usize) -> Result<(), Error>,
+	{
+		let called = Mutex::new(vec![]);
+		client
+			.call(|c| {
+				called.lock().unwrap().push(c);
+				let result = f(c);
+				async move { result }
+			})
+			.await
+			.ok();
+
+		called.into_inner().unwrap()
+	}
+
+	// Return the most calls of `client` seen running at once among `CONCURRENT_CALLS`
+	async fn max_in_flight(client: FailoverClient<usize>) -> usize {
+		let in_flight = Arc::new(AtomicUsize::new(0));
+		let max_in_flight = Arc::new(AtomicUsize::new(0));
+
+		join_all((0..CONCURRENT_CALLS).map(|_| {
+			client.call(|_| {
+				let in_flight = in_flight.clone();
+				let max_in_flight = max_in_flight.clone();
+				async move {
+					let running = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+					max_in_flight.fetch_max(running, Ordering::SeqCst);
+					sleep(Duration::from_millis(20)).await;
+					in_flight.fetch_sub(1, Ordering::SeqCst);
+					Ok::<_, Error>(())
+				}
+			})
+		}))
+		.await;
+
+		max_in_flight.load(Ordering::SeqCst)
+	}
+
+	#[tokio::test]
+	async fn concurrent_calls_are_bounded_by_the_limit() {
+		let client = FailoverClient::new(vec![0]).limiting_concurrency(Some(2));
+
+		assert_eq!(max_in_flight(client).await, 2);
+	}
+
+	#[tokio::test]
+	async fn concurrent_calls_are_unbounded_without_a_limit() {
+		let client = FailoverClient::new(vec![0]).limiting_concurrency(None);
+
+		assert_eq!(max_in_flight(client).await, CONCURRENT_CALLS);
+	}
+
+	#[tokio::test]
+	async fn a_call_failing_over_holds_a_single_permit() {
+		let client = FailoverClient::new(vec![0, 1]).limiting_concurrency(Some(1));
+		let permits = client.permits.clone().unwrap();
+
+		let tried = called(&client, |c| {
+			assert_eq!(permits.available_permits(), 0);
+			primary_down(c)
+		});
+
+		assert_eq!(tried.await, vec![0, 1]);
+		assert_eq!(permits.available_permits(), 1);
+	}
+
+	#[tokio::test]
+	async fn a_failing_client_is_skipped_until_its_cooldown_is_over() {
+		let client = FailoverClient::new(vec![0, 1, 2]);
+
+		assert_eq!(called(&client, primary_down).await, vec![0, 1]);
+		assert!(is_unhealthy(&client, 0));
+		assert_eq!(called(&client, healthy).await, vec![1]);
+
+		// The cooldown is over
+		client.unhealthy_until.lock().unwrap()[0] = Some(Instant::now());
+		assert_eq!(called(&client, healthy).await, vec![0]);
+		assert!(!is_unhealthy(&client, 0));
+	}
+
+	#[tokio::test]
+	async fn every_client_is_tried_once_all_are_unhealthy() {
+		let client = FailoverClient::new(vec![0, 1]);
+
+		assert_eq!(
+			called(&client, |_| Err(Error::MissingChainName)).await,
+			vec![0, 1]
+		);
+		assert!(is_unhealthy(&client, 0) && is_unhealthy(&client, 1));
+		assert_eq!(called(&client, primary_down).await, vec![0, 1]);
+		assert!(!is_unhealthy(&client, 1));
+	}
+
+	#[tokio::test]
+	async fn a_client_not_answering_in_time_is_failed_over() {
+		let client = FailoverClient::new(vec![0, 1]).timing_out_requests(Duration::from_millis(20));
+
+		let answered = client
+			.call_timed("test", |c| async move {
+				if c == 0 {
+					sleep(Duration::from_secs(60)).await;
+				}
+				Ok::<_, Error>(c)
+			})
+			.await
+			.unwrap();
+
+		assert_eq!(answered, 1);
+		assert!(is_unhealthy(&client, 0));
+	}
+}
@@ -5,20 +5,42 @@ mod args;
 mod client_consensus;
 mod client_execution;
 mod error;
+mod failover;
+mod metrics;
+mod node_health;
 mod sync;
 
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
 use args::Args;
 use clap::StructOpt;
 use dotenv::dotenv;
 use error::*;
-use eth2::BeaconNodeHttpClient;
-use log::info;
-use sync::validators::update_validators;
-use tokio::join;
+use kiln_postgres::{get_connection, NewStat, PgConnectionPool, SlotClock, Stat, StatKey};
+use log::{debug, error, info};
+use node_health::{with_node_backoff, with_retries};
+use sync::{
+	pruning::prune_old_slots,
+	reorg::{find_reorged_slots, rewind_reorged_slots, RewindLimiter},
+	reprocess::watch_reprocess_jobs,
+	slot_timestamps::backfill_slot_timestamps,
+	validator_counts::backfill_validator_counts,
+	validators::update_validators,
+	watchdog::watch_sync_progress,
+};
+use tokio::{sync::watch, time::sleep};
 
-use crate::sync::{ConsensusSyncer, DbSyncer, ExecutionSyncer};
+use crate::sync::{
+	BlockTimeEstimator, ConsensusSyncer, ExecutionSyncer, Pause, Supervisor, SyncError,
+};
 
 const FIRST_SLOT_WITH_EXEC_BLOCK: u64 = 29151;
+/// Id of the Kiln chain, shared by both layers
+const KILN_CHAIN_ID: u64 = 1337802;
+/// Delay before checking again a node head refused as bogus
+const BOGUS_HEAD_RETRY_DELAY: Duration = Duration::from_secs(12);
+/// Delay before checking again whether a paused sync is resumed
+const PAUSED_RETRY_DELAY: Duration = Duration::from_secs(5);
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
@@ -30,9 +52,25 @@ async fn main() -> Result<(), Error> {
 		return Err(Error::PreMergeFreezeSlot)
 	}
 
+	if let Some(address) = args.metrics_address() {
+		tokio::spawn(async move {
+			if let Err(err) = metrics::serve(address).await {
+				error!("metrics server stopped: {err}");
+			}
+		});
+	}
+
 	let conn_pool = kiln_postgres::connexion_pool();
-	let eth2 = client_consensus::new_client()?;
-	let web3 = client_execution::new_client()?;
+	if let Err(err) = kiln_postgres::check_schema_version(&get_connection(&conn_pool)?) {
+		error!("{err}");
+		return Err(err.into())
+	}
+	let eth2 = client_consensus::new_client(args.node_timeout())?
+		.limiting_concurrency(args.max_node_requests())
+		.timing_out_requests(args.node_request_timeout());
+	let web3 = client_execution::new_client()?
+		.limiting_concurrency(args.max_node_requests())
+		.timing_out_requests(args.node_request_timeout());
 
 	let spec = client_consensus::get_config_spec(&eth2).await?;
 	let config = spec.config;
@@ -47,28 +85,227 @@ async fn main() -> Result<(), Error> {
 		None => return Err(Error::MissingChainName),
 		_ => {},
 	}
+	// Both nodes must be on Kiln, or the data of another network would be stored as Kiln's
+	if config.deposit_chain_id != KILN_CHAIN_ID {
+		return Err(Error::InvalidChainId {
+			layer: "consensus",
+			expected: KILN_CHAIN_ID,
+			found: config.deposit_chain_id,
+		})
+	}
+	let execution_chain_id = client_execution::get_chain_id(&web3).await?;
+	if execution_chain_id != KILN_CHAIN_ID {
+		return Err(Error::InvalidChainId {
+			layer: "execution",
+			expected: KILN_CHAIN_ID,
+			found: execution_chain_id,
+		})
+	}
+
+	let slot_clock = SlotClock::new(
+		client_consensus::get_genesis_time(&eth2).await?,
+		config.seconds_per_slot,
+	);
+
+	// The execution syncers only count the issuers they store, start from the ones already stored
+	init_issuers_count(&conn_pool)?;
+
+	// Stop every syncer on ctrl-c
+	let (shutdown_sender, shutdown) = watch::channel(false);
+	tokio::spawn(async move {
+		if tokio::signal::ctrl_c().await.is_ok() {
+			info!("shutdown requested");
+			shutdown_sender.send(true).ok();
+		}
+	});
+
+	tokio::spawn(watch_reprocess_jobs(
+		conn_pool.clone(),
+		eth2.clone(),
+		web3.clone(),
+		args.sync_options(),
+		shutdown.clone(),
+	));
+
+	let layers = args.layers();
+	if layers.consensus() {
+		tokio::spawn(backfill_validator_counts(
+			conn_pool.clone(),
+			eth2.clone(),
+			args.validators_count_check(),
+			shutdown.clone(),
+		));
+		tokio::spawn(backfill_slot_timestamps(
+			conn_pool.clone(),
+			eth2.clone(),
+			shutdown.clone(),
+		));
+	}
+	if let Some(retained) = args.retained_slots() {
+		tokio::spawn(prune_old_slots(
+			conn_pool.clone(),
+			retained,
+			shutdown.clone(),
+		));
+	}
+	if let Some(threshold) = args.stall_threshold() {
+		if layers.consensus() {
+			tokio::spawn(watch_sync_progress(
+				ConsensusSyncer::new(conn_pool.clone(), eth2.clone()),
+				conn_pool.clone(),
+				eth2.clone(),
+				threshold,
+				args.exit_on_stall(),
+				shutdown.clone(),
+			));
+		}
+		if layers.execution() {
+			tokio::spawn(watch_sync_progress(
+				ExecutionSyncer::new(conn_pool.clone(), web3.clone()),
+				conn_pool.clone(),
+				eth2.clone(),
+				threshold,
+				args.exit_on_stall(),
+				shutdown.clone(),
+			));
+		}
+	}
 
 	let mut consensus_height: u64;
+	// Last head accepted, to detect a node suddenly reporting an absurd one
+	let mut previous_head = Stat::get(&get_connection(&conn_pool)?, StatKey::ConsensusNodeHeight)?;
+
+	let mut pause = Pause::new(conn_pool.clone());
+	// Head synced by the last bumps, nothing is left to do until the node head moves past it
+	let mut synced_head = None;
+	let mut rewind_limiter =
+		RewindLimiter::new(args.max_reorg_rewinds(), args.reorg_rewind_cooldown());
+	let block_time = BlockTimeEstimator::new(
+		conn_pool.clone(),
+		Duration::from_secs(config.seconds_per_slot),
+	);
 
 	// Sync db with chain height
 	// Will loop until heigh rejoin `freeze_at`
+	// Bumps are awaited before polling the head again, so at most one runs per layer at a time
 	loop {
-		consensus_height = client_consensus::get_head_height(&eth2).await?;
+		if *shutdown.borrow() {
+			break
+		}
+		// Checked between bumps, a pause lets the running ones finish
+		if pause.check() {
+			sleep(PAUSED_RETRY_DELAY).await;
+			continue
+		}
+
+		// Every attempt is recorded, the backoff waiting out an unreachable node until shutdown
+		let client = &eth2;
+		let head = with_retries("head height", args.head_retries(), || {
+			with_node_backoff("head height", &shutdown, || async move {
+				let head = client_consensus::get_head_height(client).await;
+				metrics::record_head_query(head.is_ok());
+				head
+			})
+		})
+		.await;
+		consensus_height = match head {
+			Err(Error::Sync(SyncError::Cancelled)) => break,
+			head => head?,
+		};
+		if synced_head == Some(consensus_height) {
+			sleep(block_time.estimate_block_time()?).await;
+			continue
+		}
+		if let Some(previous) = previous_head {
+			// The chain went on while the indexer was down, the previous head can be far behind
+			let reference = wall_clock_slot(&slot_clock).map_or(previous, |s| s.max(previous));
+			if consensus_height > reference + args.max_head_jump() {
+				error!(
+					"node head {consensus_height} is more than {} slots above {reference}, the \
+					 previous head or current slot, refusing to sync it: is the node on the right \
+					 network?",
+					args.max_head_jump()
+				);
+				sleep(BOGUS_HEAD_RETRY_DELAY).await;
+				continue
+			}
+		}
+		previous_head = Some(consensus_height);
+		// Published for the api live updates
+		NewStat::new(StatKey::ConsensusNodeHeight, consensus_height)
+			.upsert(&get_connection(&conn_pool)?)?;
 		let max_consensus_height = std::cmp::min(consensus_height, args.freeze_at());
 
-		update_validators(conn_pool.clone(), &eth2, max_consensus_height).await?;
+		if layers.consensus() {
+			update_validators(conn_pool.clone(), &eth2, max_consensus_height).await?;
+		}
 
+		// The block of the highest slot synced, in block numbers
 		let max_exec_height = find_last_exec_block(&eth2, max_consensus_height).await?;
 
-		let consensus_syncer = ConsensusSyncer::new(conn_pool.clone(), eth2.clone());
-		let execution_syncer = ExecutionSyncer::new(conn_pool.clone(), web3.clone());
+		let consensus_syncer = ConsensusSyncer::new(conn_pool.clone(), eth2.clone())
+			.storing_raw_payloads(args.store_raw_payloads())
+			.checking_validators_count(args.validators_count_check());
+		let execution_syncer = ExecutionSyncer::new(conn_pool.clone(), web3.clone())
+			.storing_raw_payloads(args.store_raw_payloads())
+			.tracing_internal_calls(args.trace_internal_calls())
+			.fetching_blocks_by_batch(args.execution_batch_size());
+
+		// Published for the api to only count transactions in finalized blocks, if configured to
+		let finalized_slot = consensus_syncer.get_node_finalized_height().await?;
+		let finalized_block = find_last_exec_block(&eth2, finalized_slot).await?;
+		NewStat::new(StatKey::FinalizedBlockNumber, finalized_block)
+			.upsert(&get_connection(&conn_pool)?)?;
+
+		let mut supervisor = Supervisor::new(shutdown.clone());
+		if layers.consensus() {
+			supervisor.spawn(
+				consensus_syncer,
+				args.first_slot().take(),
+				max_consensus_height,
+				args.sync_options(),
+			);
+		}
+		if layers.execution() {
+			supervisor.spawn(
+				execution_syncer,
+				args.first_block().take(),
+				max_exec_height,
+				args.sync_options(),
+			);
+		}
+
+		let results = supervisor.join_all().await;
+
+		if *shutdown.borrow() {
+			break
+		}
+		for result in results {
+			result?;
+		}
+		synced_head = Some(consensus_height);
 
-		let (res_consensus, res_execution) = join!(
-			consensus_syncer.bump(args.first_slot().take(), max_consensus_height),
-			execution_syncer.bump(args.first_block().take(), max_exec_height),
-		);
-		res_execution?;
-		if res_consensus? == args.freeze_at() {
+		if layers.consensus() && args.reorg_check_depth() > 0 {
+			let syncer = ConsensusSyncer::new(conn_pool.clone(), eth2.clone());
+			let reorged = find_reorged_slots(
+				&syncer,
+				&conn_pool,
+				max_consensus_height,
+				args.reorg_check_depth(),
+			)
+			.await?;
+			if !reorged.is_empty() && rewind_limiter.allow(Instant::now()) {
+				rewind_reorged_slots(
+					&conn_pool,
+					&eth2,
+					layers.execution().then(|| &web3),
+					&reorged,
+					&args.sync_options(),
+				)
+				.await?;
+			}
+		}
+		if max_consensus_height == args.freeze_at() {
 			break
 		}
 	}
@@ -76,11 +313,31 @@ async fn main() -> Result<(), Error> {
 	Ok(())
 }
 
+// Return the slot going on now, None before genesis or if the system clock is before the epoch
+fn wall_clock_slot(slot_clock: &SlotClock) -> Option<u64> {
+	let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+
+	slot_clock.slot_at_timestamp(now.as_secs())
+}
+
+// Count the stored issuers once, if not done yet
+fn init_issuers_count(conn_pool: &PgConnectionPool) -> Result<(), Error> {
+	let conn = get_connection(conn_pool)?;
+	if Stat::get(&conn, StatKey::IssuersCount)?.is_some() {
+		return Ok(())
+	}
+
+	info!("counting the stored issuers");
+	NewStat::recount_issuers(&conn)?;
+
+	Ok(())
+}
+
 // Query consensus layer for slot between `height` and 0 until it find one with a non None
 // execution_payload
-async fn find_last_exec_block(eth2: &BeaconNodeHttpClient, height: u64) -> Result<u64, Error> {
+async fn find_last_exec_block(eth2: &client_consensus::Client, height: u64) -> Result<u64, Error> {
 	for h in (0..height + 1).rev() {
-		info!("looking for execution payload in slot {h}");
+		debug!("looking for execution payload in slot {h}");
 		let slot = match client_consensus::get_block(eth2, h).await? {
 			Some(s) => s,
 			None => continue,
@@ -0,0 +1,124 @@
+use std::{
+	net::SocketAddr,
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::{info, warn};
+use prometheus::{
+	register_int_counter, register_int_counter_vec, register_int_gauge, Encoder, IntCounter,
+	IntCounterVec, IntGauge, TextEncoder,
+};
+use tokio::{
+	io::{AsyncReadExt, AsyncWriteExt},
+	net::TcpListener,
+};
+
+use crate::Error;
+
+lazy_static! {
+	/// Number of times a supervised syncer was restarted after panicking
+	pub static ref SYNCER_RESTARTS: IntCounterVec = register_int_counter_vec!(
+		"syncer_restarts_total",
+		"Number of times a syncer was restarted after panicking",
+		&["syncer"]
+	)
+	.unwrap();
+	/// Number of times a height was retried after failing to be created
+	pub static ref SYNC_RETRIES: IntCounterVec = register_int_counter_vec!(
+		"sync_retries_total",
+		"Number of times a height was retried after failing to be created",
+		&["syncer"]
+	)
+	.unwrap();
+	/// Number of heights still failing after all their retries
+	pub static ref SYNC_FAILED_HEIGHTS: IntCounterVec = register_int_counter_vec!(
+		"sync_failed_heights_total",
+		"Number of heights still failing after all their retries",
+		&["syncer"]
+	)
+	.unwrap();
+	/// Number of bumps asked to start above the node head, e.g. after a reorg shortened the chain
+	pub static ref SYNC_HEAD_BEHIND: IntCounterVec = register_int_counter_vec!(
+		"sync_head_behind_total",
+		"Number of bumps asked to start above the node head",
+		&["syncer"]
+	)
+	.unwrap();
+	/// Number of stalls reported by the sync watchdog
+	pub static ref SYNC_STALLS: IntCounterVec = register_int_counter_vec!(
+		"sync_stalls_total",
+		"Number of times the database height did not move for the stall threshold while the node \
+		 head did",
+		&["syncer"]
+	)
+	.unwrap();
+	/// Number of stored slots found holding another block than the node's
+	pub static ref REORGED_SLOTS: IntCounter = register_int_counter!(
+		"reorged_slots_total",
+		"Number of stored slots found holding another block than the node's"
+	)
+	.unwrap();
+	/// Whether the last head query to the consensus node succeeded, as 1 or 0
+	pub static ref NODE_UP: IntGauge = register_int_gauge!(
+		"node_up",
+		"Whether the last head query to the consensus node succeeded"
+	)
+	.unwrap();
+	/// Seconds since the UNIX epoch of the last successful head query, 0 until there is one
+	pub static ref NODE_LAST_SUCCESS: IntGauge = register_int_gauge!(
+		"node_last_success_timestamp_seconds",
+		"Time of the last successful head query to the consensus node"
+	)
+	.unwrap();
+}
+
+/// Record the outcome of a head query of the sync loop, every attempt included
+///
+/// `node_up` follows the node between two scrapes, even if no one probes the api.
+pub fn record_head_query(success: bool) {
+	NODE_UP.set(success as i64);
+	if success {
+		// The clock is never before the epoch
+		let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+		NODE_LAST_SUCCESS.set(now.as_secs() as i64);
+	}
+}
+
+/// Serve the Prometheus metrics over HTTP
+///
+/// Every request, whatever its path, is answered with the content of the default registry in the
+/// text exposition format.
+pub async fn serve(address: SocketAddr) -> Result<(), Error> {
+	let listener = TcpListener::bind(address).await?;
+	info!("serving metrics on {address}");
+
+	loop {
+		let (mut stream, _) = listener.accept().await?;
+
+		tokio::spawn(async move {
+			// The request content is irrelevant, read it so the client is not reset
+			let mut request = [0; 1024];
+			let _ = stream.read(&mut request).await;
+
+			let encoder = TextEncoder::new();
+			let mut body = vec![];
+			if let Err(err) = encoder.encode(&prometheus::gather(), &mut body) {
+				warn!("failed to encode metrics: {err}");
+				return
+			}
+
+			let header = format!(
+				"HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n",
+				encoder.format_type(),
+				body.len()
+			);
+			let written = match stream.write_all(header.as_bytes()).await {
+				Ok(()) => stream.write_all(&body).await,
+				Err(err) => Err(err),
+			};
+			if let Err(err) = written {
+				warn!("failed to serve metrics: {err}");
+			}
+		});
+	}
+}
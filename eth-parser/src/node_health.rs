@@ -0,0 +1,440 @@
+use std::{
+	future::Future,
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::warn;
+use tokio::{select, sync::watch, time::sleep};
+use web3::error::TransportError;
+
+use crate::{
+	sync::{wait_for_shutdown, SyncError},
+	Error,
+};
+
+/// Wait before calling an unreachable node again
+const UNREACHABLE_BACKOFF: Duration = Duration::from_secs(5);
+/// Wait before calling a node that rate limited us again, when it did not say how long
+const RATE_LIMITED_BACKOFF: Duration = Duration::from_secs(30);
+/// Bounds of the `Retry-After` delays waited, so a node cannot have us spin nor stall for good
+const MIN_RETRY_AFTER: Duration = Duration::from_secs(1);
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(10 * 60);
+/// Wait before calling a node that is still syncing again
+const SYNCING_BACKOFF: Duration = Duration::from_secs(60);
+/// Wait before a new attempt of a call retried by `with_retries`
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// What went wrong when calling a node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeErrorKind {
+	/// The node could not be reached, or did not answer in time
+	Unreachable,
+	/// The node answered with a 429, along with the `Retry-After` delay it sent if any
+	RateLimited { retry_after: Option<Duration> },
+	/// The node is too far behind the chain head
+	Syncing,
+	/// Anything else, not expected to go away by waiting
+	Other,
+}
+
+impl NodeErrorKind {
+	pub fn classify(error: &Error) -> NodeErrorKind {
+		match error {
+			Error::Eth2(eth2::Error::Reqwest(e)) if e.is_connect() || e.is_timeout() =>
+				NodeErrorKind::Unreachable,
+			Error::RateLimited { retry_after } => NodeErrorKind::RateLimited {
+				retry_after: *retry_after,
+			},
+			Error::Eth2(e) if e.status().map(|s| s.as_u16()) == Some(429) =>
+				NodeErrorKind::RateLimited { retry_after: None },
+			Error::Web3(web3::Error::Unreachable) | Error::Timeout { .. } =>
+				NodeErrorKind::Unreachable,
+			Error::Web3(web3::Error::Transport(TransportError::Code(429))) =>
+				NodeErrorKind::RateLimited { retry_after: None },
+			Error::Sync(SyncError::NodeSyncing) => NodeErrorKind::Syncing,
+			_ => NodeErrorKind::Other,
+		}
+	}
+
+	/// Return how long to wait before calling the node again, or None if the error is permanent
+	///
+	/// A rate limited call waits for the `Retry-After` delay of the 429. Only the consensus head
+	/// query reads it, the other calls go through clients dropping the response headers and fall
+	/// back to `RATE_LIMITED_BACKOFF`.
+	pub fn backoff(&self) -> Option<Duration> {
+		match self {
+			NodeErrorKind::Unreachable => Some(UNREACHABLE_BACKOFF),
+			NodeErrorKind::RateLimited {
+				retry_after: Some(after),
+			} => Some((*after).clamp(MIN_RETRY_AFTER, MAX_RETRY_AFTER)),
+			NodeErrorKind::RateLimited { retry_after: None } => Some(RATE_LIMITED_BACKOFF),
+			NodeErrorKind::Syncing => Some(SYNCING_BACKOFF),
+			NodeErrorKind::Other => None,
+		}
+	}
+}
+
+/// Parse the value of a `Retry-After` header, relative to `now` if it is a date
+///
+/// https://httpwg.org/specs/rfc9110.html#field.retry-after
+pub fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+	let value = value.trim();
+	match value.parse::<u64>() {
+		Ok(seconds) => Some(Duration::from_secs(seconds)),
+		// A date already past asks for an immediate retry
+		Err(_) => parse_http_date(value).map(|date| date.duration_since(now).unwrap_or_default()),
+	}
+}
+
+/// Parse an IMF-fixdate, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`
+///
+/// The obsolete date formats are not supported, senders must not generate them.
+fn parse_http_date(value: &str) -> Option<SystemTime> {
+	const MONTHS: [&str; 12] = [
+		"Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+	];
+
+	let (_weekday, date) = value.split_once(", ")?;
+	let parts: Vec<&str> = date.split(' ').collect();
+	let (day, month, year, time) = match parts.as_slice() {
+		[day, month, year, time, "GMT"] => (*day, *month, *year, *time),
+		_ => return None,
+	};
+	let day: u64 = day.parse().ok().filter(|d| (1..=31).contains(d))?;
+	let month = MONTHS.iter().position(|m| *m == month)? as u64 + 1;
+	let year: u64 = year.parse().ok().filter(|y| *y >= 1970)?;
+
+	let time: Vec<u64> = time.split(':').map(|t| t.parse().ok()).collect::<Option<_>>()?;
+	let seconds_of_day = match time.as_slice() {
+		[h, m, s] if *h < 24 && *m < 60 && *s < 61 => h * 3600 + m * 60 + s,
+		_ => return None,
+	};
+
+	let seconds = days_from_civil(year, month, day) * 86400 + seconds_of_day;
+	Some(UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+/// Number of days from the unix epoch to a date of the Gregorian calendar, from 1970 on
+///
+/// http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+	// Years start in March, for the leap day to end them
+	let year = if month <= 2 { year - 1 } else { year };
+	let era = year / 400;
+	let year_of_era = year - era * 400;
+	let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+	let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+	era * 146097 + day_of_era - 719468
+}
+
+/// Call `f` until it succeeds or fails with a permanent error
+///
+/// Transient node errors are waited out according to their kind. Fail with `SyncError::Cancelled`
+/// as soon as `true` is sent on `shutdown`, a node down for good must not hold the shutdown back.
+pub async fn with_node_backoff<T, F, Fut>(
+	what: &str,
+	shutdown: &watch::Receiver<bool>,
+	f: F,
+) -> Result<T, Error>
+where
+	F: Fn() -> Fut,
+	Fut: Future<Output = Result<T, Error>>,
+{
+	let mut shutdown = shutdown.clone();
+	loop {
+		if *shutdown.borrow() {
+			return Err(Error::Sync(SyncError::Cancelled))
+		}
+
+		let err = match f().await {
+			Ok(r) => return Ok(r),
+			Err(err) => err,
+		};
+
+		let kind = NodeErrorKind::classify(&err);
+		match kind.backoff() {
+			Some(backoff) => {
+				warn!("{what}: node error classified as {kind:?}, retrying in {backoff:?}: {err}");
+				select! {
+					_ = sleep(backoff) => {},
+					_ = wait_for_shutdown(&mut shutdown) =>
+						return Err(Error::Sync(SyncError::Cancelled)),
+				}
+			},
+			None => return Err(err),
+		}
+	}
+}
+
+/// Call `f` until it succeeds, retrying it at most `retries` times
+///
+/// For the errors `with_node_backoff` gives up on, which are often gone on the next call. A
+/// cancelled call is never retried.
+pub async fn with_retries<T, F, Fut>(what: &str, retries: u32, f: F) -> Result<T, Error>
+where
+	F: Fn() -> Fut,
+	Fut: Future<Output = Result<T, Error>>,
+{
+	let mut attempt = 0;
+	loop {
+		match f().await {
+			Ok(r) => return Ok(r),
+			Err(err) if attempt < retries && !matches!(err, Error::Sync(SyncError::Cancelled)) => {
+				attempt += 1;
+				warn!("{what}: failed, retry {attempt}/{retries} in {RETRY_DELAY:?}: {err}");
+				sleep(RETRY_DELAY).await;
+			},
+			Err(err) => return Err(err),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::atomic::{AtomicU32, Ordering};
+
+	use tokio::time::timeout;
+
+	use super::*;
+
+	/// Longer than any test takes, far shorter than the backoffs
+	const TEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+	/// Fails `failures` times with a permanent error, then succeeds with the number of calls
+	struct FlakyNode {
+		failures: u32,
+		calls: AtomicU32,
+	}
+
+	impl FlakyNode {
+		fn new(failures: u32) -> Self {
+			FlakyNode {
+				failures,
+				calls: AtomicU32::new(0),
+			}
+		}
+
+		async fn call(&self) -> Result<u32, Error> {
+			let calls = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+			if calls <= self.failures {
+				Err(Error::MissingChainName)
+			} else {
+				Ok(calls)
+			}
+		}
+
+		fn calls(&self) -> u32 {
+			self.calls.load(Ordering::SeqCst)
+		}
+	}
+
+	#[tokio::test]
+	async fn retries_until_the_node_recovers() {
+		let node = FlakyNode::new(2);
+
+		assert_eq!(with_retries("test", 2, || node.call()).await.unwrap(), 3);
+		assert_eq!(node.calls(), 3);
+	}
+
+	#[tokio::test]
+	async fn gives_up_once_the_retries_are_exhausted() {
+		let node = FlakyNode::new(2);
+
+		assert!(matches!(
+			with_retries("test", 1, || node.call()).await,
+			Err(Error::MissingChainName)
+		));
+		assert_eq!(node.calls(), 2);
+	}
+
+	#[tokio::test]
+	async fn calls_once_without_retries() {
+		let failing = FlakyNode::new(1);
+		let healthy = FlakyNode::new(0);
+
+		assert!(with_retries("test", 0, || failing.call()).await.is_err());
+		assert_eq!(failing.calls(), 1);
+		assert_eq!(with_retries("test", 3, || healthy.call()).await.unwrap(), 1);
+		assert_eq!(healthy.calls(), 1);
+	}
+
+	async fn syncing_node() -> Result<(), Error> {
+		Err(Error::Sync(SyncError::NodeSyncing))
+	}
+
+	#[tokio::test]
+	async fn backoff_is_cut_short_by_a_shutdown() {
+		let (shutdown_sender, shutdown) = watch::channel(false);
+		tokio::spawn(async move {
+			sleep(Duration::from_millis(50)).await;
+			shutdown_sender.send(true).ok();
+		});
+
+		let result = timeout(
+			TEST_TIMEOUT,
+			with_node_backoff("test", &shutdown, syncing_node),
+		)
+		.await
+		.expect("the backoff held the shutdown back");
+
+		assert!(matches!(result, Err(Error::Sync(SyncError::Cancelled))));
+	}
+
+	#[tokio::test]
+	async fn node_is_not_called_once_shutdown_is_requested() {
+		let (_shutdown_sender, shutdown) = watch::channel(true);
+		let node = FlakyNode::new(0);
+
+		assert!(matches!(
+			with_node_backoff("test", &shutdown, || node.call()).await,
+			Err(Error::Sync(SyncError::Cancelled))
+		));
+		assert_eq!(node.calls(), 0);
+	}
+
+	#[tokio::test]
+	async fn permanent_errors_are_not_backed_off() {
+		let (_shutdown_sender, shutdown) = watch::channel(false);
+		let node = FlakyNode::new(1);
+
+		let result = timeout(
+			TEST_TIMEOUT,
+			with_node_backoff("test", &shutdown, || node.call()),
+		)
+		.await
+		.unwrap();
+
+		assert!(matches!(result, Err(Error::MissingChainName)));
+		assert_eq!(node.calls(), 1);
+	}
+
+	#[tokio::test]
+	async fn cancelled_calls_are_not_retried() {
+		let (_shutdown_sender, shutdown) = watch::channel(true);
+		let node = FlakyNode::new(0);
+
+		let result = with_retries("test", 3, || {
+			with_node_backoff("test", &shutdown, || node.call())
+		})
+		.await;
+
+		assert!(matches!(result, Err(Error::Sync(SyncError::Cancelled))));
+		assert_eq!(node.calls(), 0);
+	}
+
+	#[test]
+	fn only_transient_errors_are_backed_off() {
+		assert_eq!(
+			NodeErrorKind::classify(&Error::Sync(SyncError::NodeSyncing)),
+			NodeErrorKind::Syncing
+		);
+		assert_eq!(NodeErrorKind::Syncing.backoff(), Some(SYNCING_BACKOFF));
+		assert_eq!(
+			NodeErrorKind::classify(&Error::MissingChainName),
+			NodeErrorKind::Other
+		);
+		assert_eq!(NodeErrorKind::Other.backoff(), None);
+	}
+
+	#[test]
+	fn rate_limited_calls_wait_for_the_retry_after_delay() {
+		let kind = NodeErrorKind::classify(&Error::RateLimited {
+			retry_after: Some(Duration::from_secs(7)),
+		});
+
+		assert_eq!(
+			kind,
+			NodeErrorKind::RateLimited {
+				retry_after: Some(Duration::from_secs(7))
+			}
+		);
+		assert_eq!(kind.backoff(), Some(Duration::from_secs(7)));
+	}
+
+	#[test]
+	fn rate_limited_calls_without_retry_after_wait_the_fallback() {
+		let kind = NodeErrorKind::classify(&Error::RateLimited { retry_after: None });
+		assert_eq!(kind.backoff(), Some(RATE_LIMITED_BACKOFF));
+
+		let web3_429 = Error::Web3(web3::Error::Transport(TransportError::Code(429)));
+		assert_eq!(
+			NodeErrorKind::classify(&web3_429).backoff(),
+			Some(RATE_LIMITED_BACKOFF)
+		);
+	}
+
+	#[test]
+	fn retry_after_delays_are_bounded() {
+		let immediate = NodeErrorKind::RateLimited {
+			retry_after: Some(Duration::ZERO),
+		};
+		let endless = NodeErrorKind::RateLimited {
+			retry_after: Some(Duration::from_secs(u32::MAX as u64)),
+		};
+
+		assert_eq!(immediate.backoff(), Some(MIN_RETRY_AFTER));
+		assert_eq!(endless.backoff(), Some(MAX_RETRY_AFTER));
+	}
+
+	#[test]
+	fn retry_after_is_parsed_from_seconds() {
+		let now = SystemTime::now();
+
+		assert_eq!(
+			parse_retry_after("120", now),
+			Some(Duration::from_secs(120))
+		);
+		assert_eq!(parse_retry_after(" 0 ", now), Some(Duration::ZERO));
+		assert_eq!(parse_retry_after("-1", now), None);
+		assert_eq!(parse_retry_after("1.5", now), None);
+	}
+
+	#[test]
+	fn retry_after_is_parsed_from_a_date() {
+		// Sun, 06 Nov 1994 08:49:37 GMT
+		let date = UNIX_EPOCH + Duration::from_secs(784111777);
+
+		assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(date));
+		assert_eq!(
+			parse_retry_after(
+				"Sun, 06 Nov 1994 08:49:37 GMT",
+				date - Duration::from_secs(90)
+			),
+			Some(Duration::from_secs(90))
+		);
+		assert_eq!(
+			parse_retry_after(
+				"Sun, 06 Nov 1994 08:49:37 GMT",
+				date + Duration::from_secs(5)
+			),
+			Some(Duration::ZERO)
+		);
+	}
+
+	#[test]
+	fn dates_are_counted_across_leap_years() {
+		assert_eq!(days_from_civil(1970, 1, 1), 0);
+		assert_eq!(days_from_civil(2000, 2, 29), 11016);
+		assert_eq!(days_from_civil(2000, 3, 1), 11017);
+		assert_eq!(days_from_civil(2022, 9, 15), 19250);
+	}
+
+	#[test]
+	fn malformed_retry_after_dates_are_rejected() {
+		let now = SystemTime::now();
+
+		for value in [
+			"",
+			"soon",
+			"Sunday, 06-Nov-94 08:49:37 GMT",
+			"Sun Nov  6 08:49:37 1994",
+			"Sun, 06 Nov 1994 08:49:37 UTC",
+			"Sun, 32 Nov 1994 08:49:37 GMT",
+			"Sun, 06 Foo 1994 08:49:37 GMT",
+			"Sun, 06 Nov 1994 24:00:00 GMT",
+			"Sun, 06 Nov 1994 08:49 GMT",
+		] {
+			assert_eq!(parse_retry_after(value, now), None, "{value}");
+		}
+	}
+}
@@ -0,0 +1,69 @@
+use std::{
+	sync::Mutex,
+	time::{Duration, Instant},
+};
+
+use kiln_postgres::{get_connection, ExecBlock, PgConnectionPool};
+
+use crate::Error;
+
+/// Number of the latest stored blocks the estimate is computed from
+const SAMPLE_SIZE: u32 = 64;
+/// Age after which the estimate is computed again
+const REFRESH_INTERVAL: Duration = Duration::from_secs(600);
+/// Lowest estimate returned, as timestamps are in seconds
+const MIN_BLOCK_TIME: Duration = Duration::from_secs(1);
+
+/// Estimate of the time between two execution blocks, from the stored ones
+pub(crate) struct BlockTimeEstimator {
+	conn_pool: PgConnectionPool,
+	/// Returned while too few blocks are stored, e.g. when only the consensus layer is indexed
+	fallback: Duration,
+	cached: Mutex<Option<(Instant, Duration)>>,
+}
+
+impl BlockTimeEstimator {
+	pub fn new(conn_pool: PgConnectionPool, fallback: Duration) -> BlockTimeEstimator {
+		BlockTimeEstimator {
+			conn_pool,
+			fallback,
+			cached: Mutex::new(None),
+		}
+	}
+
+	/// Return the median time between two of the latest stored blocks
+	///
+	/// The median is not thrown off by the long intervals of missed slots, nor by a burst of
+	/// blocks. Two blocks apart in number count as their interval split evenly.
+	/// Computed again once older than `REFRESH_INTERVAL`.
+	pub fn estimate_block_time(&self) -> Result<Duration, Error> {
+		let mut cached = self.cached.lock().unwrap();
+		if let Some((at, estimate)) = *cached {
+			if at.elapsed() < REFRESH_INTERVAL {
+				return Ok(estimate)
+			}
+		}
+
+		let blocks = ExecBlock::latest_timestamps(&get_connection(&self.conn_pool)?, SAMPLE_SIZE)?;
+		let mut intervals: Vec<f64> = blocks
+			.windows(2)
+			.filter_map(|pair| {
+				let ((number, timestamp), (previous_number, previous_timestamp)) =
+					(pair[0], pair[1]);
+				let seconds = timestamp.checked_sub(previous_timestamp)?;
+				Some(seconds as f64 / (number - previous_number) as f64)
+			})
+			.collect();
+
+		let estimate = if intervals.is_empty() {
+			self.fallback
+		} else {
+			// Safe to unwrap because intervals are finite
+			intervals.sort_by(|a, b| a.partial_cmp(b).unwrap());
+			Duration::from_secs_f64(intervals[intervals.len() / 2]).max(MIN_BLOCK_TIME)
+		};
+		*cached = Some((Instant::now(), estimate));
+
+		Ok(estimate)
+	}
+}
@@ -1,22 +1,162 @@
-use std::fmt::Display;
+use std::{collections::HashSet, fmt::Display};
 
 use async_trait::async_trait;
-use eth2::BeaconNodeHttpClient;
-use kiln_postgres::{NewSlot, PgConnectionPool, Slot};
-use log::info;
+use eth2::types::{AttesterSlashing, Hash256, MainnetEthSpec, SignedBeaconBlock};
+use kiln_postgres::{
+	get_connection, DbConnection, NewSlashingEvent, NewSlashingEvents, NewSlot, NewStat,
+	NewValidator, PgConnectionPool, ReprocessLayer, SlashingKind, Slot, SlotSpec, StatKey,
+};
+use log::{info, warn};
 
-use super::syncer::DbSyncer;
+use super::{
+	store_raw_payload,
+	syncer::{DbSyncer, WriteMode},
+	EntryWriter,
+};
 
 use crate::{client_consensus, Error};
 
-pub(crate) struct ConsensusSyncer(PgConnectionPool, BeaconNodeHttpClient);
+/// Sanity check of the validators count of every new slot against the previous one
+///
+/// The count of active validators mostly grows, exits aside, a sharp drop rather hints at bad
+/// data from the node.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ValidatorsCountCheck {
+	/// Largest drop from the previous slot's count considered normal
+	pub max_drop: u64,
+	/// Store the slot without its count on a larger drop, instead of only warning
+	pub reject: bool,
+}
+
+impl ValidatorsCountCheck {
+	/// Return `count`, the validators count at `height`, unless the check rejects it
+	///
+	/// Compared to the count of the closest slot stored with one below `height`, as seen by `conn`.
+	/// A rejected count is backfilled later, from the node again.
+	pub fn checked(
+		self,
+		conn: &DbConnection,
+		height: u64,
+		count: u64,
+	) -> Result<Option<u64>, Error> {
+		let (previous_height, previous) = match Slot::previous_validators_count(conn, height)? {
+			Some(previous) => previous,
+			None => return Ok(Some(count)),
+		};
+		if count + self.max_drop >= previous {
+			return Ok(Some(count))
+		}
+
+		warn!(
+			"{count} validators at slot {height}, down from {previous} at slot \
+			 {previous_height}{}",
+			if self.reject { ", not storing it" } else { "" }
+		);
+
+		Ok(if self.reject { None } else { Some(count) })
+	}
+}
+
+/// The fourth field tells whether the blocks fetched from the node are stored as is
+pub(crate) struct ConsensusSyncer(
+	EntryWriter,
+	client_consensus::Client,
+	WriteMode,
+	bool,
+	Option<ValidatorsCountCheck>,
+);
 
 impl ConsensusSyncer {
 	pub fn new(
 		pg_connection: PgConnectionPool,
-		client_consensus: BeaconNodeHttpClient,
+		client_consensus: client_consensus::Client,
 	) -> ConsensusSyncer {
-		ConsensusSyncer(pg_connection, client_consensus)
+		ConsensusSyncer(
+			EntryWriter::new(pg_connection),
+			client_consensus,
+			WriteMode::Insert,
+			false,
+			None,
+		)
+	}
+
+	/// Replace already stored slots instead of keeping them
+	pub fn overwriting(self) -> ConsensusSyncer {
+		ConsensusSyncer(self.0, self.1, WriteMode::Overwrite, self.3, self.4)
+	}
+
+	/// Store the blocks fetched from the node as is, for later reprocessing
+	pub fn storing_raw_payloads(self, enabled: bool) -> ConsensusSyncer {
+		ConsensusSyncer(self.0, self.1, self.2, enabled, self.4)
+	}
+
+	/// Check the validators count of every new slot against the previous one, if `check` is set
+	pub fn checking_validators_count(self, check: Option<ValidatorsCountCheck>) -> ConsensusSyncer {
+		ConsensusSyncer(self.0, self.1, self.2, self.3, check)
+	}
+
+	/// Return the id of the highest slot finalized by the node
+	///
+	/// Return 0 if the node does not know of any finalized checkpoint yet.
+	pub async fn get_node_finalized_height(&self) -> Result<u64, Error> {
+		let height = client_consensus::get_finalized_height(&self.1).await?;
+
+		Ok(height.unwrap_or(0))
+	}
+
+	/// Return the execution block hash of the node's block at every one of `heights`, in the same
+	/// order
+	///
+	/// None for a slot without block or with a pre-merge one, so the hashes compare with the
+	/// stored ones. Each height gets its own result, see `client_consensus::get_block_hashes`.
+	pub async fn get_node_block_hashes(
+		&self,
+		heights: &[u64],
+	) -> Vec<(u64, Result<Option<Hash256>, Error>)> {
+		client_consensus::get_block_hashes(&self.1, heights).await
+	}
+
+	// Write `new_slot` in database according to the write mode
+	fn write_slot(&self, new_slot: NewSlot) -> Result<(), Error> {
+		let conn = self.0.conn()?;
+		match self.2 {
+			WriteMode::Insert => new_slot.insert_do_nothing(&conn)?,
+			WriteMode::Overwrite => new_slot.upsert(&conn)?,
+		};
+
+		Ok(())
+	}
+
+	// Store the slashings included in the block at `height`
+	//
+	// The slashed validators are marked as such right away, so they are reported slashed even
+	// before the next validators update.
+	fn write_slashings(
+		&self,
+		height: u64,
+		block: &SignedBeaconBlock<MainnetEthSpec>,
+	) -> Result<(), Error> {
+		let slashings = slashings(block);
+		if slashings.is_empty() {
+			return Ok(())
+		}
+
+		let conn = self.0.conn()?;
+		let events = slashings
+			.iter()
+			.map(|(index, kind)| NewSlashingEvent::new(height, *index, *kind));
+		NewSlashingEvents::new(events.collect()).batch_insert_do_nothing(&conn)?;
+
+		// A validator can be slashed for both offences at once
+		let indexes: HashSet<u64> = slashings.iter().map(|(index, _)| *index).collect();
+		let indexes: Vec<u64> = indexes.into_iter().collect();
+		info!("{self}: validators {indexes:?} slashed at slot {height}");
+		if NewValidator::set_slashed(&conn, &indexes)? > 0 {
+			// Tell the api its cached slashing statuses are stale
+			NewStat::increment(&conn, StatKey::ValidatorsGeneration)?;
+		}
+
+		Ok(())
 	}
 }
 
@@ -28,18 +168,22 @@ impl Display for ConsensusSyncer {
 
 #[async_trait]
 impl DbSyncer for ConsensusSyncer {
-	type NodeClient = BeaconNodeHttpClient;
+	type NodeClient = client_consensus::Client;
 
 	fn node_client(&self) -> Self::NodeClient {
 		self.1.clone()
 	}
 
 	fn get_db_height(&self) -> Result<u64, Error> {
-		let highest_slot = Slot::get_highest(&self.0.get().unwrap())?;
+		let highest_slot = Slot::get_highest(&get_connection(self.0.pool())?)?;
 
 		Ok(highest_slot.height())
 	}
 
+	fn entry_writer(&self) -> Option<&EntryWriter> {
+		Some(&self.0)
+	}
+
 	async fn create_new_entry(&self, height: u64) -> Result<(), Error> {
 		// Fetch block
 		let opt_block = client_consensus::get_block(&self.node_client(), height).await?;
@@ -47,10 +191,15 @@ impl DbSyncer for ConsensusSyncer {
 			Some(b) => b,
 			None => {
 				info!("Slot {height} was missed");
+				self.write_slot(NewSlot::missed(height))?;
 				return Ok(())
 			},
 		};
 
+		if self.3 {
+			store_raw_payload(self.0.pool(), ReprocessLayer::Consensus, height, &block);
+		}
+
 		// Retrieve block hash and block number from the block
 		let block_hash = block
 			.message()
@@ -59,13 +208,116 @@ impl DbSyncer for ConsensusSyncer {
 			.ok()
 			.map(|p| p.block_hash.into_root());
 		let block_number = block.message().body().execution_payload().ok().map(|p| p.block_number);
+		let tx_count = block
+			.message()
+			.body()
+			.execution_payload()
+			.ok()
+			.map(|p| p.transactions.len() as u64);
+		let timestamp = execution_timestamp(&block);
+
+		// Counting validators must not block ingestion, a missing count is backfilled later
+		let validators_count =
+			match client_consensus::get_validators_count_at_slot(&self.node_client(), height).await
+			{
+				Ok(c) => c,
+				Err(err) => {
+					warn!("{self}: Failed to count validators at slot {height}, will be backfilled: {err}");
+					None
+				},
+			};
+		let validators_count = match validators_count {
+			Some(count) => match self.4 {
+				Some(check) => check.checked(&self.0.conn()?, height, count)?,
+				None => Some(count),
+			},
+			None => None,
+		};
 
 		// Create a new slot
-		let new_slot = NewSlot::new(height, block_hash, block_number);
+		let new_slot = NewSlot::new(
+			height,
+			block_hash,
+			block_number,
+			block.message().proposer_index(),
+			validators_count,
+			spec(&block),
+			tx_count,
+			timestamp,
+		);
 
 		// Write the new slot in database
-		new_slot.insert_do_nothing(&self.0.get().unwrap())?;
+		self.write_slot(new_slot)?;
+		self.write_slashings(height, &block)?;
 
 		Ok(())
 	}
+
+	// Publish the new database head, unless reprocessing past heights
+	fn on_entry_created(&self, height: u64) {
+		if self.2 != WriteMode::Insert {
+			return
+		}
+
+		let published = get_connection(self.0.pool())
+			.map_err(Error::from)
+			.and_then(|conn| Ok(NewStat::new(StatKey::ConsensusDbHeight, height).upsert(&conn)?));
+		if let Err(err) = published {
+			warn!("{self}: Failed to publish database head: {err}");
+		}
+	}
+}
+
+// Return the index of every validator slashed by `block`, along with the offence
+fn slashings(block: &SignedBeaconBlock<MainnetEthSpec>) -> Vec<(u64, SlashingKind)> {
+	let body = block.message().body();
+
+	let proposers = body.proposer_slashings().iter().map(|s| {
+		(
+			s.signed_header_1.message.proposer_index,
+			SlashingKind::Proposer,
+		)
+	});
+	let attesters = body
+		.attester_slashings()
+		.iter()
+		.flat_map(attester_slashing_indexes)
+		.map(|index| (index, SlashingKind::Attester));
+
+	proposers.chain(attesters).collect()
+}
+
+// Return the validators that signed both conflicting attestations of `slashing`
+fn attester_slashing_indexes(slashing: &AttesterSlashing<MainnetEthSpec>) -> Vec<u64> {
+	let second: HashSet<u64> = slashing.attestation_2.attesting_indices.iter().copied().collect();
+
+	slashing
+		.attestation_1
+		.attesting_indices
+		.iter()
+		.copied()
+		.filter(|index| second.contains(index))
+		.collect()
+}
+
+/// Return the timestamp of the execution block of `block`, None if pre-merge
+///
+/// Merge blocks built before the transition hold an empty payload, numbered 0.
+pub(crate) fn execution_timestamp(block: &SignedBeaconBlock<MainnetEthSpec>) -> Option<u64> {
+	block
+		.message()
+		.body()
+		.execution_payload()
+		.ok()
+		.filter(|p| p.block_number > 0)
+		.map(|p| p.timestamp)
+}
+
+// Return the fork `block` was built for
+fn spec(block: &SignedBeaconBlock<MainnetEthSpec>) -> SlotSpec {
+	match block {
+		SignedBeaconBlock::Base(_) => SlotSpec::Phase0,
+		SignedBeaconBlock::Altair(_) => SlotSpec::Altair,
+		SignedBeaconBlock::Merge(_) => SlotSpec::Merge,
+	}
 }
@@ -1,21 +1,30 @@
-use std::fmt::Display;
+use std::{
+	collections::{HashMap, HashSet},
+	fmt::Display,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Mutex,
+	},
+};
 
 use async_trait::async_trait;
 use ethereum_abi::Abi;
 use futures::future::try_join_all;
 use kiln_postgres::{
-	ExecBlock, NewExecBlock, NewTransaction, NewTransactions, NewValidator, PgConnectionPool,
-};
-use log::{error, info};
-use web3::{
-	transports::Http,
-	types::{Transaction, H160, H256},
-	Web3,
+	get_connection, ExecBlock, NewExecBlock, NewInternalCall, NewInternalCalls, NewStat,
+	NewTransaction, NewTransactions, NewValidator, PgConnectionPool, ReprocessLayer, StatKey,
+	Transaction as DbTransaction,
 };
+use log::{debug, error, info, warn};
+use web3::types::{Action, Block, Transaction, H160, H256};
 
-use super::{syncer::DbSyncer, SyncError};
+use super::{
+	store_raw_payload,
+	syncer::{DbSyncer, WriteMode},
+	EntryWriter, SyncError,
+};
 
-use crate::{client_execution, Error};
+use crate::{client_execution, node_health::NodeErrorKind, Error};
 
 // The deposit contract address for the kiln network
 //
@@ -33,11 +42,133 @@ lazy_static! {
 	static ref DEPOSIT_CONTRACT_ABI: Abi = serde_json::from_str(r#"[{"inputs":[],"stateMutability":"nonpayable","type":"constructor"},{"anonymous":false,"inputs":[{"indexed":false,"internalType":"bytes","name":"pubkey","type":"bytes"},{"indexed":false,"internalType":"bytes","name":"withdrawal_credentials","type":"bytes"},{"indexed":false,"internalType":"bytes","name":"amount","type":"bytes"},{"indexed":false,"internalType":"bytes","name":"signature","type":"bytes"},{"indexed":false,"internalType":"bytes","name":"index","type":"bytes"}],"name":"DepositEvent","type":"event"},{"inputs":[{"internalType":"bytes","name":"pubkey","type":"bytes"},{"internalType":"bytes","name":"withdrawal_credentials","type":"bytes"},{"internalType":"bytes","name":"signature","type":"bytes"},{"internalType":"bytes32","name":"deposit_data_root","type":"bytes32"}],"name":"deposit","outputs":[],"stateMutability":"payable","type":"function"},{"inputs":[],"name":"get_deposit_count","outputs":[{"internalType":"bytes","name":"","type":"bytes"}],"stateMutability":"view","type":"function"},{"inputs":[],"name":"get_deposit_root","outputs":[{"internalType":"bytes32","name":"","type":"bytes32"}],"stateMutability":"view","type":"function"},{"inputs":[{"internalType":"bytes4","name":"interfaceId","type":"bytes4"}],"name":"supportsInterface","outputs":[{"internalType":"bool","name":"","type":"bool"}],"stateMutability":"pure","type":"function"}]"#).unwrap();
 }
 
-pub(crate) struct ExecutionSyncer(PgConnectionPool, Web3<Http>);
+/// Whether a node without the `trace` namespace has already been warned about
+static TRACING_UNSUPPORTED: AtomicBool = AtomicBool::new(false);
+/// Whether a node failed a batch request, blocks being fetched one at a time since
+static BATCHING_UNSUPPORTED: AtomicBool = AtomicBool::new(false);
+
+// Blocks fetched ahead of their entry, a batch at a time
+struct BlockPrefetch {
+	batch_size: usize,
+	blocks: Mutex<HashMap<u64, Block<Transaction>>>,
+}
+
+impl BlockPrefetch {
+	fn new(batch_size: usize) -> Self {
+		BlockPrefetch {
+			batch_size,
+			blocks: Mutex::new(HashMap::new()),
+		}
+	}
+}
+
+/// The fourth field tells whether the blocks fetched from the node are stored as is, the fifth
+/// one whether the blocks are traced for internal calls
+pub(crate) struct ExecutionSyncer(
+	EntryWriter,
+	client_execution::Client,
+	WriteMode,
+	bool,
+	bool,
+	BlockPrefetch,
+);
 
 impl ExecutionSyncer {
-	pub fn new(conn: PgConnectionPool, client: Web3<Http>) -> ExecutionSyncer {
-		ExecutionSyncer(conn, client)
+	pub fn new(conn: PgConnectionPool, client: client_execution::Client) -> ExecutionSyncer {
+		ExecutionSyncer(
+			EntryWriter::new(conn),
+			client,
+			WriteMode::Insert,
+			false,
+			false,
+			BlockPrefetch::new(1),
+		)
+	}
+
+	/// Replace already stored blocks and transactions instead of failing
+	pub fn overwriting(self) -> ExecutionSyncer {
+		ExecutionSyncer(self.0, self.1, WriteMode::Overwrite, self.3, self.4, self.5)
+	}
+
+	/// Store the blocks fetched from the node as is, for later reprocessing
+	pub fn storing_raw_payloads(self, enabled: bool) -> ExecutionSyncer {
+		ExecutionSyncer(self.0, self.1, self.2, enabled, self.4, self.5)
+	}
+
+	/// Store the calls made by contracts, traced with the node `trace` namespace
+	pub fn tracing_internal_calls(self, enabled: bool) -> ExecutionSyncer {
+		ExecutionSyncer(self.0, self.1, self.2, self.3, enabled, self.5)
+	}
+
+	/// Fetch the blocks `batch_size` at a time, with a JSON-RPC batch request, instead of one at a
+	/// time
+	///
+	/// Capped at `client_execution::MAX_BLOCKS_PER_BATCH`. Blocks are fetched one at a time again
+	/// if the node fails a batch, as not every node handles them.
+	pub fn fetching_blocks_by_batch(self, batch_size: usize) -> ExecutionSyncer {
+		let batch_size = batch_size.clamp(1, client_execution::MAX_BLOCKS_PER_BATCH);
+
+		ExecutionSyncer(
+			self.0,
+			self.1,
+			self.2,
+			self.3,
+			self.4,
+			BlockPrefetch::new(batch_size),
+		)
+	}
+
+	// Store the calls made by contracts during the transactions of the block at `height`
+	//
+	// `origins` are the senders of the block transactions, by hash. A node without the `trace`
+	// namespace is only warned about once, its blocks are stored without internal calls.
+	async fn record_internal_calls(
+		&self,
+		height: u64,
+		origins: &HashMap<H256, H160>,
+	) -> Result<(), Error> {
+		let traces = match client_execution::get_block_traces(&self.node_client(), height).await? {
+			Some(traces) => traces,
+			None => {
+				if !TRACING_UNSUPPORTED.swap(true, Ordering::Relaxed) {
+					warn!("{self}: the node cannot trace blocks, internal calls are not indexed");
+				}
+				return Ok(())
+			},
+		};
+
+		let calls = traces
+			.into_iter()
+			.filter_map(|trace| {
+				// The top level call is the transaction itself
+				if trace.trace_address.is_empty() {
+					return None
+				}
+				let call = match trace.action {
+					Action::Call(call) => call,
+					_ => return None,
+				};
+				let hash = trace.transaction_hash?;
+				let origin = *origins.get(&hash)?;
+
+				Some(NewInternalCall::new(
+					hash,
+					&trace.trace_address,
+					origin,
+					call.from,
+					call.to,
+					height,
+				))
+			})
+			.collect();
+
+		let calls = NewInternalCalls::new(calls);
+		match self.2 {
+			WriteMode::Insert => calls.batch_insert(&self.0.conn()?)?,
+			WriteMode::Overwrite => calls.batch_upsert(&self.0.conn()?)?,
+		};
+
+		Ok(())
 	}
 }
 
@@ -49,23 +180,68 @@ impl Display for ExecutionSyncer {
 
 #[async_trait]
 impl DbSyncer for ExecutionSyncer {
-	type NodeClient = Web3<Http>;
+	type NodeClient = client_execution::Client;
 
 	fn node_client(&self) -> Self::NodeClient {
 		self.1.clone()
 	}
 
 	fn get_db_height(&self) -> Result<u64, Error> {
-		let block = ExecBlock::get_highest(&self.0.get().unwrap())?;
+		let block = ExecBlock::get_highest(&get_connection(self.0.pool())?)?;
 
 		Ok(block.number())
 	}
 
+	fn entry_writer(&self) -> Option<&EntryWriter> {
+		Some(&self.0)
+	}
+
+	fn batch_size(&self) -> usize {
+		if BATCHING_UNSUPPORTED.load(Ordering::Relaxed) {
+			1
+		} else {
+			self.5.batch_size
+		}
+	}
+
+	async fn prefetch(&self, heights: &[u64]) {
+		// Whatever is left of the previous batch would only be refetched
+		self.5.blocks.lock().unwrap().clear();
+
+		let blocks = match client_execution::get_blocks(&self.node_client(), heights).await {
+			Ok(blocks) => blocks,
+			// Transient errors are waited out by the fetches of each block
+			Err(err) if NodeErrorKind::classify(&err).backoff().is_some() => {
+				debug!("{self}: Failed to prefetch blocks {heights:?}: {err}");
+				return
+			},
+			Err(err) => {
+				if !BATCHING_UNSUPPORTED.swap(true, Ordering::Relaxed) {
+					warn!("{self}: the node failed a batch request, blocks are fetched one at a time: {err}");
+				}
+				return
+			},
+		};
+
+		let prefetched = heights
+			.iter()
+			.zip(blocks)
+			.filter_map(|(height, block)| block.map(|b| (*height, b)));
+		self.5.blocks.lock().unwrap().extend(prefetched);
+	}
+
 	async fn create_new_entry(&self, height: u64) -> Result<(), Error> {
-		// Get block from client
-		let block = client_execution::get_block(self.node_client(), height)
-			.await?
-			.ok_or(SyncError::NothingAtHeight(height))?;
+		// Get block from client, unless prefetched
+		let prefetched = self.5.blocks.lock().unwrap().remove(&height);
+		let block = match prefetched {
+			Some(block) => block,
+			None => client_execution::get_block(&self.node_client(), height)
+				.await?
+				.ok_or(SyncError::NothingAtHeight(height))?,
+		};
+		if self.3 {
+			store_raw_payload(self.0.pool(), ReprocessLayer::Execution, height, &block);
+		}
 
 		// Handle and insert block
 		let new_block = NewExecBlock::new(
@@ -75,19 +251,55 @@ impl DbSyncer for ExecutionSyncer {
 			block.state_root,
 			block.transactions_root,
 			block.receipts_root,
+			block.timestamp.as_u64(),
+			block.gas_used.as_u64(),
 		);
-		new_block.insert(&self.0.get().unwrap())?;
+		match self.2 {
+			WriteMode::Insert => new_block.insert(&self.0.conn()?)?,
+			WriteMode::Overwrite => new_block.upsert(&self.0.conn()?)?,
+		};
 
 		// async calls to execute after all new transactions are stored in db
 		let mut futures = vec![];
+		let mut deployments = vec![];
+
+		// Senders of the transactions, for their internal calls to be attributed to them
+		let origins: HashMap<H256, H160> = block
+			.transactions
+			.iter()
+			.filter_map(|t| t.from.map(|from| (t.hash, from)))
+			.collect();
+
+		// Transactions already stored, e.g. refetched after a reorg moved them to this block, are
+		// skipped when inserting, so they are not handled twice
+		let duplicates: HashSet<H256> = match self.2 {
+			WriteMode::Insert => {
+				let hashes: Vec<H256> = block.transactions.iter().map(|t| t.hash).collect();
+				DbTransaction::stored_hashes(&self.0.conn()?, &hashes)?.into_iter().collect()
+			},
+			WriteMode::Overwrite => HashSet::new(),
+		};
 
 		// Handle and insert transactions
 		let mut new_transactions = Vec::with_capacity(block.transactions.len());
 		block.transactions.into_iter().for_each(|t: Transaction| {
+			if duplicates.contains(&t.hash) {
+				warn!(
+					"transaction {:?} of block {height} already stored, skipped",
+					t.hash
+				);
+				return
+			}
+
 			match t.to {
 				Some(to) if to == H160::from(DEPOSIT_CONTRACT_ADDRESS) => futures.push(
-					link_validator_to_depositor(self.node_client(), self.0.clone(), t.clone()),
+					link_validator_to_depositor(self.node_client(), &self.0, t.clone()),
 				),
+				None => deployments.push(record_deployed_contract(
+					self.node_client(),
+					&self.0,
+					t.hash,
+				)),
 				_ => {},
 			};
 
@@ -99,22 +311,50 @@ impl DbSyncer for ExecutionSyncer {
 				t.to,
 				t.input.0,
 				t.value,
+				t.nonce.as_u64(),
+				t.block_number.unwrap().as_u64(),
+				t.transaction_type.map(|t| t.as_u64()),
 			));
 		});
 
-		NewTransactions::new(new_transactions).batch_insert(&self.0.get().unwrap())?;
+		// The issuers count is updated from the rows actually written, in the same transaction
+		let new_transactions = NewTransactions::new(new_transactions);
+		match self.2 {
+			// Duplicates stored meanwhile by another writer, e.g. a reprocess job, are left as is
+			WriteMode::Insert => new_transactions.batch_insert_new_counted(&self.0.conn()?)?,
+			WriteMode::Overwrite => new_transactions.batch_upsert_counted(&self.0.conn()?)?,
+		};
 
 		try_join_all(futures).await?;
+		try_join_all(deployments).await?;
+
+		if self.4 {
+			self.record_internal_calls(height, &origins).await?;
+		}
 
 		Ok(())
 	}
+
+	// Publish the new database head, unless reprocessing past heights
+	fn on_entry_created(&self, height: u64) {
+		if self.2 != WriteMode::Insert {
+			return
+		}
+
+		let published = get_connection(self.0.pool())
+			.map_err(Error::from)
+			.and_then(|conn| Ok(NewStat::new(StatKey::ExecutionDbHeight, height).upsert(&conn)?));
+		if let Err(err) = published {
+			warn!("{self}: Failed to publish database head: {err}");
+		}
+	}
 }
 
 // Create a link in database between a validator and the successful calls to the deposit contract
 // that registered it
 async fn link_validator_to_depositor(
-	client: Web3<Http>,
-	conn_pool: PgConnectionPool,
+	client: client_execution::Client,
+	writer: &EntryWriter,
 	transaction: Transaction,
 ) -> Result<(), Error> {
 	let (function, decoded_params) =
@@ -128,7 +368,7 @@ async fn link_validator_to_depositor(
 	}
 
 	let status = is_transaction_successful(client, transaction.hash).await?;
-	NewTransaction::set_status(&conn_pool.get().unwrap(), transaction.hash, status)?;
+	NewTransaction::set_status(&writer.conn()?, transaction.hash, status)?;
 
 	let bytes = match &decoded_params.get(0).unwrap().value {
 		ethereum_abi::Value::Bytes(b) => b,
@@ -138,9 +378,12 @@ async fn link_validator_to_depositor(
 	pubkey.push_str(&hex::encode(bytes));
 
 	let transaction_hash = transaction.hash;
-	let rows =
-		NewValidator::set_deposit_transaction(&conn_pool.get().unwrap(), pubkey, transaction_hash)?;
+	let rows = NewValidator::set_deposit_transaction(&writer.conn()?, pubkey, transaction_hash)?;
 	info!("validator deposit: from {:?}", transaction.from);
+	if rows > 0 {
+		// Tell the api its cached validator statuses are stale
+		NewStat::increment(&writer.conn()?, StatKey::ValidatorsGeneration)?;
+	}
 	if rows != 1 {
 		error!(
 			"wrong amount ({rows}) of validators are linked to the transaction {:?}",
@@ -151,15 +394,42 @@ async fn link_validator_to_depositor(
 	Ok(())
 }
 
+// Store the address of the contract created by a deployment transaction, from its receipt
+//
+// The receipt is authoritative, whatever the way the address was derived. Only the contract
+// created by the transaction itself is recorded, not the ones it created in turn.
+async fn record_deployed_contract(
+	client: client_execution::Client,
+	writer: &EntryWriter,
+	transaction_hash: H256,
+) -> Result<(), Error> {
+	let reciept = client_execution::get_transaction_receipt(&client, transaction_hash)
+		.await?
+		// Only safe to unwrap because the transaction have been included
+		.unwrap();
+
+	// Nodes report an address even for failed deployments, which create no contract
+	// Safe to unwrap because kiln is post Byzantum
+	if reciept.status.unwrap().is_zero() {
+		return Ok(())
+	}
+
+	if let Some(contract_address) = reciept.contract_address {
+		NewTransaction::set_contract_address(&writer.conn()?, transaction_hash, contract_address)?;
+	}
+
+	Ok(())
+}
+
 // Check transaction status on reciept
 //
 // # Safety
 // Will panic if called on a transaction that have not been included (pending)
 async fn is_transaction_successful(
-	client: Web3<Http>,
+	client: client_execution::Client,
 	transaction_hash: H256,
 ) -> Result<bool, Error> {
-	let reciept = client_execution::get_transaction_receipt(client, transaction_hash)
+	let reciept = client_execution::get_transaction_receipt(&client, transaction_hash)
 		.await?
 		// Only safe to unwrap because the transaction have been included
 		.unwrap();
@@ -1,11 +1,32 @@
+pub(crate) mod block_time;
 pub(crate) mod consensus_layer;
 pub(crate) mod execution_layer;
+pub(crate) mod pause;
+pub(crate) mod pruning;
+pub(crate) mod reorg;
+pub(crate) mod reprocess;
+pub(crate) mod slot_timestamps;
+pub(crate) mod supervisor;
 pub(crate) mod syncer;
+pub(crate) mod validator_counts;
 pub(crate) mod validators;
+pub(crate) mod watchdog;
+pub(crate) mod writer;
 
+pub(crate) use block_time::*;
 pub(crate) use consensus_layer::*;
 pub(crate) use execution_layer::*;
+pub(crate) use pause::*;
+pub(crate) use supervisor::*;
 pub(crate) use syncer::*;
+pub(crate) use writer::*;
+
+use std::time::Duration;
+
+use kiln_postgres::{get_connection, NewRawPayload, PgConnectionPool, ReprocessLayer};
+use log::warn;
+use serde::Serialize;
+use tokio::time::{interval, Interval, MissedTickBehavior};
 
 #[derive(Debug)]
 pub enum SyncError {
@@ -15,4 +36,85 @@ pub enum SyncError {
 	PendingBlock(u64),
 	/// The client did not return any validators
 	NoValidators,
+	/// The sync was stopped by a shutdown request
+	Cancelled,
+	/// The entry at height still failed after all its retries
+	RetriesExhausted(u64),
+	/// The node is too far behind the chain head to be used
+	NodeSyncing,
+}
+
+// Return an interval ticking every `period` for a background task
+//
+// The task awaits its work between two ticks, so runs never overlap. A run lasting longer than
+// `period` delays the next tick by a whole period, instead of the default burst of ticks catching
+// up on the missed ones.
+fn ticker(period: Duration) -> Interval {
+	let mut ticker = interval(period);
+	ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+	ticker
+}
+
+// Store the JSON of `payload`, the `layer` entry fetched at `height`
+//
+// Raw payloads are a convenience: failing to store one is logged, never fails the sync.
+fn store_raw_payload<T: Serialize>(
+	conn_pool: &PgConnectionPool,
+	layer: ReprocessLayer,
+	height: u64,
+	payload: &T,
+) {
+	let result = serde_json::to_string(payload).map_err(|e| e.to_string()).and_then(|json| {
+		let conn = get_connection(conn_pool).map_err(|e| e.to_string())?;
+		NewRawPayload::new(layer, height, json).upsert(&conn).map_err(|e| e.to_string())
+	});
+	if let Err(err) = result {
+		warn!("failed to store the raw {layer} payload at height {height}: {err}");
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use tokio::time::{sleep, Instant};
+
+	use super::*;
+
+	const PERIOD: Duration = Duration::from_millis(100);
+
+	#[tokio::test]
+	async fn ticker_ticks_right_away_then_every_period() {
+		let mut ticker = ticker(PERIOD);
+
+		let start = Instant::now();
+		ticker.tick().await;
+		assert!(start.elapsed() < PERIOD / 2);
+
+		let start = Instant::now();
+		ticker.tick().await;
+		assert!(start.elapsed() >= PERIOD * 8 / 10);
+	}
+
+	#[tokio::test]
+	async fn ticker_delays_the_ticks_missed_by_a_slow_run() {
+		let mut ticker = ticker(PERIOD);
+		ticker.tick().await;
+
+		// A run lasting several periods
+		sleep(PERIOD * 7 / 2).await;
+
+		// The missed tick fires once, right away
+		let start = Instant::now();
+		ticker.tick().await;
+		assert!(start.elapsed() < PERIOD / 2);
+
+		// Then a whole period passes, where bursting would fire the other missed ticks at once
+		let start = Instant::now();
+		ticker.tick().await;
+		assert!(
+			start.elapsed() >= PERIOD * 8 / 10,
+			"ticked after {:?}",
+			start.elapsed()
+		);
+	}
 }
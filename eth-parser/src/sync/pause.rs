@@ -0,0 +1,63 @@
+use std::time::{Duration, Instant};
+
+use kiln_postgres::{get_connection, PgConnectionPool, Stat, StatKey};
+use log::{info, warn};
+
+/// Interval between two reminders that the sync is still paused
+const PAUSED_LOG_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Return true if the sync is paused from the api
+///
+/// A pause that cannot be read is ignored, so the sync does not stall on it.
+pub(crate) fn sync_paused(conn_pool: &PgConnectionPool) -> bool {
+	let paused = get_connection(conn_pool)
+		.map_err(|e| e.to_string())
+		.and_then(|c| Stat::get(&c, StatKey::SyncPaused).map_err(|e| e.to_string()));
+
+	match paused {
+		Ok(paused) => paused == Some(1),
+		Err(err) => {
+			warn!("failed to check whether the sync is paused: {err}");
+			false
+		},
+	}
+}
+
+/// Pause state of a sync loop, logging when it changes and periodically while paused
+pub(crate) struct Pause {
+	conn_pool: PgConnectionPool,
+	/// When the pause was last logged, if paused
+	logged_at: Option<Instant>,
+}
+
+impl Pause {
+	pub fn new(conn_pool: PgConnectionPool) -> Self {
+		Pause {
+			conn_pool,
+			logged_at: None,
+		}
+	}
+
+	/// Return true if the sync is paused
+	pub fn check(&mut self) -> bool {
+		let paused = sync_paused(&self.conn_pool);
+
+		match (paused, self.logged_at) {
+			(true, None) => {
+				info!("sync paused");
+				self.logged_at = Some(Instant::now());
+			},
+			(true, Some(at)) if at.elapsed() >= PAUSED_LOG_INTERVAL => {
+				info!("sync still paused");
+				self.logged_at = Some(Instant::now());
+			},
+			(false, Some(_)) => {
+				info!("sync resumed");
+				self.logged_at = None;
+			},
+			_ => {},
+		}
+
+		paused
+	}
+}
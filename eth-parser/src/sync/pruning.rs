@@ -0,0 +1,60 @@
+use std::time::Duration;
+
+use kiln_postgres::{get_connection, prune_below, NewStat, PgConnectionPool, Slot};
+use log::{error, info};
+use tokio::sync::watch;
+
+use super::{sync_paused, ticker};
+
+use crate::Error;
+
+/// Interval between two pruning passes
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Delete the slots more than `retained` slots below the highest stored one, until shutdown
+pub(crate) async fn prune_old_slots(
+	conn_pool: PgConnectionPool,
+	retained: u64,
+	shutdown: watch::Receiver<bool>,
+) {
+	let mut ticker = ticker(PRUNE_INTERVAL);
+	loop {
+		ticker.tick().await;
+		if *shutdown.borrow() {
+			return
+		}
+		if sync_paused(&conn_pool) {
+			continue
+		}
+
+		if let Err(err) = prune_pass(&conn_pool, retained) {
+			error!("failed to prune old slots: {err}");
+		}
+	}
+}
+
+fn prune_pass(conn_pool: &PgConnectionPool, retained: u64) -> Result<(), Error> {
+	let conn = get_connection(conn_pool)?;
+	let highest = match Slot::get_highest(&conn) {
+		Ok(slot) => slot.height(),
+		Err(diesel::result::Error::NotFound) => return Ok(()),
+		Err(err) => return Err(err.into()),
+	};
+	let height = match highest.checked_sub(retained) {
+		Some(h) if h > 0 => h,
+		_ => return Ok(()),
+	};
+
+	let pruned = prune_below(&conn, height)?;
+	info!(
+		"pruned below slot {height}: {} slots, {} blocks, {} transactions, {} internal calls",
+		pruned.slots, pruned.execution_blocks, pruned.transactions, pruned.internal_calls
+	);
+
+	// The execution syncer only counts the issuers it writes, recount the ones left
+	if pruned.transactions > 0 {
+		NewStat::recount_issuers(&conn)?;
+	}
+
+	Ok(())
+}
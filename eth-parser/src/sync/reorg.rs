@@ -0,0 +1,256 @@
+use std::{
+	collections::HashMap,
+	time::{Duration, Instant},
+};
+
+use eth2::types::Hash256;
+use kiln_postgres::{get_connection, PgConnectionPool, Slot};
+use log::{error, info, warn};
+
+use super::{ConsensusSyncer, DbSyncer, ExecutionSyncer, SyncOptions};
+
+use crate::{client_consensus, client_execution, metrics::REORGED_SLOTS, Error};
+
+/// Return the slots among the `depth` highest below `head` whose stored block the node no longer
+/// has
+///
+/// Both sides are read in a single round-trip each. Missed slots count as having no block, so a
+/// slot that got or lost one is reported too. Slots not stored are not checked, nor are the ones
+/// whose block the node failed to return: they are checked on the next round, unless they are
+/// deeper than `depth` by then. Fail only if the node returned none of the blocks.
+pub(crate) async fn find_reorged_slots(
+	syncer: &ConsensusSyncer,
+	conn_pool: &PgConnectionPool,
+	head: u64,
+	depth: u64,
+) -> Result<Vec<u64>, Error> {
+	let heights: Vec<u64> = (head.saturating_sub(depth) + 1..=head).collect();
+
+	let stored = Slot::block_hashes_for(&get_connection(conn_pool)?, &heights)?;
+
+	let mut node = HashMap::new();
+	let mut failed = Vec::new();
+	let mut last_err = None;
+	for (height, hash) in syncer.get_node_block_hashes(&heights).await {
+		match hash {
+			Ok(hash) => {
+				node.insert(height, hash);
+			},
+			Err(err) => {
+				failed.push(height);
+				last_err = Some(err);
+			},
+		}
+	}
+	if let Some(err) = last_err {
+		if node.is_empty() {
+			return Err(err)
+		}
+		warn!("slots {failed:?} not checked for a reorg, their block could not be fetched: {err}");
+	}
+
+	let reorged = mismatching_heights(stored, &node);
+
+	if !reorged.is_empty() {
+		REORGED_SLOTS.inc_by(reorged.len() as u64);
+		warn!("slots {reorged:?} no longer match the node");
+	}
+
+	Ok(reorged)
+}
+
+// Return the heights whose `stored` hash differs from the one the node answered with
+//
+// The heights the node did not answer for are left out.
+fn mismatching_heights(
+	stored: Vec<(u64, Option<Hash256>)>,
+	node: &HashMap<u64, Option<Hash256>>,
+) -> Vec<u64> {
+	stored
+		.into_iter()
+		.filter(|(height, stored_hash)| {
+			node.get(height).map_or(false, |node_hash| node_hash != stored_hash)
+		})
+		.map(|(height, _)| height)
+		.collect()
+}
+
+/// Cap on the consecutive reorg rewinds, against a flapping node making the indexer rewind over and
+/// over
+///
+/// Once `max_rewinds` rewinds ran without a stable period of `cooldown` between two of them,
+/// rewinds are paused for `cooldown`. Each rewind spans at most the `reorg_check_depth` slots
+/// checked.
+#[derive(Debug)]
+pub(crate) struct RewindLimiter {
+	max_rewinds: u32,
+	cooldown: Duration,
+	consecutive: u32,
+	last_rewind: Option<Instant>,
+	paused_until: Option<Instant>,
+}
+
+impl RewindLimiter {
+	pub fn new(max_rewinds: u32, cooldown: Duration) -> RewindLimiter {
+		RewindLimiter {
+			max_rewinds,
+			cooldown,
+			consecutive: 0,
+			last_rewind: None,
+			paused_until: None,
+		}
+	}
+
+	/// Return whether a rewind can run at `now`, counting it if so
+	pub fn allow(&mut self, now: Instant) -> bool {
+		if let Some(until) = self.paused_until {
+			if now < until {
+				return false
+			}
+			self.paused_until = None;
+			self.consecutive = 0;
+		}
+		if self.last_rewind.map_or(false, |last| now >= last + self.cooldown) {
+			self.consecutive = 0;
+		}
+
+		if self.consecutive >= self.max_rewinds {
+			error!(
+				"{} consecutive reorg rewinds, pausing them for {:?}: is the node stable?",
+				self.consecutive, self.cooldown
+			);
+			self.paused_until = Some(now + self.cooldown);
+			return false
+		}
+
+		self.consecutive += 1;
+		self.last_rewind = Some(now);
+		true
+	}
+}
+
+/// Fetch the slots from the lowest to the highest of `reorged` again, overwriting the stored ones
+///
+/// The execution blocks of the refetched slots are fetched again too if `web3` is set, from the
+/// block following the one of the slot below. Both are written through the syncers, with overwrite
+/// semantics, so the superseded slots are kept in their history.
+pub(crate) async fn rewind_reorged_slots(
+	conn_pool: &PgConnectionPool,
+	eth2: &client_consensus::Client,
+	web3: Option<&client_execution::Client>,
+	reorged: &[u64],
+	options: &SyncOptions,
+) -> Result<(), Error> {
+	let (from, to) = match (reorged.first(), reorged.last()) {
+		(Some(from), Some(to)) => (*from, *to),
+		_ => return Ok(()),
+	};
+
+	info!("rewinding slots {from} to {to}");
+	ConsensusSyncer::new(conn_pool.clone(), eth2.clone())
+		.overwriting()
+		.bump(Some(from), to, options)
+		.await?;
+
+	let web3 = match web3 {
+		Some(web3) => web3,
+		None => return Ok(()),
+	};
+	let conn = get_connection(conn_pool)?;
+	let first_block = match from.checked_sub(1) {
+		Some(below) => Slot::block_at_or_before_slot(&conn, below)?.map_or(0, |b| b + 1),
+		None => 0,
+	};
+	let last_block = match Slot::block_at_or_before_slot(&conn, to)? {
+		Some(b) if b >= first_block => b,
+		// None of the refetched slots holds a block
+		_ => return Ok(()),
+	};
+	drop(conn);
+
+	info!("rewinding blocks {first_block} to {last_block}");
+	ExecutionSyncer::new(conn_pool.clone(), web3.clone())
+		.overwriting()
+		.bump(Some(first_block), last_block, options)
+		.await?;
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const COOLDOWN: Duration = Duration::from_secs(600);
+
+	fn hash(byte: u8) -> Option<Hash256> {
+		Some(Hash256::repeat_byte(byte))
+	}
+
+	#[test]
+	fn slots_whose_block_changed_are_reorged() {
+		let stored = vec![(10, hash(1)), (11, hash(2)), (12, None), (13, hash(4))];
+		let node = HashMap::from([(10, hash(1)), (11, hash(9)), (12, hash(3)), (13, None)]);
+
+		assert_eq!(mismatching_heights(stored, &node), vec![11, 12, 13]);
+	}
+
+	#[test]
+	fn slots_the_node_did_not_answer_for_are_not_compared() {
+		let stored = vec![(10, hash(1)), (11, hash(2)), (12, hash(3))];
+		// 11 failed, so the node answers for the heights around it only
+		let node = HashMap::from([(10, hash(1)), (12, hash(7))]);
+
+		assert_eq!(mismatching_heights(stored, &node), vec![12]);
+	}
+
+	#[test]
+	fn answers_are_matched_by_height_not_by_position() {
+		// The highest slots are stored, the lowest ones are not
+		let stored = vec![(12, hash(3)), (13, hash(4))];
+		let node = HashMap::from([(10, hash(8)), (11, hash(9)), (12, hash(3)), (13, hash(4))]);
+
+		assert!(mismatching_heights(stored, &node).is_empty());
+	}
+
+	#[test]
+	fn rewinds_pause_once_the_cap_is_reached() {
+		let mut limiter = RewindLimiter::new(2, COOLDOWN);
+		let start = Instant::now();
+
+		assert!(limiter.allow(start));
+		assert!(limiter.allow(start + Duration::from_secs(1)));
+		assert!(!limiter.allow(start + Duration::from_secs(2)));
+		assert!(!limiter.allow(start + Duration::from_secs(2) + COOLDOWN / 2));
+	}
+
+	#[test]
+	fn rewinds_resume_after_the_cooldown() {
+		let mut limiter = RewindLimiter::new(1, COOLDOWN);
+		let start = Instant::now();
+
+		assert!(limiter.allow(start));
+		assert!(!limiter.allow(start + Duration::from_secs(1)));
+		assert!(limiter.allow(start + Duration::from_secs(1) + COOLDOWN));
+	}
+
+	#[test]
+	fn a_stable_period_starts_the_count_over() {
+		let mut limiter = RewindLimiter::new(2, COOLDOWN);
+		let start = Instant::now();
+
+		assert!(limiter.allow(start));
+		assert!(limiter.allow(start + Duration::from_secs(1)));
+		let stable = start + Duration::from_secs(1) + COOLDOWN;
+		assert!(limiter.allow(stable));
+		assert!(limiter.allow(stable + Duration::from_secs(1)));
+		assert!(!limiter.allow(stable + Duration::from_secs(2)));
+	}
+
+	#[test]
+	fn no_rewind_when_capped_at_zero() {
+		let mut limiter = RewindLimiter::new(0, COOLDOWN);
+
+		assert!(!limiter.allow(Instant::now()));
+	}
+}
@@ -0,0 +1,137 @@
+use std::{cmp::min, time::Duration};
+
+use kiln_postgres::{
+	get_connection, NewReprocessJob, PgConnectionPool, ReprocessJob, ReprocessLayer,
+	ReprocessStatus,
+};
+use log::{error, info};
+use tokio::sync::watch;
+
+use super::{
+	sync_paused, ticker, ConsensusSyncer, DbSyncer, ExecutionSyncer, FailurePolicy, SyncOptions,
+};
+
+use crate::{client_consensus, client_execution, Error};
+
+/// Interval between two checks for pending reprocess jobs
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// Number of heights reprocessed between two progress updates
+const PROGRESS_CHUNK: u64 = 100;
+
+/// Run the reprocess jobs queued in database, until shutdown
+///
+/// Jobs are run one at a time, by creation order.
+/// Heights are written with overwrite semantics, so a job can be run again safely.
+/// A height failing past its retries fails the whole job, whatever `options.on_permanent_failure`.
+pub(crate) async fn watch_reprocess_jobs(
+	conn_pool: PgConnectionPool,
+	eth2: client_consensus::Client,
+	web3: client_execution::Client,
+	options: SyncOptions,
+	shutdown: watch::Receiver<bool>,
+) {
+	let options = SyncOptions {
+		on_permanent_failure: FailurePolicy::Halt,
+		..options
+	};
+
+	let requeued = get_connection(&conn_pool)
+		.map_err(Error::from)
+		.and_then(|conn| Ok(NewReprocessJob::requeue_running(&conn)?));
+	if let Err(err) = requeued {
+		error!("failed to requeue interrupted reprocess jobs: {err}");
+	}
+
+	let mut ticker = ticker(POLL_INTERVAL);
+	loop {
+		ticker.tick().await;
+		if *shutdown.borrow() {
+			return
+		}
+		// Pending jobs wait for the sync to be resumed
+		if sync_paused(&conn_pool) {
+			continue
+		}
+
+		if let Err(err) = run_pending_jobs(&conn_pool, &eth2, &web3, &options).await {
+			error!("failed to run reprocess jobs: {err}");
+		}
+	}
+}
+
+async fn run_pending_jobs(
+	conn_pool: &PgConnectionPool,
+	eth2: &client_consensus::Client,
+	web3: &client_execution::Client,
+	options: &SyncOptions,
+) -> Result<(), Error> {
+	loop {
+		let job = match ReprocessJob::next_pending(&get_connection(conn_pool)?)? {
+			Some(j) => j,
+			None => return Ok(()),
+		};
+
+		NewReprocessJob::set_status(
+			&get_connection(conn_pool)?,
+			job.id(),
+			ReprocessStatus::Running,
+		)?;
+		info!(
+			"reprocess job #{}: reprocessing {} heights {} to {}",
+			job.id(),
+			job.layer(),
+			job.from_height(),
+			job.to_height()
+		);
+
+		let result = match job.layer() {
+			ReprocessLayer::Consensus => {
+				let syncer = ConsensusSyncer::new(conn_pool.clone(), eth2.clone()).overwriting();
+				run_job(conn_pool, &job, syncer, options).await
+			},
+			ReprocessLayer::Execution => {
+				let syncer = ExecutionSyncer::new(conn_pool.clone(), web3.clone()).overwriting();
+				run_job(conn_pool, &job, syncer, options).await
+			},
+		};
+
+		let status = match result {
+			Ok(()) => {
+				info!("reprocess job #{}: done", job.id());
+				ReprocessStatus::Done
+			},
+			Err(err) => {
+				error!("reprocess job #{}: failed: {err}", job.id());
+				ReprocessStatus::Failed
+			},
+		};
+		NewReprocessJob::set_status(&get_connection(conn_pool)?, job.id(), status)?;
+	}
+}
+
+// Bump `syncer` over the job range, chunk by chunk, saving the progress after each one
+async fn run_job<S: DbSyncer + Send + Sync>(
+	conn_pool: &PgConnectionPool,
+	job: &ReprocessJob,
+	syncer: S,
+	options: &SyncOptions,
+) -> Result<(), Error> {
+	// Resume interrupted jobs where they stopped
+	let mut from = job.processed_height().map_or(job.from_height(), |h| h + 1);
+
+	while from <= job.to_height() {
+		let to = min(from + PROGRESS_CHUNK - 1, job.to_height());
+		syncer.bump(Some(from), to, options).await?;
+
+		NewReprocessJob::set_processed_height(&get_connection(conn_pool)?, job.id(), to)?;
+		info!(
+			"reprocess job #{}: processed up to height {to} of {}",
+			job.id(),
+			job.to_height()
+		);
+
+		from = to + 1;
+	}
+
+	Ok(())
+}
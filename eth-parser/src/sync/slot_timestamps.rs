@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use kiln_postgres::{get_connection, NewSlot, PgConnectionPool, Slot};
+use log::{error, info, warn};
+use tokio::sync::watch;
+
+use super::{execution_timestamp, sync_paused, ticker};
+
+use crate::{client_consensus, Error};
+
+/// Interval between two backfill passes
+const BACKFILL_INTERVAL: Duration = Duration::from_secs(60);
+/// Maximum number of slots whose block is fetched in a single pass
+const BACKFILL_BATCH_SIZE: u32 = 100;
+
+/// Fill the timestamp of the post-merge slots stored without one, until shutdown
+///
+/// Slots stored before timestamps were recorded have none. Their blocks are fetched from the node
+/// again, a batch at a time.
+pub(crate) async fn backfill_slot_timestamps(
+	conn_pool: PgConnectionPool,
+	eth2: client_consensus::Client,
+	shutdown: watch::Receiver<bool>,
+) {
+	let mut ticker = ticker(BACKFILL_INTERVAL);
+	loop {
+		ticker.tick().await;
+		if *shutdown.borrow() {
+			return
+		}
+		if sync_paused(&conn_pool) {
+			continue
+		}
+
+		if let Err(err) = backfill_pass(&conn_pool, &eth2).await {
+			error!("failed to backfill slot timestamps: {err}");
+		}
+	}
+}
+
+async fn backfill_pass(
+	conn_pool: &PgConnectionPool,
+	eth2: &client_consensus::Client,
+) -> Result<(), Error> {
+	let heights =
+		Slot::heights_missing_timestamp(&get_connection(conn_pool)?, BACKFILL_BATCH_SIZE)?;
+	if heights.is_empty() {
+		return Ok(())
+	}
+	info!("backfilling timestamp of {} slots", heights.len());
+
+	for height in heights {
+		let timestamp = client_consensus::get_block(eth2, height)
+			.await?
+			.as_ref()
+			.and_then(execution_timestamp);
+		match timestamp {
+			Some(timestamp) => {
+				NewSlot::set_timestamp(&get_connection(conn_pool)?, height, timestamp)?;
+			},
+			None => warn!("no execution block returned for slot {height}"),
+		}
+	}
+
+	Ok(())
+}
@@ -0,0 +1,166 @@
+use std::{
+	cmp::min,
+	fmt::Display,
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+
+use log::{debug, error, info};
+use tokio::{sync::watch, task::JoinHandle, time::sleep};
+
+use super::{
+	syncer::{DbSyncer, SyncOptions},
+	SyncError,
+};
+
+use crate::{metrics::SYNCER_RESTARTS, Error};
+
+/// Delay before restarting a syncer that panicked for the first time
+const INITIAL_RESTART_BACKOFF: Duration = Duration::from_secs(1);
+/// Maximum delay before restarting a syncer that panicked
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(60);
+
+/// State of a supervised syncer
+#[derive(Debug, Clone)]
+pub enum SyncerState {
+	Running,
+	/// Bumped up to the contained height
+	Done(u64),
+	Failed(String),
+	/// Stopped by a shutdown request
+	Cancelled,
+}
+
+/// Status of a supervised syncer
+#[derive(Debug, Clone)]
+pub struct SyncerStatus {
+	pub name: String,
+	pub restarts: u32,
+	pub state: SyncerState,
+}
+
+impl Display for SyncerStatus {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"{}: {:?} ({} restarts)",
+			self.name, self.state, self.restarts
+		)
+	}
+}
+
+/// Run several independent syncers concurrently
+///
+/// Each syncer is bumped in its own task.
+/// A task that panics is restarted, with an exponential backoff.
+/// All tasks stop as soon as `true` is sent on the shared shutdown channel.
+pub(crate) struct Supervisor {
+	shutdown: watch::Receiver<bool>,
+	tasks: Vec<JoinHandle<Result<u64, Error>>>,
+	statuses: Arc<Mutex<Vec<SyncerStatus>>>,
+}
+
+impl Supervisor {
+	pub fn new(shutdown: watch::Receiver<bool>) -> Supervisor {
+		Supervisor {
+			shutdown,
+			tasks: vec![],
+			statuses: Arc::new(Mutex::new(vec![])),
+		}
+	}
+
+	/// Bump `syncer` from `from` to `to` in a new task
+	pub fn spawn<S>(&mut self, syncer: S, from: Option<u64>, to: u64, options: SyncOptions)
+	where
+		S: DbSyncer + Send + Sync + 'static,
+	{
+		let syncer = Arc::new(syncer);
+		let statuses = self.statuses.clone();
+		let mut shutdown = self.shutdown.clone();
+
+		let index = {
+			let mut statuses = statuses.lock().unwrap();
+			statuses.push(SyncerStatus {
+				name: syncer.to_string(),
+				restarts: 0,
+				state: SyncerState::Running,
+			});
+			statuses.len() - 1
+		};
+
+		let task = tokio::spawn(async move {
+			let mut backoff = INITIAL_RESTART_BACKOFF;
+
+			let result = loop {
+				let s = syncer.clone();
+				let o = options.clone();
+				let mut attempt = tokio::spawn(async move { s.bump(from, to, &o).await });
+
+				let outcome = tokio::select! {
+					outcome = &mut attempt => outcome,
+					_ = wait_for_shutdown(&mut shutdown) => {
+						attempt.abort();
+						break Err(SyncError::Cancelled.into())
+					},
+				};
+
+				match outcome {
+					Ok(result) => break result,
+					Err(err) if err.is_panic() => {
+						error!("{syncer}: panicked, restarting in {backoff:?}");
+						SYNCER_RESTARTS.with_label_values(&[&syncer.to_string()]).inc();
+						statuses.lock().unwrap()[index].restarts += 1;
+
+						sleep(backoff).await;
+						backoff = min(backoff * 2, MAX_RESTART_BACKOFF);
+					},
+					Err(err) => break Err(err.into()),
+				}
+			};
+
+			statuses.lock().unwrap()[index].state = match &result {
+				Ok(height) => SyncerState::Done(*height),
+				Err(Error::Sync(SyncError::Cancelled)) => SyncerState::Cancelled,
+				Err(err) => SyncerState::Failed(err.to_string()),
+			};
+
+			result
+		});
+
+		self.tasks.push(task);
+	}
+
+	/// Wait for every syncer to be done
+	///
+	/// Log the status of every syncer and return their results in spawn order
+	pub async fn join_all(self) -> Vec<Result<u64, Error>> {
+		let mut results = Vec::with_capacity(self.tasks.len());
+		for task in self.tasks {
+			results.push(task.await.unwrap_or_else(|err| Err(err.into())));
+		}
+
+		let statuses = self.statuses.lock().unwrap();
+		for status in statuses.iter() {
+			// Logged on every tick, what was done is already logged by the syncer itself
+			match status.state {
+				SyncerState::Done(_) => debug!("{status}"),
+				_ => info!("{status}"),
+			}
+		}
+
+		results
+	}
+}
+
+// Resolve once `true` has been sent on the shutdown channel
+pub(crate) async fn wait_for_shutdown(shutdown: &mut watch::Receiver<bool>) {
+	loop {
+		if *shutdown.borrow() {
+			return
+		}
+		// The sender is gone, no shutdown can be requested anymore
+		if shutdown.changed().await.is_err() {
+			std::future::pending::<()>().await
+		}
+	}
+}
@@ -1,14 +1,125 @@
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr, time::Duration};
 
 use async_trait::async_trait;
-use log::{info, warn};
+use clap::ArgEnum;
+use futures::future::BoxFuture;
+use log::{debug, info, warn};
+use tokio::{sync::mpsc, time::sleep};
 
-use crate::Error;
+use super::{EntryWriter, SyncError};
+
+use crate::{
+	metrics::{SYNC_FAILED_HEIGHTS, SYNC_HEAD_BEHIND, SYNC_RETRIES},
+	Error,
+};
+
+/// Delay between two attempts at creating the same entry
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+/// Number of progress events kept for a slow `DbSyncer::catch_up` consumer
+const PROGRESS_BUFFER: usize = 128;
+
+/// What to do with a height that still fails once its retries are exhausted
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailurePolicy {
+	/// Log the failure and go on with the next height.
+	/// The height is left as a hole in database, until reprocessed.
+	Skip,
+	/// Stop the sync with an error.
+	/// The indexer exits so that an orchestrator can restart it, which will resume from the
+	/// failed height.
+	Halt,
+}
+
+/// Order in which `DbSyncer::bump` goes through its range
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FillOrder {
+	OldestFirst,
+	/// Recent heights, the most queried ones, are available sooner.
+	/// If interrupted, the heights not reached yet are left as holes below the stored head, until
+	/// reprocessed.
+	NewestFirst,
+}
+
+/// How often `DbSyncer::bump` commits the entries it writes
+///
+/// Grouping the writes of several heights in a single transaction saves the cost of committing
+/// each of them. A chunk whose heights fail to be created, or to be committed, is rolled back and
+/// its heights created again one at a time, retries and failure policy applying.
+/// The head published by `on_entry_created` and progress reports only come after the commit.
+/// Raw payloads are stored on their own, whatever the strategy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommitStrategy {
+	/// Every write commits on its own, as soon as issued.
+	/// A crash leaves the height being created partially stored, until reprocessed.
+	PerRow,
+	/// The heights of a prefetch batch, see `DbSyncer::batch_size`, commit together.
+	/// A crash loses the batch being written, but never leaves a height partially stored: the sync
+	/// resumes from the first height of the batch.
+	PerBatch,
+	/// Every `n` heights commit together, with the crash semantics of `PerBatch`
+	PerChunk(usize),
+}
+
+impl FromStr for CommitStrategy {
+	type Err = String;
+
+	/// Parse `per-row`, `per-batch` or `per-chunk:<n>`
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.split_once(':') {
+			None if s == "per-row" => Ok(CommitStrategy::PerRow),
+			None if s == "per-batch" => Ok(CommitStrategy::PerBatch),
+			Some(("per-chunk", n)) => match n.parse() {
+				Ok(n) if n > 0 => Ok(CommitStrategy::PerChunk(n)),
+				_ => Err(format!(
+					"invalid chunk size '{n}', expected a positive integer"
+				)),
+			},
+			_ => Err(format!(
+				"invalid commit strategy '{s}', expected per-row, per-batch or per-chunk:<n>"
+			)),
+		}
+	}
+}
+
+/// Options of a `DbSyncer::bump`
+#[derive(Debug, Clone)]
+pub struct SyncOptions {
+	/// Number of times a failing height is retried
+	pub retries: u32,
+	pub on_permanent_failure: FailurePolicy,
+	pub fill_order: FillOrder,
+	pub commit_strategy: CommitStrategy,
+}
+
+/// Progress of a `DbSyncer::catch_up`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+// Only read by the consumers of the progress stream
+#[allow(dead_code)]
+pub struct SyncProgress {
+	/// Height just created
+	pub height: u64,
+	/// First height of the range
+	pub from: u64,
+	/// Last height of the range
+	pub to: u64,
+}
+
+/// How `create_new_entry` writes in database
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteMode {
+	/// Keep already stored entries
+	Insert,
+	/// Replace already stored entries
+	Overwrite,
+}
 
 /// Sugar around storing chain block in database
 ///
 /// Allow for full control over how to pull entry and what to store.
 /// Only take care of the looping part.
+///
+/// Heights are in the unit of the layer synced: slots for the consensus layer, block numbers for
+/// the execution one. They never match, missed slots holding no block.
 #[async_trait]
 pub trait DbSyncer: Display {
 	type NodeClient;
@@ -19,17 +130,159 @@ pub trait DbSyncer: Display {
 	///
 	/// If from is None, the height following the highest stored height in db will be used.
 	/// If db empty from will be 0.
-	async fn bump(&self, from: Option<u64>, to: u64) -> Result<u64, Error> {
-		let from = from.unwrap_or_else(|| self.get_db_height().map_or(0, |slot| slot + 1));
+	///
+	/// Heights are created in `options.fill_order`, `batch_size` at a time, each batch being
+	/// prefetched first.
+	/// A failing height is retried `options.retries` times, then handled according to
+	/// `options.on_permanent_failure`. Entries are committed according to
+	/// `options.commit_strategy`.
+	async fn bump(&self, from: Option<u64>, to: u64, options: &SyncOptions) -> Result<u64, Error> {
+		self.bump_reporting(from, to, options, None).await
+	}
+
+	/// Bump database, streaming its progress
+	///
+	/// Return the bump, to be awaited, along with a stream of a `SyncProgress` for every height
+	/// created. The stream ends once the bump is over.
+	///
+	/// Up to `PROGRESS_BUFFER` events are buffered. Past that, or once the stream is dropped, new
+	/// events are discarded instead of waiting for the consumer: reading the stream is optional
+	/// and never slows down the bump.
+	fn catch_up<'a>(
+		&'a self,
+		from: Option<u64>,
+		to: u64,
+		options: &'a SyncOptions,
+	) -> (
+		BoxFuture<'a, Result<u64, Error>>,
+		mpsc::Receiver<SyncProgress>,
+	)
+	where
+		Self: Sync,
+	{
+		let (sender, receiver) = mpsc::channel(PROGRESS_BUFFER);
+		let bump = self.bump_reporting(from, to, options, Some(sender));
+
+		(bump, receiver)
+	}
+
+	/// Implementation of `bump`, sending a `SyncProgress` to `progress` for every height created
+	async fn bump_reporting(
+		&self,
+		from: Option<u64>,
+		to: u64,
+		options: &SyncOptions,
+		progress: Option<mpsc::Sender<SyncProgress>>,
+	) -> Result<u64, Error> {
+		let from = match from {
+			Some(from) => from,
+			None => match self.get_db_height() {
+				Ok(height) => height + 1,
+				Err(Error::Diesel(diesel::result::Error::NotFound)) => 0,
+				// Anything else than an empty table must not restart the sync from genesis
+				Err(err) => return Err(err),
+			},
+		};
+
+		// Most ticks once caught up, not worth more than a debug line
+		if from == to + 1 {
+			debug!("{self}: Database synced up to node at {to}");
+			return Ok(to)
+		}
+		// The node went back, or the range asked for is past it
+		if from > to {
+			SYNC_HEAD_BEHIND.with_label_values(&[&self.to_string()]).inc();
+			warn!(
+				"{self}: Asked to sync from height {from}, above the node head {to}: was the chain \
+				 reorganized, or is the node behind or misconfigured? Nothing synced"
+			);
+			return Ok(to)
+		}
 
 		info!("{self}: Bumping database from heigth {from} to {to}",);
 
-		for height in from..=to {
-			match self.create_new_entry(height).await {
-				Ok(()) => info!("{self}: Saved entry at height {height}"),
-				Err(err) => warn!("{self}: Failed to create enty at height {height}: {err}"),
+		let mut heights: Box<dyn Iterator<Item = u64> + Send> = match options.fill_order {
+			FillOrder::OldestFirst => Box::new(from..=to),
+			FillOrder::NewestFirst => Box::new((from..=to).rev()),
+		};
+
+		// Highest height created so far, whatever the fill order
+		let mut highest_created: Option<u64> = None;
+		let mut report_created = |height: u64| {
+			info!("{self}: Saved entry at height {height}");
+			if let Some(progress) = &progress {
+				// A full or closed stream must not hold the bump
+				progress.try_send(SyncProgress { height, from, to }).ok();
+			}
+			if highest_created.map_or(true, |h| height > h) {
+				highest_created = Some(height);
+				self.on_entry_created(height);
+			}
+		};
+
+		let batch_size = self.batch_size().max(1);
+		// Heights committed together, along with the writer of their transaction
+		let commit_chunk = match (options.commit_strategy, self.entry_writer()) {
+			(CommitStrategy::PerBatch, Some(writer)) => Some((batch_size, writer)),
+			(CommitStrategy::PerChunk(n), Some(writer)) => Some((n.max(1), writer)),
+			_ => None,
+		};
+		loop {
+			let chunk_size = commit_chunk.map_or(batch_size, |(size, _)| size);
+			let chunk: Vec<u64> = heights.by_ref().take(chunk_size).collect();
+			if chunk.is_empty() {
+				break
+			}
+
+			if let Some((_, writer)) = commit_chunk {
+				match create_in_one_transaction(self, writer, &chunk, batch_size).await {
+					Ok(()) => {
+						chunk.iter().for_each(|height| report_created(*height));
+						continue
+					},
+					Err(err) => warn!(
+						"{self}: Failed to commit heights {} to {} together, creating them one \
+						 at a time: {err}",
+						chunk[0],
+						chunk[chunk.len() - 1]
+					),
+				}
+			}
+
+			for batch in chunk.chunks(batch_size) {
+				if batch.len() > 1 {
+					self.prefetch(batch).await;
+				}
+
+				for &height in batch {
+					let mut attempt = 0;
+					loop {
+						match self.create_new_entry(height).await {
+							Ok(()) => {
+								report_created(height);
+								break
+							},
+							Err(err) if attempt < options.retries => {
+								attempt += 1;
+								SYNC_RETRIES.with_label_values(&[&self.to_string()]).inc();
+								warn!("{self}: Failed to create enty at height {height}, retry {attempt}: {err}");
+								sleep(RETRY_DELAY).await;
+							},
+							Err(err) => {
+								warn!("{self}: Failed to create enty at height {height}: {err}");
+								SYNC_FAILED_HEIGHTS.with_label_values(&[&self.to_string()]).inc();
+								match options.on_permanent_failure {
+									FailurePolicy::Skip => break,
+									FailurePolicy::Halt =>
+										return Err(SyncError::RetriesExhausted(height).into()),
+								}
+							},
+						}
+					}
+				}
 			}
 		}
+		info!("{self}: Database synced up to node at {to}");
 
 		Ok(to)
 	}
@@ -38,6 +291,8 @@ pub trait DbSyncer: Display {
 	fn node_client(&self) -> Self::NodeClient;
 
 	/// Return the database head height
+	///
+	/// Fail with diesel's `NotFound` if nothing is stored yet.
 	fn get_db_height(&self) -> Result<u64, Error>;
 
 	/// Register a new entry in database
@@ -48,4 +303,241 @@ pub trait DbSyncer: Display {
 	/// Called internaly by `keep_in_sync`.
 	/// Should fetch data from the node and store them in database.
 	async fn create_new_entry(&self, height: u64) -> Result<(), Error>;
+
+	/// Number of heights `bump` hands to `prefetch` at once, 1 to never prefetch
+	fn batch_size(&self) -> usize {
+		1
+	}
+
+	/// Fetch the data of `heights` from the node ahead of their `create_new_entry`, in as few
+	/// requests as possible
+	///
+	/// Best effort: `create_new_entry` fetches whatever was not prefetched itself, so failures
+	/// are not returned.
+	async fn prefetch(&self, _heights: &[u64]) {}
+
+	/// Called by `bump` every time an entry was successfully created at `height`, higher than
+	/// every entry it created before
+	fn on_entry_created(&self, _height: u64) {}
+
+	/// Return the writer `create_new_entry` writes with, for `bump` to commit several entries at
+	/// once
+	///
+	/// Every write commits on its own if None, whatever `SyncOptions::commit_strategy`.
+	fn entry_writer(&self) -> Option<&EntryWriter> {
+		None
+	}
+}
+
+// Create the entries at `heights` in a single transaction of `writer`, prefetching them
+// `batch_size` at a time
+//
+// Nothing is committed unless every one of them is created.
+async fn create_in_one_transaction<S: DbSyncer + Sync + ?Sized>(
+	syncer: &S,
+	writer: &EntryWriter,
+	heights: &[u64],
+	batch_size: usize,
+) -> Result<(), Error> {
+	writer.begin_chunk()?;
+
+	for batch in heights.chunks(batch_size) {
+		if batch.len() > 1 {
+			syncer.prefetch(batch).await;
+		}
+		for &height in batch {
+			if let Err(err) = syncer.create_new_entry(height).await {
+				writer.rollback_chunk();
+				return Err(err)
+			}
+		}
+	}
+
+	writer.commit_chunk()
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Mutex;
+
+	use super::*;
+
+	/// What `FakeSyncer::get_db_height` answers
+	enum DbHeight {
+		Empty,
+		Stored(u64),
+		Broken,
+	}
+
+	/// Records the heights created, fetching nothing
+	struct FakeSyncer {
+		name: &'static str,
+		db_height: DbHeight,
+		created: Mutex<Vec<u64>>,
+	}
+
+	impl FakeSyncer {
+		fn new(name: &'static str, db_height: DbHeight) -> Self {
+			FakeSyncer {
+				name,
+				db_height,
+				created: Mutex::new(vec![]),
+			}
+		}
+
+		fn created(&self) -> Vec<u64> {
+			self.created.lock().unwrap().clone()
+		}
+	}
+
+	impl Display for FakeSyncer {
+		fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+			write!(f, "{}", self.name)
+		}
+	}
+
+	#[async_trait]
+	impl DbSyncer for FakeSyncer {
+		type NodeClient = ();
+
+		fn node_client(&self) -> Self::NodeClient {}
+
+		fn get_db_height(&self) -> Result<u64, Error> {
+			match self.db_height {
+				DbHeight::Empty => Err(Error::Diesel(diesel::result::Error::NotFound)),
+				DbHeight::Stored(height) => Ok(height),
+				DbHeight::Broken => Err(Error::Diesel(diesel::result::Error::RollbackTransaction)),
+			}
+		}
+
+		async fn create_new_entry(&self, height: u64) -> Result<(), Error> {
+			self.created.lock().unwrap().push(height);
+			Ok(())
+		}
+	}
+
+	fn options() -> SyncOptions {
+		SyncOptions {
+			retries: 0,
+			on_permanent_failure: FailurePolicy::Skip,
+			fill_order: FillOrder::OldestFirst,
+			commit_strategy: CommitStrategy::PerRow,
+		}
+	}
+
+	#[tokio::test]
+	async fn explicit_from_is_used_as_is() {
+		let syncer = FakeSyncer::new("explicit_from", DbHeight::Stored(10));
+
+		assert_eq!(syncer.bump(Some(3), 5, &options()).await.unwrap(), 5);
+		assert_eq!(syncer.created(), vec![3, 4, 5]);
+	}
+
+	#[tokio::test]
+	async fn empty_database_syncs_from_genesis() {
+		let syncer = FakeSyncer::new("empty_database", DbHeight::Empty);
+
+		assert_eq!(syncer.bump(None, 2, &options()).await.unwrap(), 2);
+		assert_eq!(syncer.created(), vec![0, 1, 2]);
+	}
+
+	#[tokio::test]
+	async fn stored_database_syncs_from_the_next_height() {
+		let syncer = FakeSyncer::new("stored_database", DbHeight::Stored(4));
+
+		assert_eq!(syncer.bump(None, 6, &options()).await.unwrap(), 6);
+		assert_eq!(syncer.created(), vec![5, 6]);
+	}
+
+	#[tokio::test]
+	async fn database_errors_are_propagated() {
+		let syncer = FakeSyncer::new("database_errors", DbHeight::Broken);
+
+		let result = syncer.bump(None, 6, &options()).await;
+		assert!(matches!(
+			result,
+			Err(Error::Diesel(diesel::result::Error::RollbackTransaction))
+		));
+		assert!(syncer.created().is_empty());
+	}
+
+	#[tokio::test]
+	async fn synced_database_is_a_no_op() {
+		let syncer = FakeSyncer::new("synced_database", DbHeight::Stored(7));
+
+		assert_eq!(syncer.bump(None, 7, &options()).await.unwrap(), 7);
+		assert!(syncer.created().is_empty());
+	}
+
+	#[tokio::test]
+	async fn from_above_the_head_syncs_nothing() {
+		let syncer = FakeSyncer::new("from_above_head", DbHeight::Stored(9));
+
+		assert_eq!(syncer.bump(None, 5, &options()).await.unwrap(), 5);
+		assert_eq!(syncer.bump(Some(8), 5, &options()).await.unwrap(), 5);
+		assert!(syncer.created().is_empty());
+	}
+
+	#[tokio::test]
+	async fn only_from_above_the_head_counts_as_behind() {
+		let syncer = FakeSyncer::new("head_behind", DbHeight::Stored(9));
+		let behind = || SYNC_HEAD_BEHIND.with_label_values(&["head_behind"]).get();
+
+		syncer.bump(None, 9, &options()).await.unwrap();
+		assert_eq!(behind(), 0);
+
+		syncer.bump(None, 5, &options()).await.unwrap();
+		assert_eq!(behind(), 1);
+
+		syncer.bump(Some(8), 6, &options()).await.unwrap();
+		assert_eq!(behind(), 2);
+	}
+
+	#[tokio::test]
+	async fn newest_first_goes_down_the_range() {
+		let syncer = FakeSyncer::new("newest_first", DbHeight::Empty);
+		let options = SyncOptions {
+			fill_order: FillOrder::NewestFirst,
+			..options()
+		};
+
+		assert_eq!(syncer.bump(Some(1), 3, &options).await.unwrap(), 3);
+		assert_eq!(syncer.created(), vec![3, 2, 1]);
+	}
+
+	#[tokio::test]
+	async fn chunks_are_created_one_at_a_time_without_writer() {
+		let syncer = FakeSyncer::new("no_writer", DbHeight::Empty);
+		let options = SyncOptions {
+			commit_strategy: CommitStrategy::PerChunk(2),
+			..options()
+		};
+
+		assert_eq!(syncer.bump(Some(1), 3, &options).await.unwrap(), 3);
+		assert_eq!(syncer.created(), vec![1, 2, 3]);
+	}
+
+	#[test]
+	fn commit_strategies_are_parsed() {
+		assert_eq!("per-row".parse(), Ok(CommitStrategy::PerRow));
+		assert_eq!("per-batch".parse(), Ok(CommitStrategy::PerBatch));
+		assert_eq!("per-chunk:50".parse(), Ok(CommitStrategy::PerChunk(50)));
+	}
+
+	#[test]
+	fn invalid_commit_strategies_are_rejected() {
+		for invalid in [
+			"",
+			"per-chunk",
+			"per-chunk:0",
+			"per-chunk:-1",
+			"per-row:2",
+			"per-block",
+		] {
+			assert!(
+				invalid.parse::<CommitStrategy>().is_err(),
+				"{invalid} accepted"
+			);
+		}
+	}
 }
@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+use kiln_postgres::{get_connection, NewSlot, PgConnectionPool, Slot};
+use log::{error, info, warn};
+use tokio::sync::watch;
+
+use super::{sync_paused, ticker, ValidatorsCountCheck};
+
+use crate::{client_consensus, Error};
+
+/// Interval between two backfill passes
+const BACKFILL_INTERVAL: Duration = Duration::from_secs(60);
+/// Maximum number of slots whose count is fetched in a single pass
+const BACKFILL_BATCH_SIZE: u32 = 100;
+
+/// Fill the validators count of the slots stored without one, until shutdown
+///
+/// Counts are checked like the ones of new slots, if `check` is set. Slots the node returns no
+/// count for, or whose count is rejected, are left without one: passes go on above them, and
+/// retry them once every slot missing a count was gone through.
+pub(crate) async fn backfill_validator_counts(
+	conn_pool: PgConnectionPool,
+	eth2: client_consensus::Client,
+	check: Option<ValidatorsCountCheck>,
+	shutdown: watch::Receiver<bool>,
+) {
+	let mut ticker = ticker(BACKFILL_INTERVAL);
+	// Highest height of the previous pass
+	let mut after = None;
+	loop {
+		ticker.tick().await;
+		if *shutdown.borrow() {
+			return
+		}
+		if sync_paused(&conn_pool) {
+			continue
+		}
+
+		match backfill_pass(&conn_pool, &eth2, check, after).await {
+			Ok(last) => after = last,
+			Err(err) => error!("failed to backfill validators counts: {err}"),
+		}
+	}
+}
+
+// Fill the count of a batch of slots missing one, above `after` if set
+//
+// Return the highest height of the batch, None once there is none left above `after`.
+async fn backfill_pass(
+	conn_pool: &PgConnectionPool,
+	eth2: &client_consensus::Client,
+	check: Option<ValidatorsCountCheck>,
+	after: Option<u64>,
+) -> Result<Option<u64>, Error> {
+	let heights =
+		Slot::heights_missing_count(&get_connection(conn_pool)?, after, BACKFILL_BATCH_SIZE)?;
+	let last = match heights.last() {
+		Some(last) => *last,
+		None => return Ok(None),
+	};
+	info!("backfilling validators count of {} slots", heights.len());
+
+	for height in heights {
+		let count = match client_consensus::get_validators_count_at_slot(eth2, height).await? {
+			Some(count) => count,
+			None => {
+				warn!("no validators returned for slot {height}, will be retried");
+				continue
+			},
+		};
+		let count = match check {
+			Some(check) => check.checked(&get_connection(conn_pool)?, height, count)?,
+			None => Some(count),
+		};
+		if let Some(count) = count {
+			NewSlot::set_validators_count(&get_connection(conn_pool)?, height, count)?;
+		}
+	}
+
+	Ok(Some(last))
+}
@@ -1,6 +1,5 @@
-use eth2::BeaconNodeHttpClient;
-use kiln_postgres::{NewValidators, PgConnectionPool};
-use log::info;
+use kiln_postgres::{get_connection, NewStat, NewValidators, PgConnectionPool, StatKey, Validator};
+use log::debug;
 
 use crate::{client_consensus, error::Error};
 
@@ -9,17 +8,25 @@ use super::SyncError;
 /// Update db validators
 pub async fn update_validators(
 	conn_pool: PgConnectionPool,
-	client: &BeaconNodeHttpClient,
+	client: &client_consensus::Client,
 	slot: u64,
 ) -> Result<(), Error> {
-	info!("syncing db with validators at slot {slot}");
+	debug!("syncing db with validators at slot {slot}");
 
 	let validators = client_consensus::get_validators_at_slot(client, slot)
 		.await?
 		.ok_or(SyncError::NoValidators)?;
 
+	let conn = get_connection(&conn_pool)?;
+	let slashed_before = Validator::count_slashed(&conn)?;
+
 	let new_validators = NewValidators::from_iter(validators.into_iter().map(|v| v.into()));
-	new_validators.batch_upsert(&conn_pool.get().unwrap())?;
+	new_validators.batch_upsert(&conn)?;
+
+	// Tell the api its cached slashing statuses are stale
+	if Validator::count_slashed(&conn)? != slashed_before {
+		NewStat::increment(&conn, StatKey::ValidatorsGeneration)?;
+	}
 
 	Ok(())
 }
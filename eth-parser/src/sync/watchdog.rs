@@ -0,0 +1,94 @@
+use std::time::{Duration, Instant};
+
+use kiln_postgres::PgConnectionPool;
+use log::{error, warn};
+use tokio::sync::watch;
+
+use super::{sync_paused, syncer::DbSyncer, ticker};
+
+use crate::{client_consensus, metrics::SYNC_STALLS, Error};
+
+/// Interval between two progress checks
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+// Database height of a syncer, along with the node head when it was first seen
+struct Progress {
+	db_height: Option<u64>,
+	node_head: u64,
+	since: Instant,
+}
+
+/// Report `syncer` as stalled whenever its database height did not move for `threshold` while the
+/// consensus node head did, until shutdown
+///
+/// Catches the bumps stuck without failing, e.g. on a node request that never completes, which
+/// nothing else logs. Each stall is logged once per `threshold`. With `exit`, the indexer exits
+/// instead, to be restarted by an orchestrator. Paused syncs are not watched.
+pub(crate) async fn watch_sync_progress<S>(
+	syncer: S,
+	conn_pool: PgConnectionPool,
+	eth2: client_consensus::Client,
+	threshold: Duration,
+	exit: bool,
+	shutdown: watch::Receiver<bool>,
+) where
+	S: DbSyncer + Send + Sync,
+{
+	let mut ticker = ticker(CHECK_INTERVAL);
+	let mut progress: Option<Progress> = None;
+	loop {
+		ticker.tick().await;
+		if *shutdown.borrow() {
+			return
+		}
+		if sync_paused(&conn_pool) {
+			progress = None;
+			continue
+		}
+
+		let db_height = match syncer.get_db_height() {
+			Ok(height) => Some(height),
+			Err(Error::Diesel(diesel::result::Error::NotFound)) => None,
+			Err(err) => {
+				warn!("{syncer}: failed to read the database height for the watchdog: {err}");
+				continue
+			},
+		};
+		let node_head = match client_consensus::get_head_height(&eth2).await {
+			Ok(head) => head,
+			Err(err) => {
+				warn!("{syncer}: failed to read the node head for the watchdog: {err}");
+				continue
+			},
+		};
+
+		match &mut progress {
+			Some(p) if p.db_height == db_height => {
+				if p.since.elapsed() < threshold || node_head <= p.node_head {
+					continue
+				}
+
+				error!(
+					"{syncer}: stuck at height {:?} for {:?} while the node head moved from {} to \
+					 {node_head}",
+					db_height,
+					p.since.elapsed(),
+					p.node_head
+				);
+				SYNC_STALLS.with_label_values(&[&syncer.to_string()]).inc();
+				if exit {
+					error!("{syncer}: exiting on the stall, to be restarted");
+					std::process::exit(1)
+				}
+				p.node_head = node_head;
+				p.since = Instant::now();
+			},
+			_ =>
+				progress = Some(Progress {
+					db_height,
+					node_head,
+					since: Instant::now(),
+				}),
+		}
+	}
+}
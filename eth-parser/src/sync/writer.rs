@@ -0,0 +1,114 @@
+use std::{
+	ops::Deref,
+	sync::{Mutex, MutexGuard},
+};
+
+use diesel::connection::{Connection, TransactionManager};
+use kiln_postgres::{get_connection, DbConnection, PgConnectionPool, PgPooledConnection};
+use log::warn;
+
+use crate::Error;
+
+/// Connections a syncer writes its entries with
+///
+/// Every write gets its own connection from the pool and commits on its own, unless a chunk is
+/// open: the writes then all go through the chunk connection, inside a single transaction, until
+/// the chunk is committed or rolled back. Reads meant to see the entries written before them, such
+/// as the issuers already known, must go through it too.
+pub(crate) struct EntryWriter {
+	pool: PgConnectionPool,
+	chunk: Mutex<Option<PgPooledConnection>>,
+}
+
+/// A connection of an `EntryWriter`
+///
+/// Holds the chunk, if one is open, until dropped: it must not be kept across an await.
+pub(crate) enum WriteConnection<'a> {
+	Pooled(PgPooledConnection),
+	Chunk(MutexGuard<'a, Option<PgPooledConnection>>),
+}
+
+impl Deref for WriteConnection<'_> {
+	type Target = DbConnection;
+
+	fn deref(&self) -> &DbConnection {
+		match self {
+			WriteConnection::Pooled(conn) => conn,
+			// Safe to unwrap because only an open chunk is handed out
+			WriteConnection::Chunk(chunk) => chunk.as_ref().unwrap(),
+		}
+	}
+}
+
+impl EntryWriter {
+	pub fn new(pool: PgConnectionPool) -> EntryWriter {
+		EntryWriter {
+			pool,
+			chunk: Mutex::new(None),
+		}
+	}
+
+	/// Return the pool the connections are taken from
+	pub fn pool(&self) -> &PgConnectionPool {
+		&self.pool
+	}
+
+	/// Return the connection to write with: the chunk one if open, a pooled one otherwise
+	pub fn conn(&self) -> Result<WriteConnection<'_>, Error> {
+		let chunk = self.chunk.lock().unwrap();
+		if chunk.is_some() {
+			return Ok(WriteConnection::Chunk(chunk))
+		}
+		drop(chunk);
+
+		Ok(WriteConnection::Pooled(get_connection(&self.pool)?))
+	}
+
+	/// Open a chunk: the following writes are only committed together, by `commit_chunk`
+	///
+	/// A chunk left open, e.g. by an interrupted bump, is rolled back first.
+	pub fn begin_chunk(&self) -> Result<(), Error> {
+		self.rollback_chunk();
+
+		let conn = get_connection(&self.pool)?;
+		conn.transaction_manager().begin_transaction(&*conn)?;
+		*self.chunk.lock().unwrap() = Some(conn);
+
+		Ok(())
+	}
+
+	/// Commit the writes of the open chunk, if any, and close it
+	pub fn commit_chunk(&self) -> Result<(), Error> {
+		let conn = match self.chunk.lock().unwrap().take() {
+			Some(conn) => conn,
+			None => return Ok(()),
+		};
+
+		let committed = conn.transaction_manager().commit_transaction(&*conn);
+		if committed.is_err() {
+			// Postgres already rolled it back, but diesel still counts the transaction as open
+			conn.transaction_manager().rollback_transaction(&*conn).ok();
+		}
+
+		committed.map_err(Error::from)
+	}
+
+	/// Discard the writes of the open chunk, if any, and close it
+	pub fn rollback_chunk(&self) {
+		let conn = match self.chunk.lock().unwrap().take() {
+			Some(conn) => conn,
+			None => return,
+		};
+
+		if let Err(err) = conn.transaction_manager().rollback_transaction(&*conn) {
+			warn!("failed to roll back a chunk of entries: {err}");
+		}
+	}
+}
+
+// A connection must never go back to the pool inside a transaction
+impl Drop for EntryWriter {
+	fn drop(&mut self) {
+		self.rollback_chunk();
+	}
+}
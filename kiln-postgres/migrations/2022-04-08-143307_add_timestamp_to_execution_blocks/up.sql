@@ -0,0 +1,4 @@
+-- Your SQL goes here
+
+ALTER TABLE execution_blocks
+ADD COLUMN "timestamp" BIGINT;
@@ -0,0 +1,10 @@
+-- Your SQL goes here
+
+CREATE TABLE reprocess_jobs (
+    id SERIAL PRIMARY KEY,
+    layer VARCHAR NOT NULL,
+    from_height BIGINT NOT NULL,
+    to_height BIGINT NOT NULL,
+    processed_height BIGINT,
+    "status" VARCHAR NOT NULL DEFAULT 'pending'
+);
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+
+CREATE TABLE stats (
+    "name" VARCHAR PRIMARY KEY,
+    "value" BIGINT NOT NULL
+);
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+
+DROP INDEX slots_proposer_index_idx;
+
+ALTER TABLE slots
+DROP COLUMN proposer_index;
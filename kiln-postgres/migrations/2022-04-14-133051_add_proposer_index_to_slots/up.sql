@@ -0,0 +1,6 @@
+-- Your SQL goes here
+
+ALTER TABLE slots
+ADD COLUMN proposer_index BIGINT;
+
+CREATE INDEX slots_proposer_index_idx ON slots (proposer_index);
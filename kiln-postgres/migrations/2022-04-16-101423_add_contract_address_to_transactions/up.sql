@@ -0,0 +1,4 @@
+-- Your SQL goes here
+
+ALTER TABLE transactions
+ADD COLUMN contract_address BYTEA;
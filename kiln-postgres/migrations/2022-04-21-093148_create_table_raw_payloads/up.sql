@@ -0,0 +1,8 @@
+-- Your SQL goes here
+
+CREATE TABLE raw_payloads (
+    "layer" VARCHAR NOT NULL,
+    "height" BIGINT NOT NULL,
+    "payload" TEXT NOT NULL,
+    PRIMARY KEY ("layer", "height")
+);
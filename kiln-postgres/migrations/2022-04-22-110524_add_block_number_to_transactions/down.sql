@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+
+DROP INDEX transactions_from_block_number_idx;
+
+ALTER TABLE transactions
+DROP COLUMN block_number;
@@ -0,0 +1,14 @@
+-- Your SQL goes here
+
+ALTER TABLE transactions
+ADD COLUMN block_number BIGINT;
+
+UPDATE transactions
+SET block_number = execution_blocks.number
+FROM execution_blocks
+WHERE execution_blocks.hash = transactions.block_hash;
+
+ALTER TABLE transactions
+ALTER COLUMN block_number SET NOT NULL;
+
+CREATE INDEX transactions_from_block_number_idx ON transactions ("from", block_number);
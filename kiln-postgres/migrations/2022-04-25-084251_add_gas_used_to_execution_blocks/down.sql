@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE execution_blocks
+DROP COLUMN gas_used;
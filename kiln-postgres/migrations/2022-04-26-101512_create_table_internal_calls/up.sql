@@ -0,0 +1,13 @@
+-- Your SQL goes here
+
+CREATE TABLE internal_calls (
+    "transaction_hash" BYTEA NOT NULL,
+    "trace_address" VARCHAR NOT NULL,
+    "origin" BYTEA NOT NULL,
+    "from" BYTEA NOT NULL,
+    "to" BYTEA NOT NULL,
+    "block_number" BIGINT NOT NULL,
+    PRIMARY KEY ("transaction_hash", "trace_address")
+);
+
+CREATE INDEX internal_calls_origin_block_number ON internal_calls ("origin", "block_number");
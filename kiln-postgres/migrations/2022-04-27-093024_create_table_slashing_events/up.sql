@@ -0,0 +1,10 @@
+-- Your SQL goes here
+
+CREATE TABLE slashing_events (
+    "slot" BIGINT NOT NULL,
+    "validator_index" BIGINT NOT NULL,
+    "kind" VARCHAR NOT NULL,
+    PRIMARY KEY ("slot", "validator_index")
+);
+
+CREATE INDEX slashing_events_validator_index ON slashing_events ("validator_index");
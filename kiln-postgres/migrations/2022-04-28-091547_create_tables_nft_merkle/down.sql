@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE nft_merkle_leaves;
+DROP TABLE nft_merkle_trees;
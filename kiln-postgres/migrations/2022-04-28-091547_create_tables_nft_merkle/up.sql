@@ -0,0 +1,16 @@
+-- Your SQL goes here
+
+CREATE TABLE nft_merkle_trees (
+    "id" SERIAL PRIMARY KEY,
+    "root" BYTEA NOT NULL,
+    "leaves_count" BIGINT NOT NULL
+);
+
+CREATE TABLE nft_merkle_leaves (
+    "tree_id" INTEGER NOT NULL REFERENCES nft_merkle_trees ("id") ON DELETE CASCADE,
+    "address" BYTEA NOT NULL,
+    "nft" BYTEA NOT NULL,
+    "leaf_index" BIGINT NOT NULL,
+    "proof" BYTEA NOT NULL,
+    PRIMARY KEY ("tree_id", "address")
+);
@@ -0,0 +1,16 @@
+-- Your SQL goes here
+
+CREATE TABLE slots_history (
+    "id" SERIAL PRIMARY KEY,
+    "height" BIGINT NOT NULL,
+    "block_hash" BYTEA,
+    "block_number" BIGINT,
+    "missed" BOOLEAN NOT NULL,
+    "proposer_index" BIGINT,
+    "validators_count" BIGINT,
+    "spec" VARCHAR,
+    "reason" VARCHAR NOT NULL,
+    "superseded_at" BIGINT NOT NULL DEFAULT EXTRACT(EPOCH FROM NOW())::BIGINT
+);
+
+CREATE INDEX slots_history_height ON slots_history ("height");
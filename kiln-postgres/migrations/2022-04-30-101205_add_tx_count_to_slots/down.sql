@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE slots_history DROP COLUMN "tx_count";
+ALTER TABLE slots DROP COLUMN "tx_count";
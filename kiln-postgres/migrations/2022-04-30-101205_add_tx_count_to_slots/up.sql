@@ -0,0 +1,4 @@
+-- Your SQL goes here
+
+ALTER TABLE slots ADD COLUMN "tx_count" BIGINT;
+ALTER TABLE slots_history ADD COLUMN "tx_count" BIGINT;
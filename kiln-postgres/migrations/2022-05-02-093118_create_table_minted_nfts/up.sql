@@ -0,0 +1,6 @@
+-- Your SQL goes here
+
+CREATE TABLE minted_nfts (
+    "address" BYTEA PRIMARY KEY,
+    "nft" BYTEA NOT NULL
+);
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+
+CREATE INDEX transactions_to_idx ON transactions ("to");
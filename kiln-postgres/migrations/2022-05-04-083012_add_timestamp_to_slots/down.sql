@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE slots_history DROP COLUMN "timestamp";
+ALTER TABLE slots DROP COLUMN "timestamp";
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+
+CREATE INDEX packed_nfts_nft_address_idx ON packed_nfts (nft, address);
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+
+CREATE INDEX slots_block_number_idx ON slots (block_number);
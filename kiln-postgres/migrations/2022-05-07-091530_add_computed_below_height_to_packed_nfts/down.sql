@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE packed_nfts DROP COLUMN computed_below_height;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+
+ALTER TABLE packed_nfts ADD COLUMN computed_below_height BIGINT;
@@ -0,0 +1,172 @@
+//! Fill a database with synthetic issuers and transactions, to benchmark the api without a chain
+//!
+//! Needs the `test-util` feature:
+//! `cargo run -p kiln-postgres --features test-util --bin seed -- --addresses 10000`
+//!
+//! Blocks are numbered from `--first-block`, far above the Kiln head by default, so a seeded
+//! database can also hold real data. Addresses and hashes are derived from a fixed tag, the same
+//! arguments always produce the same rows.
+//!
+//! The issuers count, when set, is maintained like the indexer does, each insert counting the
+//! issuers it adds.
+
+use clap::Parser;
+use kiln_postgres::{get_connection, NewExecBlock, NewTransaction, NewTransactions};
+use primitive_types::{H160, H256, U256};
+
+/// Leading bytes of the seeded addresses and hashes, to tell them apart from real ones
+const SEED_TAG: u64 = 0x5eed;
+
+/// Transactions inserted per statement, to stay under Postgres' 65535 bound parameters
+const INSERT_CHUNK_SIZE: usize = 5000;
+
+/// Seconds between two seeded blocks
+const BLOCK_TIME: u64 = 12;
+
+#[derive(Parser)]
+struct Args {
+	/// Number of issuers
+	#[clap(long, default_value_t = 1000)]
+	addresses: u64,
+
+	/// Number of transactions sent by each issuer
+	#[clap(long, default_value_t = 100)]
+	transactions: u64,
+
+	/// Share of the transactions that deploy a contract, between 0 and 1
+	#[clap(long, default_value_t = 0.05)]
+	deploy_ratio: f64,
+
+	/// Share of the transactions that call a contract, between 0 and 1. The others are transfers
+	#[clap(long, default_value_t = 0.5)]
+	call_ratio: f64,
+
+	/// Number of distinct contracts called
+	#[clap(long, default_value_t = 20)]
+	contracts: u64,
+
+	/// Largest value transferred, in wei
+	#[clap(long, default_value_t = 1_000_000_000_000_000_000)]
+	max_value: u64,
+
+	/// Number of transactions per block
+	#[clap(long, default_value_t = 200)]
+	block_size: u64,
+
+	/// Number of the first seeded block
+	#[clap(long, default_value_t = 1_000_000_000)]
+	first_block: u64,
+
+	/// Timestamp of the first seeded block, in seconds since the UNIX epoch
+	#[clap(long, default_value_t = 1_650_000_000)]
+	first_timestamp: u64,
+
+	/// Seed of the pseudo random distribution
+	#[clap(long, default_value_t = 42)]
+	seed: u64,
+}
+
+// xorshift64*, good enough to spread synthetic values, and reproducible without dependencies
+struct Rng(u64);
+
+impl Rng {
+	fn next(&mut self) -> u64 {
+		self.0 ^= self.0 >> 12;
+		self.0 ^= self.0 << 25;
+		self.0 ^= self.0 >> 27;
+		self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+	}
+
+	// Uniform in [0, 1)
+	fn next_ratio(&mut self) -> f64 {
+		(self.next() >> 11) as f64 / (1u64 << 53) as f64
+	}
+}
+
+fn tagged_address(kind: u64, index: u64) -> H160 {
+	let mut bytes = [0; 20];
+	bytes[..8].copy_from_slice(&(SEED_TAG << 48 | kind).to_be_bytes());
+	bytes[12..].copy_from_slice(&index.to_be_bytes());
+	H160(bytes)
+}
+
+fn tagged_hash(kind: u64, index: u64) -> H256 {
+	let mut bytes = [0; 32];
+	bytes[..8].copy_from_slice(&(SEED_TAG << 48 | kind).to_be_bytes());
+	bytes[24..].copy_from_slice(&index.to_be_bytes());
+	H256(bytes)
+}
+
+fn main() {
+	let args = Args::parse();
+	if args.block_size == 0 {
+		panic!("--block-size must be positive");
+	}
+
+	let pool = kiln_postgres::connexion_pool();
+	let conn = get_connection(&pool).expect("failed to connect to the database");
+	let mut rng = Rng(args.seed.max(1));
+
+	let total = args.addresses * args.transactions;
+	let blocks = (total + args.block_size - 1) / args.block_size;
+	for b in 0..blocks {
+		let number = args.first_block + b;
+		NewExecBlock::new(
+			tagged_hash(1, number),
+			number,
+			tagged_hash(1, number.saturating_sub(1)),
+			H256::zero(),
+			H256::zero(),
+			H256::zero(),
+			args.first_timestamp + b * BLOCK_TIME,
+			0,
+		)
+		.insert(&conn)
+		.expect("failed to insert a block");
+	}
+	println!("{blocks} blocks inserted");
+
+	// Transactions of an issuer are interleaved with the others, as on a real chain
+	let mut chunk = Vec::with_capacity(INSERT_CHUNK_SIZE);
+	for i in 0..total {
+		let issuer = i % args.addresses;
+		let nonce = i / args.addresses;
+		let block = i / args.block_size;
+
+		let draw = rng.next_ratio();
+		let (to, input) = if draw < args.deploy_ratio {
+			(None, vec![0x60, 0x80, 0x60, 0x40])
+		} else if draw < args.deploy_ratio + args.call_ratio && args.contracts > 0 {
+			(
+				Some(tagged_address(3, rng.next() % args.contracts)),
+				vec![0xa9, 0x05, 0x9c, 0xbb],
+			)
+		} else {
+			(
+				Some(tagged_address(2, rng.next() % args.addresses.max(1))),
+				vec![],
+			)
+		};
+
+		chunk.push(NewTransaction::new(
+			tagged_hash(2, i),
+			tagged_hash(1, args.first_block + block),
+			i % args.block_size,
+			Some(tagged_address(2, issuer)),
+			to,
+			input,
+			U256::from(rng.next() % args.max_value.max(1)),
+			nonce,
+			args.first_block + block,
+			// EIP-1559, as most transactions since London
+			Some(2),
+		));
+
+		if chunk.len() == INSERT_CHUNK_SIZE || i + 1 == total {
+			NewTransactions::new(std::mem::take(&mut chunk))
+				.batch_insert_new_counted(&conn)
+				.expect("failed to insert transactions");
+			println!("{} / {total} transactions inserted", i + 1);
+		}
+	}
+}
@@ -0,0 +1,98 @@
+use primitive_types::H160;
+use serde::Serializer;
+use tiny_keccak::{Hasher, Keccak};
+
+/// Return the EIP-55 mixed-case checksum encoding of `address`, '0x' prefixed
+///
+/// https://eips.ethereum.org/EIPS/eip-55
+pub fn checksum_address(address: &H160) -> String {
+	let lowercase = hex::encode(address.as_bytes());
+
+	let mut hash = [0u8; 32];
+	let mut keccak = Keccak::v256();
+	keccak.update(lowercase.as_bytes());
+	keccak.finalize(&mut hash);
+
+	let mut checksummed = String::with_capacity(42);
+	checksummed.push_str("0x");
+	for (i, c) in lowercase.chars().enumerate() {
+		// The i-th nibble of the hash decides the casing of the i-th character
+		let nibble = if i % 2 == 0 {
+			hash[i / 2] >> 4
+		} else {
+			hash[i / 2] & 0x0f
+		};
+		if nibble >= 8 {
+			checksummed.push(c.to_ascii_uppercase());
+		} else {
+			checksummed.push(c);
+		}
+	}
+
+	checksummed
+}
+
+/// Serialize `address` as its EIP-55 checksum encoding
+///
+/// Meant for `#[serde(serialize_with = "serialize_checksummed")]` on every address returned by the
+/// api, so they match wallet displays. Deserializing accepts any casing.
+pub fn serialize_checksummed<S: Serializer>(
+	address: &H160,
+	serializer: S,
+) -> Result<S::Ok, S::Error> {
+	serializer.serialize_str(&checksum_address(address))
+}
+
+/// Same as `serialize_checksummed`, for an optional address
+pub fn serialize_checksummed_option<S: Serializer>(
+	address: &Option<H160>,
+	serializer: S,
+) -> Result<S::Ok, S::Error> {
+	match address {
+		Some(address) => serializer.serialize_some(&checksum_address(address)),
+		None => serializer.serialize_none(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::str::FromStr;
+
+	use super::*;
+
+	// Test vectors of EIP-55
+	const CHECKSUMMED: &[&str] = &[
+		"0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+		"0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+		"0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+		"0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+	];
+
+	#[test]
+	fn addresses_are_checksummed() {
+		for expected in CHECKSUMMED {
+			let address = H160::from_str(&expected[2..].to_lowercase()).unwrap();
+
+			assert_eq!(checksum_address(&address), *expected);
+		}
+	}
+
+	#[test]
+	fn any_casing_gives_the_same_checksum() {
+		for expected in CHECKSUMMED {
+			let mixed = H160::from_str(&expected[2..]).unwrap();
+			let upper = H160::from_str(&expected[2..].to_uppercase()).unwrap();
+
+			assert_eq!(checksum_address(&mixed), *expected);
+			assert_eq!(checksum_address(&upper), *expected);
+		}
+	}
+
+	#[test]
+	fn zero_address_has_no_letter_to_case() {
+		assert_eq!(
+			checksum_address(&H160::zero()),
+			"0x0000000000000000000000000000000000000000"
+		);
+	}
+}
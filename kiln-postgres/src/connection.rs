@@ -0,0 +1,156 @@
+use std::cell::Cell;
+
+use diesel::{
+	connection::{AnsiTransactionManager, Connection, SimpleConnection},
+	deserialize::{Queryable, QueryableByName},
+	pg::Pg,
+	query_builder::{AsQuery, QueryFragment, QueryId},
+	result::{ConnectionResult, QueryResult},
+	sql_types::HasSqlType,
+	PgConnection,
+};
+
+thread_local! {
+	// Number of statements run by the connections of this thread
+	static STATEMENTS: Cell<u64> = Cell::new(0);
+}
+
+/// Connection to Postgres counting the statements it runs
+///
+/// Every query of this crate takes one. The statements are counted per thread, see
+/// `count_statements`, a connection being used by a single thread at a time.
+pub struct DbConnection(PgConnection);
+
+impl DbConnection {
+	fn counted(&self) -> &PgConnection {
+		STATEMENTS.with(|count| count.set(count.get() + 1));
+		&self.0
+	}
+}
+
+/// Run `f`, returning along its result the number of statements run by this thread meanwhile
+///
+/// Transactions count their `BEGIN` and `COMMIT`. Checking out a pooled connection runs a
+/// statement too, it is counted only when done by `f`.
+pub fn count_statements<R>(f: impl FnOnce() -> R) -> (R, u64) {
+	let before = STATEMENTS.with(Cell::get);
+	let result = f();
+
+	(result, STATEMENTS.with(Cell::get) - before)
+}
+
+impl SimpleConnection for DbConnection {
+	fn batch_execute(&self, query: &str) -> QueryResult<()> {
+		self.counted().batch_execute(query)
+	}
+}
+
+impl Connection for DbConnection {
+	type Backend = Pg;
+	type TransactionManager = AnsiTransactionManager;
+
+	fn establish(database_url: &str) -> ConnectionResult<Self> {
+		PgConnection::establish(database_url).map(DbConnection)
+	}
+
+	fn execute(&self, query: &str) -> QueryResult<usize> {
+		self.counted().execute(query)
+	}
+
+	fn query_by_index<T, U>(&self, source: T) -> QueryResult<Vec<U>>
+	where
+		T: AsQuery,
+		T::Query: QueryFragment<Self::Backend> + QueryId,
+		Self::Backend: HasSqlType<T::SqlType>,
+		U: Queryable<T::SqlType, Self::Backend>,
+	{
+		self.counted().query_by_index(source)
+	}
+
+	fn query_by_name<T, U>(&self, source: &T) -> QueryResult<Vec<U>>
+	where
+		T: QueryFragment<Self::Backend> + QueryId,
+		U: QueryableByName<Self::Backend>,
+	{
+		self.counted().query_by_name(source)
+	}
+
+	fn execute_returning_count<T>(&self, source: &T) -> QueryResult<usize>
+	where
+		T: QueryFragment<Self::Backend> + QueryId,
+	{
+		self.counted().execute_returning_count(source)
+	}
+
+	fn transaction_manager(&self) -> &Self::TransactionManager {
+		self.0.transaction_manager()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{test_connection, Stat, StatKey};
+
+	#[test]
+	fn every_statement_is_counted() {
+		let conn = match test_connection() {
+			Some(conn) => conn,
+			None => return,
+		};
+
+		let ((), count) = count_statements(|| {
+			Stat::get(&conn, StatKey::IssuersCount).unwrap();
+			Stat::get(&conn, StatKey::ExecutionDbHeight).unwrap();
+		});
+		assert_eq!(count, 2);
+
+		let (_, count) = count_statements(|| conn.execute("SELECT 1").unwrap());
+		assert_eq!(count, 1);
+	}
+
+	#[test]
+	fn transactions_count_their_begin_and_commit() {
+		let conn = match test_connection() {
+			Some(conn) => conn,
+			None => return,
+		};
+
+		let (result, count) =
+			count_statements(|| conn.transaction(|| Stat::get(&conn, StatKey::IssuersCount)));
+		result.unwrap();
+		assert_eq!(count, 3);
+	}
+
+	#[test]
+	fn nested_counts_include_the_inner_ones() {
+		let conn = match test_connection() {
+			Some(conn) => conn,
+			None => return,
+		};
+
+		let ((_, inner), outer) = count_statements(|| {
+			Stat::get(&conn, StatKey::IssuersCount).unwrap();
+			count_statements(|| Stat::get(&conn, StatKey::IssuersCount).unwrap())
+		});
+		assert_eq!(inner, 1);
+		assert_eq!(outer, 2);
+	}
+
+	#[test]
+	fn statements_of_other_threads_are_not_counted() {
+		let conn = match test_connection() {
+			Some(conn) => conn,
+			None => return,
+		};
+
+		let ((), count) = count_statements(|| {
+			std::thread::spawn(move || {
+				Stat::get(&conn, StatKey::IssuersCount).unwrap();
+			})
+			.join()
+			.unwrap();
+		});
+		assert_eq!(count, 0);
+	}
+}
@@ -1,29 +1,117 @@
 #[macro_use]
 extern crate diesel;
 
+mod checksum;
+mod connection;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 mod models;
+mod prune;
+mod redact;
 mod schema;
+mod schema_version;
+mod slot_clock;
+#[cfg(any(test, feature = "test-util"))]
+mod test_util;
 
-use std::env;
+use std::{env, time::Duration};
 
 use diesel::{
-	r2d2::{self, ConnectionManager, Pool},
-	PgConnection,
+	connection::SimpleConnection,
+	r2d2::{self, ConnectionManager, CustomizeConnection, Pool, PooledConnection},
 };
+use log::warn;
 
+pub use checksum::*;
+pub use connection::*;
+pub use diesel::r2d2::PoolError;
 pub use models::*;
+pub use prune::*;
+pub use redact::*;
+pub use schema_version::*;
+pub use slot_clock::*;
+#[cfg(any(test, feature = "test-util"))]
+pub use test_util::*;
 
-pub type PgConnectionPool = Pool<ConnectionManager<PgConnection>>;
+pub type PgConnectionPool = Pool<ConnectionManager<DbConnection>>;
+
+pub type PgPooledConnection = PooledConnection<ConnectionManager<DbConnection>>;
+
+/// Time waited for a pooled connection before giving up, instead of r2d2's default 30 seconds
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// Return a pool of connections to a Postgres instance
 ///
 /// # Environment requirements
 /// DATABASE_URL="postgres:<username>:<password>:<host_url>:<port>/<db_name>"
+///
+/// # Optional environment
+/// DATABASE_SCHEMA="<schema>": schema holding the tables, to isolate several networks sharing a
+/// database. Defaults to Postgres' `search_path`, usually `public`.
+/// Made of lowercase letters, digits and underscores.
+/// Migrations are not schema aware: run them with the schema as search path, e.g. with
+/// `DATABASE_URL=postgres://...?options=-c%20search_path%3D<schema> diesel migration run`.
+/// The same URL can be given to the clients not using this pool.
 pub fn connexion_pool() -> PgConnectionPool {
+	build_pool(r2d2::Pool::builder().connection_timeout(CONNECTION_TIMEOUT))
+}
+
+/// Same as `connexion_pool`, holding at most `max_size` connections instead of r2d2's default 10
+pub fn connexion_pool_with_size(max_size: u32) -> PgConnectionPool {
+	build_pool(r2d2::Pool::builder().connection_timeout(CONNECTION_TIMEOUT).max_size(max_size))
+}
+
+fn build_pool(builder: r2d2::Builder<ConnectionManager<DbConnection>>) -> PgConnectionPool {
 	let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
 
-	let manager = r2d2::ConnectionManager::<PgConnection>::new(&database_url);
+	let manager = r2d2::ConnectionManager::<DbConnection>::new(&database_url);
+
+	let builder = match env::var("DATABASE_SCHEMA") {
+		Ok(schema) => {
+			if schema.is_empty()
+				|| !schema.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+			{
+				panic!("Invalid DATABASE_SCHEMA {schema}");
+			}
+			builder.connection_customizer(Box::new(SearchPath(schema)))
+		},
+		Err(_) => builder,
+	};
+
+	builder.build(manager).unwrap_or_else(|_| {
+		panic!(
+			"Failed to create a pool for database at {}",
+			redact_url(&database_url)
+		)
+	})
+}
+
+// Set the schema searched for tables on every new connection
+#[derive(Debug)]
+struct SearchPath(String);
+
+impl CustomizeConnection<DbConnection, r2d2::Error> for SearchPath {
+	fn on_acquire(&self, conn: &mut DbConnection) -> Result<(), r2d2::Error> {
+		conn.batch_execute(&format!("SET search_path TO {}", self.0))
+			.map_err(r2d2::Error::QueryError)
+	}
+}
+
+/// Get a connection from `pool`
+///
+/// Pooled connections are checked before being handed out, broken ones are replaced by new ones.
+/// If none is available, e.g. while Postgres restarts, the pool keeps trying to establish new
+/// ones in the background, and this fails after waiting `CONNECTION_TIMEOUT` for one. This blocks
+/// the calling thread meanwhile.
+///
+/// With the `metrics` feature, the time spent waiting for the connection is recorded in the
+/// `db_connection_wait_seconds` histogram.
+pub fn get_connection(pool: &PgConnectionPool) -> Result<PgPooledConnection, PoolError> {
+	#[cfg(feature = "metrics")]
+	let _timer = metrics::DB_CONNECTION_WAIT_SECONDS.start_timer();
 
-	r2d2::Pool::new(manager)
-		.unwrap_or_else(|_| panic!("Failed to create a pool for database at {}", database_url))
+	pool.get().map_err(|err| {
+		warn!("failed to get a database connection within {CONNECTION_TIMEOUT:?}: {err}");
+		err
+	})
 }
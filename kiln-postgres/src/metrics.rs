@@ -0,0 +1,11 @@
+use lazy_static::lazy_static;
+use prometheus::{register_histogram, Histogram};
+
+lazy_static! {
+	/// Time spent waiting for a database connection
+	pub static ref DB_CONNECTION_WAIT_SECONDS: Histogram = register_histogram!(
+		"db_connection_wait_seconds",
+		"Time spent waiting for a database connection to be available"
+	)
+	.unwrap();
+}
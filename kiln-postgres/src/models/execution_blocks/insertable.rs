@@ -1,8 +1,8 @@
 use crate::diesel::RunQueryDsl;
-use diesel::{Insertable, PgConnection, QueryResult};
+use diesel::{pg::upsert::excluded, ExpressionMethods, Insertable, QueryResult};
 use primitive_types::H256;
 
-use crate::{models::Hash256, schema::execution_blocks};
+use crate::{models::Hash256, schema::execution_blocks, DbConnection};
 
 #[derive(Insertable)]
 #[table_name = "execution_blocks"]
@@ -13,9 +13,12 @@ pub struct NewExecBlock {
 	state_root: Hash256,
 	transactions_root: Hash256,
 	receipts_root: Hash256,
+	timestamp: i64,
+	gas_used: i64,
 }
 
 impl NewExecBlock {
+	#[allow(clippy::too_many_arguments)]
 	pub fn new(
 		hash: H256,
 		number: u64,
@@ -23,6 +26,8 @@ impl NewExecBlock {
 		state_root: H256,
 		transactions_root: H256,
 		receipts_root: H256,
+		timestamp: u64,
+		gas_used: u64,
 	) -> NewExecBlock {
 		NewExecBlock {
 			hash: hash.into(),
@@ -31,6 +36,8 @@ impl NewExecBlock {
 			state_root: state_root.into(),
 			transactions_root: transactions_root.into(),
 			receipts_root: receipts_root.into(),
+			timestamp: timestamp as i64,
+			gas_used: gas_used as i64,
 		}
 	}
 
@@ -39,7 +46,7 @@ impl NewExecBlock {
 	/// On conflict do nothing
 	///
 	/// Return the number of affected rows
-	pub fn insert_do_nothing(&self, conn: &PgConnection) -> QueryResult<usize> {
+	pub fn insert_do_nothing(&self, conn: &DbConnection) -> QueryResult<usize> {
 		let affected_rows = diesel::insert_into(execution_blocks::table)
 			.values(self)
 			.on_conflict_do_nothing()
@@ -51,7 +58,28 @@ impl NewExecBlock {
 	/// Insert a new slot on db
 	///
 	/// Fail in case of conflict
-	pub fn insert(&self, conn: &PgConnection) -> QueryResult<usize> {
+	pub fn insert(&self, conn: &DbConnection) -> QueryResult<usize> {
 		diesel::insert_into(execution_blocks::table).values(self).execute(conn)
 	}
+
+	/// Upsert a block on db
+	///
+	/// On conflict overwrite the stored block
+	pub fn upsert(&self, conn: &DbConnection) -> QueryResult<usize> {
+		diesel::insert_into(execution_blocks::table)
+			.values(self)
+			.on_conflict(execution_blocks::hash)
+			.do_update()
+			.set((
+				execution_blocks::number.eq(excluded(execution_blocks::number)),
+				execution_blocks::parent_hash.eq(excluded(execution_blocks::parent_hash)),
+				execution_blocks::state_root.eq(excluded(execution_blocks::state_root)),
+				execution_blocks::transactions_root
+					.eq(excluded(execution_blocks::transactions_root)),
+				execution_blocks::receipts_root.eq(excluded(execution_blocks::receipts_root)),
+				execution_blocks::timestamp.eq(excluded(execution_blocks::timestamp)),
+				execution_blocks::gas_used.eq(excluded(execution_blocks::gas_used)),
+			))
+			.execute(conn)
+	}
 }
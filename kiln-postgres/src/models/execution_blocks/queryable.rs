@@ -1,15 +1,14 @@
-use diesel::{
-	ExpressionMethods, Identifiable, PgConnection, QueryDsl, QueryResult, Queryable, RunQueryDsl,
-};
+use diesel::{ExpressionMethods, Identifiable, QueryDsl, QueryResult, Queryable, RunQueryDsl};
 use primitive_types::H256;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-	models::Hash256,
+	models::{Hash256, RawPayload, ReprocessLayer},
 	schema::{
 		execution_blocks,
 		execution_blocks::{dsl::execution_blocks as dsl_blocks, number},
 	},
+	DbConnection,
 };
 
 #[derive(Queryable, Identifiable)]
@@ -22,6 +21,8 @@ struct DbExecBlock {
 	state_root: Hash256,
 	transactions_root: Hash256,
 	receipts_root: Hash256,
+	timestamp: Option<i64>,
+	gas_used: Option<i64>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -32,6 +33,9 @@ pub struct ExecBlock {
 	state_root: H256,
 	transactions_root: H256,
 	receipts_root: H256,
+	timestamp: Option<u64>,
+	/// Gas used by all the transactions of the block, unknown for blocks stored before it was
+	gas_used: Option<u64>,
 }
 
 impl From<DbExecBlock> for ExecBlock {
@@ -43,26 +47,59 @@ impl From<DbExecBlock> for ExecBlock {
 			state_root: db_block.state_root.into(),
 			transactions_root: db_block.transactions_root.into(),
 			receipts_root: db_block.receipts_root.into(),
+			timestamp: db_block.timestamp.map(|t| t as u64),
+			gas_used: db_block.gas_used.map(|g| g as u64),
 		}
 	}
 }
 
 impl ExecBlock {
 	/// Return the highest block from db
-	pub fn get_highest(conn: &PgConnection) -> QueryResult<ExecBlock> {
+	pub fn get_highest(conn: &DbConnection) -> QueryResult<ExecBlock> {
 		let block = dsl_blocks.order(execution_blocks::number.desc()).first::<DbExecBlock>(conn)?;
 
 		Ok(block.into())
 	}
 
+	/// Return the number and timestamp of at most `limit` of the highest blocks, highest first
+	///
+	/// Blocks stored before timestamps were recorded are left out.
+	pub fn latest_timestamps(conn: &DbConnection, limit: u32) -> QueryResult<Vec<(u64, u64)>> {
+		let rows: Vec<(i64, Option<i64>)> = dsl_blocks
+			.select((number, execution_blocks::timestamp))
+			.filter(execution_blocks::timestamp.is_not_null())
+			.order(number.desc())
+			.limit(limit as i64)
+			.load(conn)?;
+
+		Ok(rows.into_iter().filter_map(|(n, t)| t.map(|t| (n as u64, t as u64))).collect())
+	}
+
 	/// Return an unique block from db
-	pub fn get(conn: &PgConnection, height: u64) -> QueryResult<ExecBlock> {
+	pub fn get(conn: &DbConnection, height: u64) -> QueryResult<ExecBlock> {
 		let block = dsl_blocks.filter(number.eq(height as i64)).first::<DbExecBlock>(conn)?;
 
 		Ok(block.into())
 	}
 
+	/// Return the block response stored for the block `number`, if any
+	///
+	/// Only stored when the indexer is run with raw payloads enabled.
+	pub fn raw_payload(conn: &DbConnection, number: u64) -> QueryResult<Option<String>> {
+		RawPayload::get(conn, ReprocessLayer::Execution, number)
+	}
+
 	pub fn number(&self) -> u64 {
 		self.number
 	}
+
+	/// Return the gas used by all the transactions of the block, if known
+	pub fn gas_used(&self) -> Option<u64> {
+		self.gas_used
+	}
+
+	/// Return the block timestamp, in seconds since the UNIX epoch
+	pub fn timestamp(&self) -> Option<u64> {
+		self.timestamp
+	}
 }
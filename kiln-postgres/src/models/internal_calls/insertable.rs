@@ -0,0 +1,78 @@
+use diesel::{pg::upsert::excluded, ExpressionMethods, Insertable, QueryResult, RunQueryDsl};
+use primitive_types::{H160, H256};
+
+use crate::{
+	models::{Hash160, Hash256},
+	schema::internal_calls,
+	DbConnection,
+};
+
+/// Representation of a row to be inserted
+#[derive(Insertable)]
+#[table_name = "internal_calls"]
+pub struct NewInternalCall {
+	transaction_hash: Hash256,
+	trace_address: String,
+	origin: Hash160,
+	from: Hash160,
+	to: Hash160,
+	block_number: i64,
+}
+
+impl NewInternalCall {
+	/// Return a new insertable internal call
+	///
+	/// `trace_address` locates the call in the call tree of transaction `transaction_hash`, which
+	/// was sent by `origin`. `from` is the contract making the call.
+	pub fn new(
+		transaction_hash: H256,
+		trace_address: &[usize],
+		origin: H160,
+		from: H160,
+		to: H160,
+		block_number: u64,
+	) -> NewInternalCall {
+		let trace_address: Vec<String> = trace_address.iter().map(|i| i.to_string()).collect();
+
+		NewInternalCall {
+			transaction_hash: transaction_hash.into(),
+			trace_address: trace_address.join(","),
+			origin: origin.into(),
+			from: from.into(),
+			to: to.into(),
+			block_number: block_number as i64,
+		}
+	}
+}
+
+pub struct NewInternalCalls(Vec<NewInternalCall>);
+
+impl NewInternalCalls {
+	pub fn batch_insert(&self, conn: &DbConnection) -> QueryResult<usize> {
+		diesel::insert_into(internal_calls::table).values(&self.0).execute(conn)
+	}
+
+	/// Upsert an array of internal calls in db
+	///
+	/// On conflict overwrite every field
+	pub fn batch_upsert(&self, conn: &DbConnection) -> QueryResult<usize> {
+		diesel::insert_into(internal_calls::table)
+			.values(&self.0)
+			.on_conflict((
+				internal_calls::transaction_hash,
+				internal_calls::trace_address,
+			))
+			.do_update()
+			.set((
+				internal_calls::origin.eq(excluded(internal_calls::origin)),
+				internal_calls::from.eq(excluded(internal_calls::from)),
+				internal_calls::to.eq(excluded(internal_calls::to)),
+				internal_calls::block_number.eq(excluded(internal_calls::block_number)),
+			))
+			.execute(conn)
+	}
+
+	pub fn new(calls: Vec<NewInternalCall>) -> Self {
+		Self(calls)
+	}
+}
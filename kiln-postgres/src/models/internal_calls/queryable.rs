@@ -0,0 +1,33 @@
+use diesel::{ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl};
+use primitive_types::H160;
+
+use crate::{
+	models::Hash160,
+	schema::{internal_calls, internal_calls::dsl::internal_calls as dsl_internal_calls},
+	DbConnection,
+};
+
+pub struct InternalCall;
+
+impl InternalCall {
+	/// Return the number of internal calls triggered by the transactions of `address`
+	///
+	/// Calls in blocks above `finalized_below_height` are ignored, if set.
+	/// Only indexed if the indexer is run with internal calls tracing enabled.
+	pub fn count_from_origin(
+		conn: &DbConnection,
+		address: H160,
+		finalized_below_height: Option<u64>,
+	) -> QueryResult<u64> {
+		let address: Hash160 = address.into();
+
+		let mut query = dsl_internal_calls.filter(internal_calls::origin.eq(address)).into_boxed();
+		if let Some(height) = finalized_below_height {
+			query = query.filter(internal_calls::block_number.le(height as i64));
+		}
+
+		let count: i64 = query.count().get_result(conn)?;
+
+		Ok(count as u64)
+	}
+}
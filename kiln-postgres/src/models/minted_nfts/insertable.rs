@@ -0,0 +1,52 @@
+use diesel::{
+	pg::upsert::excluded, Connection, ExpressionMethods, Insertable, QueryResult, RunQueryDsl,
+};
+use primitive_types::{H160, U256};
+
+use super::MintedNft;
+
+use crate::{models::Hash160, schema::minted_nfts, DbConnection};
+
+/// Representation of a row to be inserted
+#[derive(Insertable)]
+#[table_name = "minted_nfts"]
+pub struct NewMintedNft {
+	address: Hash160,
+	// Little endian bytes of the packed NFTs integer
+	nft: Vec<u8>,
+}
+
+impl NewMintedNft {
+	/// Return a new insertable set of NFTs minted by `address`, packed like the eligible ones
+	pub fn new(address: H160, nft: U256) -> NewMintedNft {
+		let mut bytes = vec![0; 32];
+		nft.to_little_endian(&mut bytes);
+
+		NewMintedNft {
+			address: address.into(),
+			nft: bytes,
+		}
+	}
+
+	/// Add the NFTs minted by `address` to the ones already stored, and return them all
+	pub fn add(conn: &DbConnection, address: H160, nft: U256) -> QueryResult<U256> {
+		conn.transaction(|| {
+			let minted = MintedNft::get(conn, address)? | nft;
+			NewMintedNft::new(address, minted).upsert(conn)?;
+
+			Ok(minted)
+		})
+	}
+
+	/// Upsert minted NFTs on db
+	///
+	/// On conflict overwrite the stored value
+	pub fn upsert(&self, conn: &DbConnection) -> QueryResult<usize> {
+		diesel::insert_into(minted_nfts::table)
+			.values(self)
+			.on_conflict(minted_nfts::address)
+			.do_update()
+			.set(minted_nfts::nft.eq(excluded(minted_nfts::nft)))
+			.execute(conn)
+	}
+}
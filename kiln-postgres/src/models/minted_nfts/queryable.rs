@@ -0,0 +1,22 @@
+use diesel::{OptionalExtension, QueryDsl, QueryResult, RunQueryDsl};
+use primitive_types::{H160, U256};
+
+use crate::{
+	models::Hash160,
+	schema::{minted_nfts, minted_nfts::dsl::minted_nfts as dsl_minted_nfts},
+	DbConnection,
+};
+
+pub struct MintedNft;
+
+impl MintedNft {
+	/// Return the packed NFTs `address` already minted, zero if none
+	pub fn get(conn: &DbConnection, address: H160) -> QueryResult<U256> {
+		let address: Hash160 = address.into();
+
+		let nft: Option<Vec<u8>> =
+			dsl_minted_nfts.find(address).select(minted_nfts::nft).first(conn).optional()?;
+
+		Ok(nft.map_or_else(U256::zero, |n| U256::from_little_endian(&n)))
+	}
+}
@@ -1,11 +1,27 @@
 mod execution_blocks;
+mod internal_calls;
+mod minted_nfts;
+mod nft_merkle_trees;
+mod packed_nfts;
+mod raw_payloads;
+mod reprocess_jobs;
+mod slashing_events;
 mod slots;
+mod stats;
 mod transactions;
 mod types;
 mod validators;
 
 pub use execution_blocks::*;
+pub use internal_calls::*;
+pub use minted_nfts::*;
+pub use nft_merkle_trees::*;
+pub use packed_nfts::*;
+pub use raw_payloads::*;
+pub use reprocess_jobs::*;
+pub use slashing_events::*;
 pub use slots::*;
+pub use stats::*;
 pub use transactions::*;
 pub(self) use types::*;
 pub use validators::*;
@@ -0,0 +1,89 @@
+use diesel::{Connection, ExpressionMethods, Insertable, QueryDsl, QueryResult, RunQueryDsl};
+use primitive_types::{H160, H256, U256};
+
+use crate::{
+	models::{Hash160, Hash256},
+	schema::{
+		nft_merkle_leaves, nft_merkle_trees, nft_merkle_trees::dsl::nft_merkle_trees as dsl_trees,
+	},
+	DbConnection,
+};
+
+/// Number of leaves inserted at once, below Postgres' limit of bind parameters per query
+const LEAVES_CHUNK_SIZE: usize = 10_000;
+
+#[derive(Insertable)]
+#[table_name = "nft_merkle_trees"]
+struct DbNewMerkleTree {
+	root: Hash256,
+	leaves_count: i64,
+}
+
+/// Representation of a row to be inserted
+#[derive(Insertable)]
+#[table_name = "nft_merkle_leaves"]
+pub struct NewMerkleLeaf {
+	// Set on insert, along with the tree
+	tree_id: i32,
+	address: Hash160,
+	// Little endian bytes of the packed NFTs integer
+	nft: Vec<u8>,
+	leaf_index: i64,
+	// Concatenated 32 bytes hashes, from the leaf level up
+	proof: Vec<u8>,
+}
+
+impl NewMerkleLeaf {
+	/// Return a new insertable leaf of `address` eligible to `nft`, at `leaf_index` in the tree
+	pub fn new(address: H160, nft: U256, leaf_index: u64, proof: &[H256]) -> NewMerkleLeaf {
+		let mut bytes = vec![0; 32];
+		nft.to_little_endian(&mut bytes);
+
+		NewMerkleLeaf {
+			tree_id: 0,
+			address: address.into(),
+			nft: bytes,
+			leaf_index: leaf_index as i64,
+			proof: proof.iter().flat_map(|h| h.as_bytes().to_vec()).collect(),
+		}
+	}
+}
+
+/// Merkle tree of the packed NFTs, along with the proof of each of its leaves
+pub struct NewMerkleTree {
+	root: H256,
+	leaves: Vec<NewMerkleLeaf>,
+}
+
+impl NewMerkleTree {
+	pub fn new(root: H256, leaves: Vec<NewMerkleLeaf>) -> Self {
+		Self { root, leaves }
+	}
+
+	/// Insert the tree and its leaves in db, replacing the stored ones at once
+	///
+	/// Return the id of the tree
+	pub fn replace(mut self, conn: &DbConnection) -> QueryResult<i32> {
+		conn.transaction(|| {
+			let id = diesel::insert_into(nft_merkle_trees::table)
+				.values(&DbNewMerkleTree {
+					root: self.root.into(),
+					leaves_count: self.leaves.len() as i64,
+				})
+				.returning(nft_merkle_trees::id)
+				.get_result(conn)?;
+
+			for leaf in &mut self.leaves {
+				leaf.tree_id = id;
+			}
+			for chunk in self.leaves.chunks(LEAVES_CHUNK_SIZE) {
+				diesel::insert_into(nft_merkle_leaves::table).values(chunk).execute(conn)?;
+			}
+
+			// Leaves of the former trees are deleted along with them
+			diesel::delete(dsl_trees.filter(nft_merkle_trees::id.ne(id))).execute(conn)?;
+
+			Ok(id)
+		})
+	}
+}
@@ -0,0 +1,86 @@
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, Queryable, RunQueryDsl};
+use primitive_types::{H160, H256, U256};
+
+use crate::{
+	models::{Hash160, Hash256},
+	schema::{
+		nft_merkle_leaves, nft_merkle_leaves::dsl::nft_merkle_leaves as dsl_leaves,
+		nft_merkle_trees, nft_merkle_trees::dsl::nft_merkle_trees as dsl_trees,
+	},
+	DbConnection,
+};
+
+#[derive(Queryable)]
+struct DbMerkleTree {
+	root: Hash256,
+	leaves_count: i64,
+}
+
+/// Merkle tree of the packed NFTs
+#[derive(Clone, Debug)]
+pub struct MerkleTree {
+	pub root: H256,
+	pub leaves_count: u64,
+}
+
+impl MerkleTree {
+	/// Return the stored tree, if any
+	pub fn get(conn: &DbConnection) -> QueryResult<Option<MerkleTree>> {
+		let tree: Option<DbMerkleTree> = dsl_trees
+			.select((nft_merkle_trees::root, nft_merkle_trees::leaves_count))
+			.order(nft_merkle_trees::id.desc())
+			.first(conn)
+			.optional()?;
+
+		Ok(tree.map(|t| MerkleTree {
+			root: t.root.into(),
+			leaves_count: t.leaves_count as u64,
+		}))
+	}
+}
+
+#[derive(Queryable)]
+struct DbMerkleLeaf {
+	root: Hash256,
+	nft: Vec<u8>,
+	leaf_index: i64,
+	proof: Vec<u8>,
+}
+
+/// Leaf of an address in the Merkle tree of the packed NFTs
+#[derive(Clone, Debug)]
+pub struct MerkleLeaf {
+	/// Root of the tree the leaf belongs to
+	pub root: H256,
+	pub nft: U256,
+	pub leaf_index: u64,
+	/// Sibling hashes, from the leaf level up
+	pub proof: Vec<H256>,
+}
+
+impl MerkleLeaf {
+	/// Return the leaf of `address` in the stored tree, if it is part of it
+	pub fn get(conn: &DbConnection, address: H160) -> QueryResult<Option<MerkleLeaf>> {
+		let address: Hash160 = address.into();
+
+		let leaf: Option<DbMerkleLeaf> = dsl_leaves
+			.inner_join(nft_merkle_trees::table)
+			.filter(nft_merkle_leaves::address.eq(address))
+			.select((
+				nft_merkle_trees::root,
+				nft_merkle_leaves::nft,
+				nft_merkle_leaves::leaf_index,
+				nft_merkle_leaves::proof,
+			))
+			.order(nft_merkle_trees::id.desc())
+			.first(conn)
+			.optional()?;
+
+		Ok(leaf.map(|l| MerkleLeaf {
+			root: l.root.into(),
+			nft: U256::from_little_endian(&l.nft),
+			leaf_index: l.leaf_index as u64,
+			proof: l.proof.chunks(32).map(H256::from_slice).collect(),
+		}))
+	}
+}
@@ -0,0 +1,44 @@
+use diesel::{pg::upsert::excluded, ExpressionMethods, Insertable, QueryResult, RunQueryDsl};
+use primitive_types::{H160, U256};
+
+use crate::{models::Hash160, schema::packed_nfts, DbConnection};
+
+/// Representation of a row to be inserted
+#[derive(Insertable)]
+#[table_name = "packed_nfts"]
+pub struct NewPackedNft {
+	address: Hash160,
+	// Little endian bytes of the packed NFTs integer
+	nft: Vec<u8>,
+	computed_below_height: Option<i64>,
+}
+
+impl NewPackedNft {
+	/// Return a new insertable packed NFTs of `address`, computed from its transactions in blocks
+	/// up to `computed_below_height`
+	pub fn new(address: H160, nft: U256, computed_below_height: u64) -> NewPackedNft {
+		let mut bytes = vec![0; 32];
+		nft.to_little_endian(&mut bytes);
+
+		NewPackedNft {
+			address: address.into(),
+			nft: bytes,
+			computed_below_height: Some(computed_below_height as i64),
+		}
+	}
+
+	/// Upsert packed NFTs on db
+	///
+	/// On conflict overwrite the stored value
+	pub fn upsert(&self, conn: &DbConnection) -> QueryResult<usize> {
+		diesel::insert_into(packed_nfts::table)
+			.values(self)
+			.on_conflict(packed_nfts::address)
+			.do_update()
+			.set((
+				packed_nfts::nft.eq(excluded(packed_nfts::nft)),
+				packed_nfts::computed_below_height.eq(excluded(packed_nfts::computed_below_height)),
+			))
+			.execute(conn)
+	}
+}
@@ -0,0 +1,78 @@
+use diesel::{
+	dsl::sql, sql_types::Double, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult,
+	RunQueryDsl,
+};
+use primitive_types::{H160, U256};
+
+use crate::{
+	models::Hash160,
+	schema::{packed_nfts, packed_nfts::dsl::packed_nfts as dsl_packed_nfts},
+	DbConnection,
+};
+
+/// Packed NFTs precomputed for an address
+pub struct PackedNft {
+	pub nft: U256,
+	/// Highest block whose transactions were counted, None if stored before it was recorded
+	pub computed_below_height: Option<u64>,
+}
+
+impl PackedNft {
+	/// Return the packed NFTs precomputed for `address`, if any
+	pub fn get(conn: &DbConnection, address: H160) -> QueryResult<Option<PackedNft>> {
+		let address: Hash160 = address.into();
+
+		let row: Option<(Vec<u8>, Option<i64>)> = dsl_packed_nfts
+			.find(address)
+			.select((packed_nfts::nft, packed_nfts::computed_below_height))
+			.first(conn)
+			.optional()?;
+
+		Ok(row.map(|(nft, height)| PackedNft {
+			nft: U256::from_little_endian(&nft),
+			computed_below_height: height.map(|h| h as u64),
+		}))
+	}
+
+	/// Return `size` addresses drawn at random along with their precomputed packed NFTs, or every
+	/// address if `size` is None
+	pub fn sample(conn: &DbConnection, size: Option<u32>) -> QueryResult<Vec<(H160, U256)>> {
+		let mut query =
+			dsl_packed_nfts.select((packed_nfts::address, packed_nfts::nft)).into_boxed();
+		if let Some(size) = size {
+			query = query.order(sql::<Double>("RANDOM()")).limit(size as i64);
+		}
+
+		let rows: Vec<(Hash160, Vec<u8>)> = query.load(conn)?;
+
+		Ok(rows
+			.into_iter()
+			.map(|(address, nft)| (address.into(), U256::from_little_endian(&nft)))
+			.collect())
+	}
+
+	/// Return at most `limit` of the addresses whose precomputed packed NFTs are exactly `nft`, by
+	/// address, skipping the first `offset`
+	///
+	/// Served by the `(nft, address)` index.
+	pub fn addresses_with(
+		conn: &DbConnection,
+		nft: U256,
+		offset: u64,
+		limit: u32,
+	) -> QueryResult<Vec<H160>> {
+		// Stored as 32 little endian bytes, see `NewPackedNft::new`
+		let mut bytes = vec![0; 32];
+		nft.to_little_endian(&mut bytes);
+
+		let addresses: Vec<Hash160> = dsl_packed_nfts
+			.select(packed_nfts::address)
+			.filter(packed_nfts::nft.eq(bytes))
+			.order(packed_nfts::address.asc())
+			.offset(offset as i64)
+			.limit(limit as i64)
+			.load(conn)?;
+
+		Ok(addresses.into_iter().map(|a| a.into()).collect())
+	}
+}
@@ -0,0 +1,37 @@
+use diesel::{pg::upsert::excluded, ExpressionMethods, Insertable, QueryResult, RunQueryDsl};
+
+use crate::{models::ReprocessLayer, schema::raw_payloads, DbConnection};
+
+/// Representation of a row to be inserted
+#[derive(Insertable)]
+#[table_name = "raw_payloads"]
+pub struct NewRawPayload {
+	layer: String,
+	height: i64,
+	payload: String,
+}
+
+impl NewRawPayload {
+	/// Return a new insertable raw payload
+	///
+	/// `payload` is the JSON of the entry at `height`, as returned by the `layer` node.
+	pub fn new(layer: ReprocessLayer, height: u64, payload: String) -> NewRawPayload {
+		NewRawPayload {
+			layer: layer.to_string(),
+			height: height as i64,
+			payload,
+		}
+	}
+
+	/// Upsert a raw payload on db
+	///
+	/// On conflict overwrite the stored payload
+	pub fn upsert(&self, conn: &DbConnection) -> QueryResult<usize> {
+		diesel::insert_into(raw_payloads::table)
+			.values(self)
+			.on_conflict((raw_payloads::layer, raw_payloads::height))
+			.do_update()
+			.set(raw_payloads::payload.eq(excluded(raw_payloads::payload)))
+			.execute(conn)
+	}
+}
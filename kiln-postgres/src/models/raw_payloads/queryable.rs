@@ -0,0 +1,24 @@
+use diesel::{OptionalExtension, QueryDsl, QueryResult, RunQueryDsl};
+
+use crate::{
+	models::ReprocessLayer,
+	schema::{raw_payloads, raw_payloads::dsl::raw_payloads as dsl_raw_payloads},
+	DbConnection,
+};
+
+pub struct RawPayload;
+
+impl RawPayload {
+	/// Return the node response stored for the `layer` entry at `height`, if any
+	pub fn get(
+		conn: &DbConnection,
+		layer: ReprocessLayer,
+		height: u64,
+	) -> QueryResult<Option<String>> {
+		dsl_raw_payloads
+			.find((layer.to_string(), height as i64))
+			.select(raw_payloads::payload)
+			.first(conn)
+			.optional()
+	}
+}
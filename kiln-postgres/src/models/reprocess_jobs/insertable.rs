@@ -0,0 +1,96 @@
+use diesel::{
+	sql_query, sql_types::BigInt, Connection, ExpressionMethods, Insertable, QueryDsl, QueryResult,
+	RunQueryDsl,
+};
+
+use crate::DbConnection;
+
+use super::{ReprocessJob, ReprocessLayer, ReprocessStatus};
+
+use crate::schema::{reprocess_jobs, reprocess_jobs::dsl::reprocess_jobs as dsl_reprocess_jobs};
+
+/// Key of the advisory lock serializing the queueing of jobs
+const QUEUE_LOCK_KEY: i64 = 0x7265_7072_6f63;
+
+/// Outcome of the queueing of a job
+pub enum QueuedReprocessJob {
+	/// The job was inserted, with this id
+	Inserted(i32),
+	/// Another job is already pending or running, nothing was inserted
+	Active(ReprocessJob),
+}
+
+/// Representation of a row to be inserted
+///
+/// A job is created as pending
+#[derive(Insertable)]
+#[table_name = "reprocess_jobs"]
+pub struct NewReprocessJob {
+	layer: String,
+	from_height: i64,
+	to_height: i64,
+}
+
+impl NewReprocessJob {
+	/// Return a new insertable reprocess job
+	pub fn new(layer: ReprocessLayer, from_height: u64, to_height: u64) -> NewReprocessJob {
+		NewReprocessJob {
+			layer: layer.to_string(),
+			from_height: from_height as i64,
+			to_height: to_height as i64,
+		}
+	}
+
+	/// Insert a new job on db
+	///
+	/// Return the id of the job
+	pub fn insert(&self, conn: &DbConnection) -> QueryResult<i32> {
+		diesel::insert_into(reprocess_jobs::table)
+			.values(self)
+			.returning(reprocess_jobs::id)
+			.get_result(conn)
+	}
+
+	/// Insert the job unless another one is pending or running
+	///
+	/// Concurrent calls are serialized by a transaction scoped advisory lock, so that two of them
+	/// never both see an empty queue and insert.
+	pub fn queue(&self, conn: &DbConnection) -> QueryResult<QueuedReprocessJob> {
+		conn.transaction(|| {
+			sql_query("SELECT pg_advisory_xact_lock($1)")
+				.bind::<BigInt, _>(QUEUE_LOCK_KEY)
+				.execute(conn)?;
+
+			match ReprocessJob::active(conn)? {
+				Some(job) => Ok(QueuedReprocessJob::Active(job)),
+				None => self.insert(conn).map(QueuedReprocessJob::Inserted),
+			}
+		})
+	}
+
+	/// Set a job status
+	pub fn set_status(conn: &DbConnection, id: i32, status: ReprocessStatus) -> QueryResult<usize> {
+		diesel::update(dsl_reprocess_jobs.find(id))
+			.set(reprocess_jobs::status.eq(status.to_string()))
+			.execute(conn)
+	}
+
+	/// Set the highest height reprocessed by a job
+	pub fn set_processed_height(conn: &DbConnection, id: i32, height: u64) -> QueryResult<usize> {
+		diesel::update(dsl_reprocess_jobs.find(id))
+			.set(reprocess_jobs::processed_height.eq(Some(height as i64)))
+			.execute(conn)
+	}
+
+	/// Set back running jobs as pending
+	///
+	/// Jobs interrupted by a shutdown are left running, they must be resumed on restart.
+	pub fn requeue_running(conn: &DbConnection) -> QueryResult<usize> {
+		diesel::update(
+			dsl_reprocess_jobs
+				.filter(reprocess_jobs::status.eq(ReprocessStatus::Running.to_string())),
+		)
+		.set(reprocess_jobs::status.eq(ReprocessStatus::Pending.to_string()))
+		.execute(conn)
+	}
+}
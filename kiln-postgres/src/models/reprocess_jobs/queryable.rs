@@ -0,0 +1,176 @@
+use std::{fmt::Display, str::FromStr};
+
+use diesel::{
+	ExpressionMethods, Identifiable, OptionalExtension, QueryDsl, QueryResult, Queryable,
+	RunQueryDsl,
+};
+
+use crate::DbConnection;
+use serde::{Deserialize, Serialize};
+
+use crate::schema::{reprocess_jobs, reprocess_jobs::dsl::reprocess_jobs as dsl_reprocess_jobs};
+
+/// Chain layer a reprocess job applies to
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReprocessLayer {
+	/// Heights are slots
+	Consensus,
+	/// Heights are block numbers
+	Execution,
+}
+
+impl Display for ReprocessLayer {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ReprocessLayer::Consensus => write!(f, "consensus"),
+			ReprocessLayer::Execution => write!(f, "execution"),
+		}
+	}
+}
+
+impl FromStr for ReprocessLayer {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"consensus" => Ok(ReprocessLayer::Consensus),
+			"execution" => Ok(ReprocessLayer::Execution),
+			_ => Err(()),
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReprocessStatus {
+	Pending,
+	Running,
+	Done,
+	Failed,
+}
+
+impl Display for ReprocessStatus {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ReprocessStatus::Pending => write!(f, "pending"),
+			ReprocessStatus::Running => write!(f, "running"),
+			ReprocessStatus::Done => write!(f, "done"),
+			ReprocessStatus::Failed => write!(f, "failed"),
+		}
+	}
+}
+
+impl FromStr for ReprocessStatus {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"pending" => Ok(ReprocessStatus::Pending),
+			"running" => Ok(ReprocessStatus::Running),
+			"done" => Ok(ReprocessStatus::Done),
+			"failed" => Ok(ReprocessStatus::Failed),
+			_ => Err(()),
+		}
+	}
+}
+
+#[derive(Queryable, Identifiable)]
+#[table_name = "reprocess_jobs"]
+struct DbReprocessJob {
+	id: i32,
+	layer: String,
+	from_height: i64,
+	to_height: i64,
+	processed_height: Option<i64>,
+	status: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReprocessJob {
+	id: i32,
+	layer: ReprocessLayer,
+	from_height: u64,
+	to_height: u64,
+	processed_height: Option<u64>,
+	status: ReprocessStatus,
+}
+
+impl From<DbReprocessJob> for ReprocessJob {
+	fn from(db_job: DbReprocessJob) -> Self {
+		ReprocessJob {
+			id: db_job.id,
+			// Only values written by `NewReprocessJob` are stored
+			layer: db_job.layer.parse().expect("invalid reprocess job layer in db"),
+			from_height: db_job.from_height as u64,
+			to_height: db_job.to_height as u64,
+			processed_height: db_job.processed_height.map(|h| h as u64),
+			status: db_job.status.parse().expect("invalid reprocess job status in db"),
+		}
+	}
+}
+
+impl ReprocessJob {
+	pub fn id(&self) -> i32 {
+		self.id
+	}
+
+	pub fn layer(&self) -> ReprocessLayer {
+		self.layer
+	}
+
+	pub fn from_height(&self) -> u64 {
+		self.from_height
+	}
+
+	pub fn to_height(&self) -> u64 {
+		self.to_height
+	}
+
+	/// Return the highest height reprocessed so far
+	pub fn processed_height(&self) -> Option<u64> {
+		self.processed_height
+	}
+
+	pub fn status(&self) -> ReprocessStatus {
+		self.status
+	}
+
+	/// Return the job with `id`, if any
+	pub fn find(conn: &DbConnection, id: i32) -> QueryResult<Option<ReprocessJob>> {
+		let job = dsl_reprocess_jobs.find(id).first::<DbReprocessJob>(conn).optional()?;
+
+		Ok(job.map(|j| j.into()))
+	}
+
+	/// Return the oldest pending job, if any
+	pub fn next_pending(conn: &DbConnection) -> QueryResult<Option<ReprocessJob>> {
+		let job = dsl_reprocess_jobs
+			.filter(reprocess_jobs::status.eq(ReprocessStatus::Pending.to_string()))
+			.order(reprocess_jobs::id.asc())
+			.first::<DbReprocessJob>(conn)
+			.optional()?;
+
+		Ok(job.map(|j| j.into()))
+	}
+
+	/// Return the oldest pending or running job, if any
+	pub fn active(conn: &DbConnection) -> QueryResult<Option<ReprocessJob>> {
+		let job = dsl_reprocess_jobs
+			.filter(reprocess_jobs::status.eq_any(vec![
+				ReprocessStatus::Pending.to_string(),
+				ReprocessStatus::Running.to_string(),
+			]))
+			.order(reprocess_jobs::id.asc())
+			.first::<DbReprocessJob>(conn)
+			.optional()?;
+
+		Ok(job.map(|j| j.into()))
+	}
+
+	/// Return true if the job reprocesses exactly the heights between `from` and `to` included of
+	/// `layer`
+	pub fn covers(&self, layer: ReprocessLayer, from: u64, to: u64) -> bool {
+		self.layer == layer && self.from_height == from && self.to_height == to
+	}
+}
@@ -0,0 +1,43 @@
+use diesel::{Insertable, QueryResult, RunQueryDsl};
+
+use crate::{models::SlashingKind, schema::slashing_events, DbConnection};
+
+/// Representation of a row to be inserted
+#[derive(Insertable)]
+#[table_name = "slashing_events"]
+pub struct NewSlashingEvent {
+	slot: i64,
+	validator_index: i64,
+	kind: String,
+}
+
+impl NewSlashingEvent {
+	/// Return a new insertable slashing event
+	///
+	/// `slot` is the one of the block including the slashing of validator `validator_index`.
+	pub fn new(slot: u64, validator_index: u64, kind: SlashingKind) -> NewSlashingEvent {
+		NewSlashingEvent {
+			slot: slot as i64,
+			validator_index: validator_index as i64,
+			kind: kind.to_string(),
+		}
+	}
+}
+
+pub struct NewSlashingEvents(Vec<NewSlashingEvent>);
+
+impl NewSlashingEvents {
+	/// Insert an array of slashing events in db
+	///
+	/// Events already stored are left untouched, they never change
+	pub fn batch_insert_do_nothing(&self, conn: &DbConnection) -> QueryResult<usize> {
+		diesel::insert_into(slashing_events::table)
+			.values(&self.0)
+			.on_conflict_do_nothing()
+			.execute(conn)
+	}
+
+	pub fn new(events: Vec<NewSlashingEvent>) -> Self {
+		Self(events)
+	}
+}
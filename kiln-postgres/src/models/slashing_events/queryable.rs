@@ -0,0 +1,98 @@
+use std::{fmt::Display, str::FromStr};
+
+use diesel::{ExpressionMethods, QueryDsl, QueryResult, Queryable, RunQueryDsl};
+use primitive_types::H160;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	models::Hash160,
+	schema::{
+		slashing_events, slashing_events::dsl::slashing_events as dsl_events, transactions,
+		validators,
+	},
+	DbConnection,
+};
+
+/// Offence a validator was slashed for
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SlashingKind {
+	/// Proposed two different blocks for the same slot
+	Proposer,
+	/// Made two conflicting attestations
+	Attester,
+}
+
+impl Display for SlashingKind {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			SlashingKind::Proposer => write!(f, "proposer"),
+			SlashingKind::Attester => write!(f, "attester"),
+		}
+	}
+}
+
+impl FromStr for SlashingKind {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"proposer" => Ok(SlashingKind::Proposer),
+			"attester" => Ok(SlashingKind::Attester),
+			_ => Err(()),
+		}
+	}
+}
+
+#[derive(Queryable)]
+struct DbSlashingEvent {
+	slot: i64,
+	validator_index: i64,
+	kind: String,
+}
+
+/// Slashing of a validator, as included in a block
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SlashingEvent {
+	/// Slot of the block including the slashing
+	pub slot: u64,
+	pub validator_index: u64,
+	pub kind: SlashingKind,
+}
+
+impl From<DbSlashingEvent> for SlashingEvent {
+	fn from(db_event: DbSlashingEvent) -> Self {
+		SlashingEvent {
+			slot: db_event.slot as u64,
+			validator_index: db_event.validator_index as u64,
+			// Safe to unwrap because only valid kinds are inserted
+			kind: db_event.kind.parse().unwrap(),
+		}
+	}
+}
+
+impl SlashingEvent {
+	/// Return the slashings of the validators deposited by `address`, by ascending slot
+	pub fn list_from_depositor(
+		conn: &DbConnection,
+		address: H160,
+	) -> QueryResult<Vec<SlashingEvent>> {
+		let address: Hash160 = address.into();
+
+		let db_events: Vec<DbSlashingEvent> = dsl_events
+			.inner_join(validators::table.inner_join(transactions::table))
+			.filter(transactions::from.eq(address))
+			.select((
+				slashing_events::slot,
+				slashing_events::validator_index,
+				slashing_events::kind,
+			))
+			.order((
+				slashing_events::slot.asc(),
+				slashing_events::validator_index.asc(),
+			))
+			.load(conn)?;
+
+		Ok(db_events.into_iter().map(|e| e.into()).collect())
+	}
+}
@@ -1,7 +1,16 @@
-use diesel::{Insertable, PgConnection, QueryResult, RunQueryDsl};
+use diesel::{
+	pg::upsert::excluded, Connection, ExpressionMethods, Insertable, OptionalExtension, QueryDsl,
+	QueryResult, RunQueryDsl,
+};
 use primitive_types::H256;
 
-use crate::{models::Hash256, schema::slots};
+use super::{SlotSpec, SupersedeReason};
+
+use crate::{
+	models::Hash256,
+	schema::{slots, slots_history},
+	DbConnection,
+};
 
 /// Representation of a row to be inserted
 #[derive(Insertable)]
@@ -12,15 +21,55 @@ pub struct NewSlot {
 	height: i64,
 	block_hash: Option<Hash256>,
 	block_number: Option<i64>,
+	missed: bool,
+	proposer_index: Option<i64>,
+	/// None until it could be fetched from the node
+	validators_count: Option<i64>,
+	/// Name of the fork the slot's block was built for
+	spec: Option<String>,
+	/// Number of transactions of the slot's execution block, None for pre-merge blocks
+	tx_count: Option<i64>,
+	/// Seconds since the UNIX epoch of the slot's execution block, None for pre-merge blocks
+	timestamp: Option<i64>,
 }
 
 impl NewSlot {
 	/// Return a new insertable slot
-	pub fn new(height: u64, block_hash: Option<H256>, block_number: Option<u64>) -> NewSlot {
+	pub fn new(
+		height: u64,
+		block_hash: Option<H256>,
+		block_number: Option<u64>,
+		proposer_index: u64,
+		validators_count: Option<u64>,
+		spec: SlotSpec,
+		tx_count: Option<u64>,
+		timestamp: Option<u64>,
+	) -> NewSlot {
 		NewSlot {
 			height: height as i64,
 			block_hash: block_hash.map(|h| h.into()),
 			block_number: block_number.map(|n| n as i64),
+			missed: false,
+			proposer_index: Some(proposer_index as i64),
+			validators_count: validators_count.map(|c| c as i64),
+			spec: Some(spec.to_string()),
+			tx_count: tx_count.map(|c| c as i64),
+			timestamp: timestamp.map(|t| t as i64),
+		}
+	}
+
+	/// Return a new insertable slot for which no block was proposed
+	pub fn missed(height: u64) -> NewSlot {
+		NewSlot {
+			height: height as i64,
+			block_hash: None,
+			block_number: None,
+			missed: true,
+			proposer_index: None,
+			validators_count: None,
+			spec: None,
+			tx_count: Some(0),
+			timestamp: None,
 		}
 	}
 
@@ -29,7 +78,7 @@ impl NewSlot {
 	/// On conflict do nothing
 	///
 	/// Return the number of affected rows
-	pub fn insert_do_nothing(&self, conn: &PgConnection) -> QueryResult<usize> {
+	pub fn insert_do_nothing(&self, conn: &DbConnection) -> QueryResult<usize> {
 		let affected_rows = diesel::insert_into(slots::table)
 			.values(self)
 			.on_conflict_do_nothing()
@@ -41,7 +90,157 @@ impl NewSlot {
 	/// Insert a new slot on db
 	///
 	/// Fail in case of conflict
-	pub fn insert(&self, conn: &PgConnection) -> QueryResult<usize> {
+	pub fn insert(&self, conn: &DbConnection) -> QueryResult<usize> {
 		diesel::insert_into(slots::table).values(self).execute(conn)
 	}
+
+	/// Set the number of validators at a slot
+	pub fn set_validators_count(
+		conn: &DbConnection,
+		height: u64,
+		count: u64,
+	) -> QueryResult<usize> {
+		diesel::update(slots::dsl::slots.find(height as i64))
+			.set(slots::validators_count.eq(Some(count as i64)))
+			.execute(conn)
+	}
+
+	/// Set the timestamp of the execution block of a slot
+	pub fn set_timestamp(conn: &DbConnection, height: u64, timestamp: u64) -> QueryResult<usize> {
+		diesel::update(slots::dsl::slots.find(height as i64))
+			.set(slots::timestamp.eq(Some(timestamp as i64)))
+			.execute(conn)
+	}
+
+	/// Upsert a slot on db
+	///
+	/// On conflict overwrite the stored slot, after moving it to the history if it differs.
+	/// It is recorded as reorged if it holds another block, or lost its block or got one.
+	pub fn upsert(&self, conn: &DbConnection) -> QueryResult<usize> {
+		conn.transaction(|| {
+			let stored: Option<StoredSlot> = slots::dsl::slots
+				.find(self.height)
+				.select((
+					slots::block_hash,
+					slots::block_number,
+					slots::missed,
+					slots::proposer_index,
+					slots::validators_count,
+					slots::spec,
+					slots::tx_count,
+					slots::timestamp,
+				))
+				.first(conn)
+				.optional()?;
+			if let Some(stored) = stored {
+				if let Some(reason) = self.supersede_reason(&stored) {
+					diesel::insert_into(slots_history::table)
+						.values(&NewSlotHistory::new(self.height, stored, reason))
+						.execute(conn)?;
+				}
+			}
+
+			diesel::insert_into(slots::table)
+				.values(self)
+				.on_conflict(slots::height)
+				.do_update()
+				.set((
+					slots::block_hash.eq(excluded(slots::block_hash)),
+					slots::block_number.eq(excluded(slots::block_number)),
+					slots::missed.eq(excluded(slots::missed)),
+					slots::proposer_index.eq(excluded(slots::proposer_index)),
+					slots::validators_count.eq(excluded(slots::validators_count)),
+					slots::spec.eq(excluded(slots::spec)),
+					slots::tx_count.eq(excluded(slots::tx_count)),
+					slots::timestamp.eq(excluded(slots::timestamp)),
+				))
+				.execute(conn)
+		})
+	}
+
+	// Return why `stored` is superseded by this slot, if it is not the same
+	fn supersede_reason(&self, stored: &StoredSlot) -> Option<SupersedeReason> {
+		let (
+			block_hash,
+			block_number,
+			missed,
+			proposer_index,
+			validators_count,
+			spec,
+			tx_count,
+			timestamp,
+		) = stored;
+
+		if *block_hash != self.block_hash || *missed != self.missed {
+			Some(SupersedeReason::Reorg)
+		} else if *block_number != self.block_number
+			|| *proposer_index != self.proposer_index
+			|| *validators_count != self.validators_count
+			|| *spec != self.spec
+			|| *tx_count != self.tx_count
+			|| *timestamp != self.timestamp
+		{
+			Some(SupersedeReason::Reprocess)
+		} else {
+			None
+		}
+	}
+}
+
+// Columns of a stored slot, but its height
+type StoredSlot = (
+	Option<Hash256>,
+	Option<i64>,
+	bool,
+	Option<i64>,
+	Option<i64>,
+	Option<String>,
+	Option<i64>,
+	Option<i64>,
+);
+
+/// Representation of a superseded slot to be inserted in the history
+///
+/// It is timestamped on insert.
+#[derive(Insertable)]
+#[table_name = "slots_history"]
+struct NewSlotHistory {
+	height: i64,
+	block_hash: Option<Hash256>,
+	block_number: Option<i64>,
+	missed: bool,
+	proposer_index: Option<i64>,
+	validators_count: Option<i64>,
+	spec: Option<String>,
+	tx_count: Option<i64>,
+	timestamp: Option<i64>,
+	reason: String,
+}
+
+impl NewSlotHistory {
+	fn new(height: i64, stored: StoredSlot, reason: SupersedeReason) -> Self {
+		let (
+			block_hash,
+			block_number,
+			missed,
+			proposer_index,
+			validators_count,
+			spec,
+			tx_count,
+			timestamp,
+		) = stored;
+
+		NewSlotHistory {
+			height,
+			block_hash,
+			block_number,
+			missed,
+			proposer_index,
+			validators_count,
+			spec,
+			tx_count,
+			timestamp,
+			reason: reason.to_string(),
+		}
+	}
 }
@@ -1,11 +1,18 @@
+use std::{convert::Infallible, fmt::Display, str::FromStr};
+
 use crate::{
-	models::Hash256,
-	schema::{slots, slots::dsl::slots as dsl_slots},
+	models::{Hash160, Hash256, RawPayload, ReprocessLayer},
+	schema::{
+		slots, slots::dsl::slots as dsl_slots, slots_history,
+		slots_history::dsl::slots_history as dsl_slots_history, transactions, validators,
+	},
+	DbConnection,
 };
 use diesel::{
-	ExpressionMethods, Identifiable, PgConnection, QueryDsl, QueryResult, Queryable, RunQueryDsl,
+	dsl::sql, sql_types::BigInt, ExpressionMethods, Identifiable, OptionalExtension, QueryDsl,
+	QueryResult, Queryable, RunQueryDsl,
 };
-use primitive_types::H256;
+use primitive_types::{H160, H256};
 use serde::{Deserialize, Serialize};
 
 #[derive(Queryable, Identifiable)]
@@ -17,6 +24,12 @@ struct DbSlot {
 	height: i64,
 	block_hash: Option<Hash256>,
 	block_number: Option<i64>,
+	missed: bool,
+	proposer_index: Option<i64>,
+	validators_count: Option<i64>,
+	spec: Option<String>,
+	tx_count: Option<i64>,
+	timestamp: Option<i64>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -24,6 +37,13 @@ pub struct Slot {
 	height: u64,
 	block_hash: Option<H256>,
 	block_number: Option<u64>,
+	missed: bool,
+	proposer_index: Option<u64>,
+	validators_count: Option<u64>,
+	spec: Option<SlotSpec>,
+	tx_count: Option<u64>,
+	/// Seconds since the UNIX epoch of the slot's execution block
+	timestamp: Option<u64>,
 }
 
 impl From<DbSlot> for Slot {
@@ -32,11 +52,142 @@ impl From<DbSlot> for Slot {
 			height: db_slot.height as u64,
 			block_hash: db_slot.block_hash.map(|h| h.into()),
 			block_number: db_slot.block_number.map(|n| n as u64),
+			missed: db_slot.missed,
+			proposer_index: db_slot.proposer_index.map(|i| i as u64),
+			validators_count: db_slot.validators_count.map(|c| c as u64),
+			spec: db_slot.spec.map(SlotSpec::from),
+			tx_count: db_slot.tx_count.map(|c| c as u64),
+			timestamp: db_slot.timestamp.map(|t| t as u64),
+		}
+	}
+}
+
+/// Fork a slot's block was built for
+///
+/// Stored as its canonical name. Names of forks unknown to this version are kept as they are.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(into = "String", from = "String")]
+pub enum SlotSpec {
+	Phase0,
+	Altair,
+	Merge,
+	Other(String),
+}
+
+impl Display for SlotSpec {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			SlotSpec::Phase0 => write!(f, "phase0"),
+			SlotSpec::Altair => write!(f, "altair"),
+			SlotSpec::Merge => write!(f, "merge"),
+			SlotSpec::Other(name) => write!(f, "{name}"),
+		}
+	}
+}
+
+impl FromStr for SlotSpec {
+	type Err = Infallible;
+
+	// Known names are matched whatever their casing
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Ok(match s.to_ascii_lowercase().as_str() {
+			"phase0" => SlotSpec::Phase0,
+			"altair" => SlotSpec::Altair,
+			"merge" => SlotSpec::Merge,
+			_ => SlotSpec::Other(s.to_string()),
+		})
+	}
+}
+
+impl From<String> for SlotSpec {
+	fn from(s: String) -> Self {
+		s.parse().unwrap_or_else(|e: Infallible| match e {})
+	}
+}
+
+impl From<SlotSpec> for String {
+	fn from(spec: SlotSpec) -> Self {
+		spec.to_string()
+	}
+}
+
+/// Why a stored slot was replaced
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SupersedeReason {
+	/// The slot now holds another block, or lost its block or got one
+	Reorg,
+	/// The slot holds the same block, but some of its fields changed
+	Reprocess,
+}
+
+impl Display for SupersedeReason {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			SupersedeReason::Reorg => write!(f, "reorg"),
+			SupersedeReason::Reprocess => write!(f, "reprocess"),
 		}
 	}
 }
 
+impl FromStr for SupersedeReason {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"reorg" => Ok(SupersedeReason::Reorg),
+			"reprocess" => Ok(SupersedeReason::Reprocess),
+			_ => Err(()),
+		}
+	}
+}
+
+/// Former version of a slot, replaced by a reprocess
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SupersededSlot {
+	#[serde(flatten)]
+	pub slot: Slot,
+	pub reason: SupersedeReason,
+	/// Seconds since the UNIX epoch at which the slot was replaced
+	pub superseded_at: u64,
+}
+
 impl Slot {
+	/// Return the former versions of the slot at `height`, from the oldest
+	///
+	/// The history is append only, former versions are never deleted.
+	pub fn history(conn: &DbConnection, height: u64) -> QueryResult<Vec<SupersededSlot>> {
+		let rows: Vec<(DbSlot, String, i64)> = dsl_slots_history
+			.select((
+				(
+					slots_history::height,
+					slots_history::block_hash,
+					slots_history::block_number,
+					slots_history::missed,
+					slots_history::proposer_index,
+					slots_history::validators_count,
+					slots_history::spec,
+					slots_history::tx_count,
+					slots_history::timestamp,
+				),
+				slots_history::reason,
+				slots_history::superseded_at,
+			))
+			.filter(slots_history::height.eq(height as i64))
+			.order(slots_history::id.asc())
+			.load(conn)?;
+
+		Ok(rows
+			.into_iter()
+			.map(|(db_slot, reason, superseded_at)| SupersededSlot {
+				slot: db_slot.into(),
+				// Safe to unwrap because only valid reasons are inserted
+				reason: reason.parse().unwrap(),
+				superseded_at: superseded_at as u64,
+			})
+			.collect())
+	}
+
 	/// Return the height of the slot
 	pub fn height(&self) -> u64 {
 		self.height
@@ -52,17 +203,288 @@ impl Slot {
 		self.block_number
 	}
 
+	/// Return true if no block was proposed for the slot
+	pub fn missed(&self) -> bool {
+		self.missed
+	}
+
+	/// Return the index of the validator that proposed the slot's block
+	pub fn proposer_index(&self) -> Option<u64> {
+		self.proposer_index
+	}
+
+	/// Return the number of validators at the slot, if already fetched
+	pub fn validators_count(&self) -> Option<u64> {
+		self.validators_count
+	}
+
+	/// Return the name of the fork the slot's block was built for
+	pub fn spec(&self) -> Option<&SlotSpec> {
+		self.spec.as_ref()
+	}
+
+	/// Return the number of transactions of the slot's execution block, if known
+	///
+	/// Unknown for pre-merge blocks, and for slots stored before it was recorded until they are
+	/// reprocessed. Missed slots have none.
+	pub fn tx_count(&self) -> Option<u64> {
+		self.tx_count
+	}
+
+	/// Return the number of transactions of the slot at `height`, if it is stored and known
+	pub fn tx_count_at(conn: &DbConnection, height: u64) -> QueryResult<Option<u64>> {
+		let count: Option<Option<i64>> =
+			dsl_slots.find(height as i64).select(slots::tx_count).first(conn).optional()?;
+
+		Ok(count.flatten().map(|c| c as u64))
+	}
+
+	/// Return the timestamp of the slot's execution block, if known
+	///
+	/// Unknown for pre-merge blocks, and for slots stored before it was recorded until they are
+	/// backfilled. Missed slots have none.
+	pub fn timestamp(&self) -> Option<u64> {
+		self.timestamp
+	}
+
+	/// Return the timestamp of the slot at `height`, if it is stored and known
+	pub fn timestamp_at(conn: &DbConnection, height: u64) -> QueryResult<Option<u64>> {
+		let timestamp: Option<Option<i64>> =
+			dsl_slots.find(height as i64).select(slots::timestamp).first(conn).optional()?;
+
+		Ok(timestamp.flatten().map(|t| t as u64))
+	}
+
+	/// Return the number of slots stored for every spec, by spec name
+	///
+	/// Missed slots, and slots stored before specs were recorded, are not counted.
+	pub fn count_by_spec(conn: &DbConnection) -> QueryResult<Vec<(SlotSpec, i64)>> {
+		let counts: Vec<(Option<String>, i64)> = dsl_slots
+			.select((slots::spec, sql::<BigInt>("COUNT(*)")))
+			.filter(slots::spec.is_not_null())
+			.group_by(slots::spec)
+			.order(slots::spec.asc())
+			.load(conn)?;
+
+		// Safe to use `unwrap_unchecked` because we filtered NOT NULL in the query
+		let counts = counts
+			.into_iter()
+			.map(|(s, c)| (unsafe { s.unwrap_unchecked() }.into(), c))
+			.collect();
+
+		Ok(counts)
+	}
+
+	/// Return the block response stored for the slot at `height`, if any
+	///
+	/// Only stored when the indexer is run with raw payloads enabled.
+	pub fn raw_payload(conn: &DbConnection, height: u64) -> QueryResult<Option<String>> {
+		RawPayload::get(conn, ReprocessLayer::Consensus, height)
+	}
+
 	/// Return the highest slot from db
-	pub fn get_highest(conn: &PgConnection) -> QueryResult<Slot> {
+	pub fn get_highest(conn: &DbConnection) -> QueryResult<Slot> {
 		let slot = dsl_slots.order(slots::height.desc()).first::<DbSlot>(conn)?;
 
 		Ok(slot.into())
 	}
 
 	/// Return an unique slot from db
-	pub fn get(conn: &PgConnection, height: u64) -> QueryResult<Slot> {
+	pub fn get(conn: &DbConnection, height: u64) -> QueryResult<Slot> {
 		let slot = dsl_slots.find(height as i64).first::<DbSlot>(conn)?;
 
 		Ok(slot.into())
 	}
+
+	/// Return the number of missed slots between `from` and `to` included
+	pub fn missed_count_in_range(conn: &DbConnection, from: u64, to: u64) -> QueryResult<u64> {
+		let count: i64 = dsl_slots
+			.filter(slots::height.between(from as i64, to as i64))
+			.filter(slots::missed.eq(true))
+			.count()
+			.get_result(conn)?;
+
+		Ok(count as u64)
+	}
+
+	/// Return the execution block hash of the slots stored among `heights`, by ascending height
+	///
+	/// Heights not stored are left out. The hash is None for missed slots and pre-merge blocks.
+	pub fn block_hashes_for(
+		conn: &DbConnection,
+		heights: &[u64],
+	) -> QueryResult<Vec<(u64, Option<H256>)>> {
+		let heights: Vec<i64> = heights.iter().map(|h| *h as i64).collect();
+		let rows: Vec<(i64, Option<Hash256>)> = dsl_slots
+			.select((slots::height, slots::block_hash))
+			.filter(slots::height.eq_any(heights))
+			.order(slots::height.asc())
+			.load(conn)?;
+
+		Ok(rows.into_iter().map(|(h, hash)| (h as u64, hash.map(|h| h.into()))).collect())
+	}
+
+	/// Return the heights of the slots stored between `from` and `to` included, by ascending height
+	///
+	/// Missed slots are stored, so they are returned too.
+	pub fn present_heights_in_range(
+		conn: &DbConnection,
+		from: u64,
+		to: u64,
+	) -> QueryResult<Vec<u64>> {
+		let heights: Vec<i64> = dsl_slots
+			.select(slots::height)
+			.filter(slots::height.between(from as i64, to as i64))
+			.order(slots::height.asc())
+			.load(conn)?;
+
+		Ok(heights.into_iter().map(|h| h as u64).collect())
+	}
+
+	/// Return the height and validators count of the highest slot below `height` holding a count
+	pub fn previous_validators_count(
+		conn: &DbConnection,
+		height: u64,
+	) -> QueryResult<Option<(u64, u64)>> {
+		let previous: Option<(i64, Option<i64>)> = dsl_slots
+			.select((slots::height, slots::validators_count))
+			.filter(slots::height.lt(height as i64))
+			.filter(slots::validators_count.is_not_null())
+			.order(slots::height.desc())
+			.first(conn)
+			.optional()?;
+
+		// Counts are never NULL here, filtered in the query
+		Ok(previous.and_then(|(h, count)| count.map(|c| (h as u64, c as u64))))
+	}
+
+	/// Return the number of the execution block proposed at `height`, or in the closest slot below
+	///
+	/// Missed slots hold no block, so the one of the previous proposed slot is returned, like
+	/// pre-merge slots whose payload is numbered 0. Only stored slots are looked at: None if none
+	/// of them below `height` holds a block, e.g. before the merge.
+	pub fn block_at_or_before_slot(conn: &DbConnection, height: u64) -> QueryResult<Option<u64>> {
+		let block_number: Option<Option<i64>> = dsl_slots
+			.select(slots::block_number)
+			.filter(slots::height.le(height as i64))
+			.filter(slots::block_number.gt(0))
+			.order(slots::height.desc())
+			.first(conn)
+			.optional()?;
+
+		Ok(block_number.flatten().map(|n| n as u64))
+	}
+
+	/// Return the height of the stored slot holding the execution block `block_number`, or None
+	/// if it was proposed before the merge or its slot is not stored
+	pub fn slot_of_block(conn: &DbConnection, block_number: u64) -> QueryResult<Option<u64>> {
+		let height: Option<i64> = dsl_slots
+			.select(slots::height)
+			.filter(slots::block_number.eq(block_number as i64))
+			.filter(slots::block_number.gt(0))
+			.first(conn)
+			.optional()?;
+
+		Ok(height.map(|h| h as u64))
+	}
+
+	/// Return at most `limit` heights of post-merge slots whose timestamp is still unknown, by
+	/// ascending height
+	pub fn heights_missing_timestamp(conn: &DbConnection, limit: u32) -> QueryResult<Vec<u64>> {
+		let heights: Vec<i64> = dsl_slots
+			.select(slots::height)
+			.filter(slots::timestamp.is_null())
+			.filter(slots::block_number.gt(0))
+			.order(slots::height.asc())
+			.limit(limit as i64)
+			.load(conn)?;
+
+		Ok(heights.into_iter().map(|h| h as u64).collect())
+	}
+
+	/// Return at most `limit` heights of proposed slots whose validators count is still unknown,
+	/// strictly above `after` if set, by ascending height
+	///
+	/// Go through every such slot by passing the last height returned as `after` to the next call,
+	/// so that slots left without a count do not hold back the ones above.
+	pub fn heights_missing_count(
+		conn: &DbConnection,
+		after: Option<u64>,
+		limit: u32,
+	) -> QueryResult<Vec<u64>> {
+		let mut query = dsl_slots
+			.select(slots::height)
+			.filter(slots::validators_count.is_null())
+			.filter(slots::missed.eq(false))
+			.into_boxed();
+		if let Some(after) = after {
+			query = query.filter(slots::height.gt(after as i64));
+		}
+
+		let heights: Vec<i64> = query.order(slots::height.asc()).limit(limit as i64).load(conn)?;
+
+		Ok(heights.into_iter().map(|h| h as u64).collect())
+	}
+
+	/// Return the number of blocks proposed between `from` and `to` included by the validators
+	/// deposited by `address`
+	pub fn proposed_count(
+		conn: &DbConnection,
+		address: H160,
+		from: u64,
+		to: u64,
+	) -> QueryResult<i64> {
+		let address: Hash160 = address.into();
+
+		dsl_slots
+			.inner_join(validators::table.inner_join(transactions::table))
+			.filter(transactions::from.eq(address))
+			.filter(slots::height.between(from as i64, to as i64))
+			.count()
+			.get_result(conn)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const KNOWN: [(SlotSpec, &str); 3] = [
+		(SlotSpec::Phase0, "phase0"),
+		(SlotSpec::Altair, "altair"),
+		(SlotSpec::Merge, "merge"),
+	];
+
+	#[test]
+	fn known_specs_are_stored_as_their_canonical_name() {
+		for (spec, name) in KNOWN {
+			assert_eq!(spec.to_string(), name);
+			assert_eq!(String::from(spec), name);
+		}
+	}
+
+	#[test]
+	fn known_specs_are_parsed_whatever_their_casing() {
+		for (spec, name) in KNOWN {
+			assert_eq!(SlotSpec::from(name.to_string()), spec);
+			assert_eq!(SlotSpec::from(name.to_uppercase()), spec);
+		}
+		assert_eq!(SlotSpec::from("Phase0".to_string()), SlotSpec::Phase0);
+	}
+
+	#[test]
+	fn unknown_specs_are_kept_as_they_are() {
+		let spec = SlotSpec::from("Bellatrix".to_string());
+
+		assert_eq!(spec, SlotSpec::Other("Bellatrix".to_string()));
+		assert_eq!(spec.to_string(), "Bellatrix");
+	}
+
+	#[test]
+	fn specs_round_trip_through_their_name() {
+		let specs = KNOWN.into_iter().map(|(spec, _)| spec);
+		for spec in specs.chain([SlotSpec::Other("capella".to_string())]) {
+			assert_eq!(SlotSpec::from(String::from(spec.clone())), spec);
+		}
+	}
 }
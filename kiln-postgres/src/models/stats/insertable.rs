@@ -0,0 +1,89 @@
+use diesel::{
+	pg::upsert::excluded, sql_query, sql_types::BigInt, Connection, ExpressionMethods, Insertable,
+	QueryDsl, QueryResult, RunQueryDsl,
+};
+
+use super::StatKey;
+
+use crate::{models::Transaction, schema::stats, DbConnection};
+
+/// Key of the advisory lock of the `IssuersCount` stat, see `NewStat::lock_issuers_count`
+const ISSUERS_COUNT_LOCK: i64 = 0x6973_7375_6572_73;
+
+/// Representation of a row to be inserted
+#[derive(Insertable)]
+#[table_name = "stats"]
+pub struct NewStat {
+	name: String,
+	value: i64,
+}
+
+impl NewStat {
+	/// Return a new insertable stat
+	pub fn new(key: StatKey, value: u64) -> NewStat {
+		NewStat {
+			name: key.as_str().to_string(),
+			value: value as i64,
+		}
+	}
+
+	/// Upsert a stat on db
+	///
+	/// On conflict overwrite the stored value
+	pub fn upsert(&self, conn: &DbConnection) -> QueryResult<usize> {
+		diesel::insert_into(stats::table)
+			.values(self)
+			.on_conflict(stats::name)
+			.do_update()
+			.set(stats::value.eq(excluded(stats::value)))
+			.execute(conn)
+	}
+
+	/// Add one to the stat stored at `key`, starting from 0 if there is none
+	pub fn increment(conn: &DbConnection, key: StatKey) -> QueryResult<usize> {
+		NewStat::add(conn, key, 1)
+	}
+
+	/// Add `amount` to the stat stored at `key`, starting from 0 if there is none
+	pub fn add(conn: &DbConnection, key: StatKey, amount: u64) -> QueryResult<usize> {
+		diesel::insert_into(stats::table)
+			.values(NewStat::new(key, amount))
+			.on_conflict(stats::name)
+			.do_update()
+			.set(stats::value.eq(stats::value + amount as i64))
+			.execute(conn)
+	}
+
+	/// Add `delta` to the stat stored at `key`, if there is one
+	pub fn shift_stored(conn: &DbConnection, key: StatKey, delta: i64) -> QueryResult<usize> {
+		diesel::update(stats::table.filter(stats::name.eq(key.as_str())))
+			.set(stats::value.eq(stats::value + delta))
+			.execute(conn)
+	}
+
+	/// Lock the `IssuersCount` stat until the end of the database transaction `conn` is in
+	///
+	/// Every update of the count takes it, from reading the issuers it counts to its commit, so
+	/// concurrent writers of transactions are counted one after the other.
+	pub(crate) fn lock_issuers_count(conn: &DbConnection) -> QueryResult<()> {
+		sql_query("SELECT pg_advisory_xact_lock($1)")
+			.bind::<BigInt, _>(ISSUERS_COUNT_LOCK)
+			.execute(conn)?;
+
+		Ok(())
+	}
+
+	/// Count the stored issuers, storing the result in the `IssuersCount` stat
+	///
+	/// Scans every transaction. Holds the lock of the count, so no transaction written meanwhile
+	/// is left out.
+	pub fn recount_issuers(conn: &DbConnection) -> QueryResult<u64> {
+		conn.transaction(|| {
+			NewStat::lock_issuers_count(conn)?;
+			let count = Transaction::count_distinct_issuers(conn)?;
+			NewStat::new(StatKey::IssuersCount, count).upsert(conn)?;
+
+			Ok(count)
+		})
+	}
+}
@@ -0,0 +1,50 @@
+use diesel::{OptionalExtension, QueryDsl, QueryResult, RunQueryDsl};
+
+use crate::DbConnection;
+
+use crate::schema::{stats, stats::dsl::stats as dsl_stats};
+
+/// Key of a value shared between the indexer and the api
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatKey {
+	/// Highest slot stored by the consensus syncer
+	ConsensusDbHeight,
+	/// Highest block stored by the execution syncer
+	ExecutionDbHeight,
+	/// Highest slot known by the consensus node
+	ConsensusNodeHeight,
+	/// Number of the highest execution block finalized by the consensus node
+	FinalizedBlockNumber,
+	/// Incremented every time a validator is slashed or linked to its deposit
+	ValidatorsGeneration,
+	/// Number of distinct transaction issuers stored, kept up to date by the execution syncer
+	IssuersCount,
+	/// 1 while the indexer must not sync anything, set from the api
+	SyncPaused,
+}
+
+impl StatKey {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			StatKey::ConsensusDbHeight => "consensus_db_height",
+			StatKey::ExecutionDbHeight => "execution_db_height",
+			StatKey::ConsensusNodeHeight => "consensus_node_height",
+			StatKey::FinalizedBlockNumber => "finalized_block_number",
+			StatKey::ValidatorsGeneration => "validators_generation",
+			StatKey::IssuersCount => "issuers_count",
+			StatKey::SyncPaused => "sync_paused",
+		}
+	}
+}
+
+pub struct Stat;
+
+impl Stat {
+	/// Return the value stored at `key`, if any
+	pub fn get(conn: &DbConnection, key: StatKey) -> QueryResult<Option<u64>> {
+		let value: Option<i64> =
+			dsl_stats.find(key.as_str()).select(stats::value).first(conn).optional()?;
+
+		Ok(value.map(|v| v as u64))
+	}
+}
@@ -1,9 +1,14 @@
-use diesel::{ExpressionMethods, PgConnection, QueryDsl, QueryResult, RunQueryDsl};
+use std::collections::HashSet;
+
+use diesel::{
+	pg::upsert::excluded, Connection, ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl,
+};
 use primitive_types::{H160, H256, U256};
 
 use crate::{
-	models::{Hash160, Hash256},
+	models::{Hash160, Hash256, NewStat, StatKey, Transaction},
 	schema::transactions,
+	DbConnection,
 };
 
 #[derive(Insertable, Identifiable)]
@@ -17,10 +22,15 @@ pub struct NewTransaction {
 	to: Option<Hash160>,
 	input: Vec<u8>,
 	value: Vec<u8>,
+	nonce: i64,
+	block_number: i64,
+	/// EIP-2718 type, as reported by the node, None if it reports none
+	tx_type: Option<i64>,
 }
 
 impl NewTransaction {
 	/// Return a new insertable Transaction
+	#[allow(clippy::too_many_arguments)]
 	pub fn new(
 		hash: H256,
 		block_hash: H256,
@@ -29,6 +39,9 @@ impl NewTransaction {
 		to: Option<H160>,
 		input: Vec<u8>,
 		value: U256,
+		nonce: u64,
+		block_number: u64,
+		tx_type: Option<u64>,
 	) -> NewTransaction {
 		NewTransaction {
 			hash: hash.into(),
@@ -38,32 +51,133 @@ impl NewTransaction {
 			to: to.map(|t| t.into()),
 			input,
 			value: u256_to_vec_u8(value),
+			nonce: nonce as i64,
+			block_number: block_number as i64,
+			tx_type: tx_type.map(|t| t as i64),
 		}
 	}
 
 	/// Insert a new transaction on db
 	///
 	/// Fail in case of conflict
-	pub fn insert(&self, conn: &PgConnection) -> QueryResult<usize> {
+	pub fn insert(&self, conn: &DbConnection) -> QueryResult<usize> {
 		diesel::insert_into(transactions::table).values(self).execute(conn)
 	}
 
 	/// Set a transaction status
-	pub fn set_status(conn: &PgConnection, hash: H256, status: bool) -> QueryResult<usize> {
+	pub fn set_status(conn: &DbConnection, hash: H256, status: bool) -> QueryResult<usize> {
 		let hash: Hash256 = hash.into();
 		diesel::update(transactions::dsl::transactions.find(hash))
 			.set(transactions::status.eq(Some(status)))
 			.execute(conn)
 	}
+
+	/// Set the address of the contract created by a deployment transaction
+	pub fn set_contract_address(
+		conn: &DbConnection,
+		hash: H256,
+		contract_address: H160,
+	) -> QueryResult<usize> {
+		let hash: Hash256 = hash.into();
+		let contract_address: Hash160 = contract_address.into();
+		diesel::update(transactions::dsl::transactions.find(hash))
+			.set(transactions::contract_address.eq(Some(contract_address)))
+			.execute(conn)
+	}
 }
 
 pub struct NewTransactions(Vec<NewTransaction>);
 
 impl NewTransactions {
-	pub fn batch_insert(&self, conn: &PgConnection) -> QueryResult<usize> {
+	pub fn batch_insert(&self, conn: &DbConnection) -> QueryResult<usize> {
 		diesel::insert_into(transactions::table).values(&self.0).execute(conn)
 	}
 
+	/// Insert an array of transactions in db, skipping the ones already stored
+	///
+	/// Return the number of transactions inserted.
+	pub fn batch_insert_new(&self, conn: &DbConnection) -> QueryResult<usize> {
+		diesel::insert_into(transactions::table)
+			.values(&self.0)
+			.on_conflict(transactions::hash)
+			.do_nothing()
+			.execute(conn)
+	}
+
+	/// Upsert an array of transactions in db
+	///
+	/// On conflict overwrite every field but `status`
+	pub fn batch_upsert(&self, conn: &DbConnection) -> QueryResult<usize> {
+		diesel::insert_into(transactions::table)
+			.values(&self.0)
+			.on_conflict(transactions::hash)
+			.do_update()
+			.set((
+				transactions::block_hash.eq(excluded(transactions::block_hash)),
+				transactions::index.eq(excluded(transactions::index)),
+				transactions::from.eq(excluded(transactions::from)),
+				transactions::to.eq(excluded(transactions::to)),
+				transactions::input.eq(excluded(transactions::input)),
+				transactions::value.eq(excluded(transactions::value)),
+				transactions::nonce.eq(excluded(transactions::nonce)),
+				transactions::block_number.eq(excluded(transactions::block_number)),
+				transactions::tx_type.eq(excluded(transactions::tx_type)),
+			))
+			.execute(conn)
+	}
+
+	/// Same as `batch_insert_new`, keeping the `IssuersCount` stat right if it is set
+	pub fn batch_insert_new_counted(&self, conn: &DbConnection) -> QueryResult<usize> {
+		self.counting_issuers(conn, false, || self.batch_insert_new(conn))
+	}
+
+	/// Same as `batch_upsert`, keeping the `IssuersCount` stat right if it is set
+	///
+	/// An overwritten transaction can change sender, so its stored sender is counted again too.
+	pub fn batch_upsert_counted(&self, conn: &DbConnection) -> QueryResult<usize> {
+		self.counting_issuers(conn, true, || self.batch_upsert(conn))
+	}
+
+	// Run `write` in a transaction holding the lock of the `IssuersCount` stat, shifting the stat
+	// by the number of issuers it adds or removes
+	//
+	// Only the senders of the transactions, and the stored senders of the ones they overwrite if
+	// `overwriting`, can become or stop being issuers. They are compared before and after.
+	fn counting_issuers<F>(
+		&self,
+		conn: &DbConnection,
+		overwriting: bool,
+		write: F,
+	) -> QueryResult<usize>
+	where
+		F: FnOnce() -> QueryResult<usize>,
+	{
+		if self.0.is_empty() {
+			return write()
+		}
+
+		conn.transaction(|| {
+			NewStat::lock_issuers_count(conn)?;
+
+			let mut senders: HashSet<H160> =
+				self.0.iter().filter_map(|t| t.from).map(H160::from).collect();
+			if overwriting {
+				let hashes: Vec<H256> = self.0.iter().map(|t| t.hash.into()).collect();
+				senders.extend(Transaction::senders_of(conn, &hashes)?);
+			}
+			let senders: Vec<H160> = senders.into_iter().collect();
+
+			let before = Transaction::known_issuers(conn, &senders)?.len() as i64;
+			let written = write()?;
+			let after = Transaction::known_issuers(conn, &senders)?.len() as i64;
+			if after != before {
+				NewStat::shift_stored(conn, StatKey::IssuersCount, after - before)?;
+			}
+
+			Ok(written)
+		})
+	}
+
 	pub fn new(transactions: Vec<NewTransaction>) -> Self {
 		Self(transactions)
 	}
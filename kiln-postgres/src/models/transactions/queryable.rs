@@ -1,12 +1,22 @@
 use diesel::{
-	ExpressionMethods, Identifiable, PgConnection, QueryDsl, QueryResult, Queryable, RunQueryDsl,
+	dsl::{exists, max, sql},
+	sql_query,
+	sql_types::{Array, BigInt, Binary, Bool, Nullable},
+	ExpressionMethods, Identifiable, OptionalExtension, QueryDsl, QueryResult, Queryable,
+	RunQueryDsl,
 };
+use std::{collections::HashMap, fmt::Display, str::FromStr};
+
 use primitive_types::{H160, H256, U256};
 use serde::{Deserialize, Serialize};
 
 use crate::{
+	checksum::serialize_checksummed_option,
 	models::{Hash160, Hash256},
-	schema::{transactions, transactions::dsl::transactions as dsl_transactions},
+	schema::{
+		execution_blocks, slots, transactions, transactions::dsl::transactions as dsl_transactions,
+	},
+	DbConnection,
 };
 
 #[derive(Queryable, Identifiable)]
@@ -21,6 +31,123 @@ struct DbTransaction {
 	input: Vec<u8>,
 	value: Vec<u8>,
 	status: Option<bool>,
+	nonce: Option<i64>,
+	contract_address: Option<Hash160>,
+	block_number: i64,
+	tx_type: Option<i64>,
+}
+
+/// Issuers kept when listing or counting them, every one by default
+#[derive(Debug, Clone, Default)]
+pub struct IssuerFilter {
+	/// When set, only those issuers are kept
+	pub allowed: Option<Vec<H160>>,
+	/// Those issuers are never kept
+	pub denied: Vec<H160>,
+}
+
+impl IssuerFilter {
+	/// Return true if every issuer is kept
+	pub fn is_empty(&self) -> bool {
+		self.allowed.is_none() && self.denied.is_empty()
+	}
+}
+
+#[derive(QueryableByName)]
+struct DbCount {
+	#[sql_type = "BigInt"]
+	count: i64,
+}
+
+#[derive(QueryableByName)]
+struct DbActivityBucket {
+	#[sql_type = "BigInt"]
+	bucket: i64,
+	#[sql_type = "BigInt"]
+	count: i64,
+}
+
+#[derive(QueryableByName)]
+struct DbAddressActivity {
+	#[sql_type = "Binary"]
+	address: Hash160,
+	#[sql_type = "BigInt"]
+	transactions_count: i64,
+	#[sql_type = "BigInt"]
+	active_days: i64,
+	#[sql_type = "Bool"]
+	used_type: bool,
+	#[sql_type = "Bool"]
+	used_target_contract: bool,
+	#[sql_type = "Nullable<BigInt>"]
+	max_nonce: Option<i64>,
+	#[sql_type = "BigInt"]
+	deployed_contracts: i64,
+}
+
+#[derive(QueryableByName)]
+struct DbAddressCount {
+	#[sql_type = "Binary"]
+	address: Hash160,
+	#[sql_type = "BigInt"]
+	count: i64,
+}
+
+/// Aggregates of the transactions issued by an address, see `Transaction::activity_for_addresses`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AddressActivity {
+	pub transactions_count: u64,
+	/// Number of distinct UTC days with a transaction
+	pub active_days: i64,
+	/// Whether a transaction of the EIP-2718 type looked for was issued
+	pub used_type: bool,
+	/// Whether one of the target contracts was called
+	pub used_target_contract: bool,
+	pub max_nonce: Option<u64>,
+	pub deployed_contracts: u64,
+}
+
+/// Number of transactions issued in a range of blocks
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ActivityBucket {
+	/// Number of the first block of the range
+	pub bucket: u64,
+	pub count: u64,
+}
+
+/// What a transaction does, told apart from its recipient and input
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionKind {
+	/// Sent to an address with some input
+	Call,
+	/// Sent to an address without input
+	Transfer,
+	/// Sent to no address, creating a contract
+	Deploy,
+}
+
+impl Display for TransactionKind {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			TransactionKind::Call => write!(f, "call"),
+			TransactionKind::Transfer => write!(f, "transfer"),
+			TransactionKind::Deploy => write!(f, "deploy"),
+		}
+	}
+}
+
+impl FromStr for TransactionKind {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"call" => Ok(TransactionKind::Call),
+			"transfer" => Ok(TransactionKind::Transfer),
+			"deploy" => Ok(TransactionKind::Deploy),
+			_ => Err(()),
+		}
+	}
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -28,11 +155,21 @@ pub struct Transaction {
 	hash: H256,
 	block_hash: H256,
 	index: u64,
+	#[serde(serialize_with = "serialize_checksummed_option")]
 	from: Option<H160>,
+	#[serde(serialize_with = "serialize_checksummed_option")]
 	to: Option<H160>,
 	input: Vec<u8>,
 	value: U256,
 	status: Option<bool>,
+	nonce: Option<u64>,
+	/// Address of the contract created, if a deployment
+	#[serde(serialize_with = "serialize_checksummed_option")]
+	contract_address: Option<H160>,
+	/// Number of the block including the transaction
+	block_number: u64,
+	/// EIP-2718 type, unknown for transactions stored before it was
+	tx_type: Option<u64>,
 }
 
 impl From<DbTransaction> for Transaction {
@@ -46,19 +183,31 @@ impl From<DbTransaction> for Transaction {
 			input: db_transaction.input,
 			value: U256::from_little_endian(&db_transaction.value),
 			status: db_transaction.status,
+			nonce: db_transaction.nonce.map(|n| n as u64),
+			contract_address: db_transaction.contract_address.map(|a| a.into()),
+			block_number: db_transaction.block_number as u64,
+			tx_type: db_transaction.tx_type.map(|t| t as u64),
 		}
 	}
 }
 
 impl Transaction {
+	/// Return every transaction issued by `address`
+	///
+	/// Transactions in blocks above `finalized_below_height` are ignored, if set.
 	pub fn list_all_from_address(
-		conn: &PgConnection,
+		conn: &DbConnection,
 		address: H160,
+		finalized_below_height: Option<u64>,
 	) -> QueryResult<Vec<Transaction>> {
 		let address: Hash160 = address.into();
 
-		let db_transactions: Vec<DbTransaction> =
-			dsl_transactions.filter(transactions::from.eq(address)).load(conn)?;
+		let mut query = dsl_transactions.filter(transactions::from.eq(address)).into_boxed();
+		if let Some(height) = finalized_below_height {
+			query = query.filter(transactions::block_number.le(height as i64));
+		}
+
+		let db_transactions: Vec<DbTransaction> = query.load(conn)?;
 
 		let transactions: Vec<Transaction> =
 			db_transactions.into_iter().map(|t| t.into()).collect();
@@ -66,7 +215,42 @@ impl Transaction {
 		Ok(transactions)
 	}
 
-	pub fn list_all_distinct_issuer(conn: &PgConnection) -> QueryResult<Vec<H160>> {
+	/// Return the `limit` latest transactions issued by `address`, newest first
+	///
+	/// Only the transactions of `kind` are returned, if set. Served by the `("from", block_number)`
+	/// index.
+	pub fn recent_from_address(
+		conn: &DbConnection,
+		address: H160,
+		kind: Option<TransactionKind>,
+		limit: u32,
+	) -> QueryResult<Vec<Transaction>> {
+		let address: Hash160 = address.into();
+
+		let mut query = dsl_transactions.filter(transactions::from.eq(address)).into_boxed();
+		query = match kind {
+			Some(TransactionKind::Call) => query
+				.filter(transactions::to.is_not_null())
+				.filter(sql::<Bool>("LENGTH(input) > 0")),
+			Some(TransactionKind::Transfer) => query
+				.filter(transactions::to.is_not_null())
+				.filter(sql::<Bool>("LENGTH(input) = 0")),
+			Some(TransactionKind::Deploy) => query.filter(transactions::to.is_null()),
+			None => query,
+		};
+
+		let db_transactions: Vec<DbTransaction> = query
+			.order((
+				transactions::block_number.desc(),
+				transactions::index.desc(),
+			))
+			.limit(limit as i64)
+			.load(conn)?;
+
+		Ok(db_transactions.into_iter().map(|t| t.into()).collect())
+	}
+
+	pub fn list_all_distinct_issuer(conn: &DbConnection) -> QueryResult<Vec<H160>> {
 		let db_hashs: Vec<Option<Hash160>> = dsl_transactions
 			.select(transactions::from)
 			.filter(transactions::from.is_not_null())
@@ -80,13 +264,758 @@ impl Transaction {
 		Ok(addresses)
 	}
 
+	/// Return the number of distinct issuers
+	///
+	/// Scans every transaction, the `IssuersCount` stat is meant to be read instead when set.
+	pub fn count_distinct_issuers(conn: &DbConnection) -> QueryResult<u64> {
+		let count: i64 = dsl_transactions
+			.select(sql::<BigInt>(r#"COUNT(DISTINCT "from")"#))
+			.first(conn)?;
+
+		Ok(count as u64)
+	}
+
+	/// Return the transaction with `hash`, if stored
+	pub fn get_by_hash(conn: &DbConnection, hash: H256) -> QueryResult<Option<Transaction>> {
+		let hash: Hash256 = hash.into();
+
+		let db_transaction: Option<DbTransaction> =
+			dsl_transactions.find(hash).first(conn).optional()?;
+
+		Ok(db_transaction.map(|t| t.into()))
+	}
+
+	/// Return true if the transaction with `hash` is stored
+	pub fn exists_by_hash(conn: &DbConnection, hash: H256) -> QueryResult<bool> {
+		let hash: Hash256 = hash.into();
+
+		diesel::select(exists(dsl_transactions.find(hash))).get_result(conn)
+	}
+
+	/// Return which of `hashes` are the hashes of stored transactions
+	///
+	/// Same as `exists_by_hash`, for many transactions at once.
+	pub fn stored_hashes(conn: &DbConnection, hashes: &[H256]) -> QueryResult<Vec<H256>> {
+		let hashes: Vec<Hash256> = hashes.iter().map(|h| (*h).into()).collect();
+
+		let stored: Vec<Hash256> = dsl_transactions
+			.select(transactions::hash)
+			.filter(transactions::hash.eq_any(hashes))
+			.load(conn)?;
+
+		Ok(stored.into_iter().map(|h| h.into()).collect())
+	}
+
+	/// Return the distinct senders of the stored transactions among `hashes`
+	pub fn senders_of(conn: &DbConnection, hashes: &[H256]) -> QueryResult<Vec<H160>> {
+		let hashes: Vec<Hash256> = hashes.iter().map(|h| (*h).into()).collect();
+
+		let senders: Vec<Option<Hash160>> = dsl_transactions
+			.select(transactions::from)
+			.filter(transactions::hash.eq_any(hashes))
+			.filter(transactions::from.is_not_null())
+			.distinct()
+			.load(conn)?;
+
+		Ok(senders.into_iter().flatten().map(|s| s.into()).collect())
+	}
+
+	/// Return which of `addresses` already issued a stored transaction
+	pub fn known_issuers(conn: &DbConnection, addresses: &[H160]) -> QueryResult<Vec<H160>> {
+		let addresses: Vec<Hash160> = addresses.iter().map(|a| (*a).into()).collect();
+
+		let db_hashs: Vec<Option<Hash160>> = dsl_transactions
+			.select(transactions::from)
+			.filter(transactions::from.eq_any(addresses))
+			.distinct()
+			.load(conn)?;
+
+		// Safe to use `unwrap_unchecked` because NULL never matches `eq_any`
+		let known = db_hashs.into_iter().map(|h| unsafe { h.unwrap_unchecked().into() }).collect();
+
+		Ok(known)
+	}
+
+	/// Return the number of distinct issuers kept by `filter`
+	///
+	/// Scans every transaction, like `count_distinct_issuers`.
+	pub fn count_issuers(conn: &DbConnection, filter: &IssuerFilter) -> QueryResult<u64> {
+		let mut query =
+			dsl_transactions.select(sql::<BigInt>(r#"COUNT(DISTINCT "from")"#)).into_boxed();
+		if let Some(allowed) = &filter.allowed {
+			let allowed: Vec<Hash160> = allowed.iter().map(|a| (*a).into()).collect();
+			query = query.filter(transactions::from.eq_any(allowed));
+		}
+		if !filter.denied.is_empty() {
+			let denied: Vec<Hash160> = filter.denied.iter().map(|a| (*a).into()).collect();
+			query = query.filter(transactions::from.ne_all(denied));
+		}
+
+		let count: i64 = query.first(conn)?;
+
+		Ok(count as u64)
+	}
+
+	/// Return at most `limit` distinct issuers kept by `filter` strictly above `after`, skipping
+	/// the first `offset`, ordered by address
+	///
+	/// Walk every issuer in bounded chunks by passing the last address of a chunk as `after` to
+	/// get the next one. Start with `after` set to `None`.
+	pub fn issuers_after(
+		conn: &DbConnection,
+		filter: &IssuerFilter,
+		after: Option<H160>,
+		offset: u64,
+		limit: u32,
+	) -> QueryResult<Vec<H160>> {
+		let mut query = dsl_transactions
+			.select(transactions::from)
+			.filter(transactions::from.is_not_null())
+			.distinct()
+			.order(transactions::from.asc())
+			.offset(offset as i64)
+			.limit(limit as i64)
+			.into_boxed();
+		if let Some(after) = after {
+			let after: Hash160 = after.into();
+			query = query.filter(transactions::from.gt(after));
+		}
+		if let Some(allowed) = &filter.allowed {
+			let allowed: Vec<Hash160> = allowed.iter().map(|a| (*a).into()).collect();
+			query = query.filter(transactions::from.eq_any(allowed));
+		}
+		if !filter.denied.is_empty() {
+			let denied: Vec<Hash160> = filter.denied.iter().map(|a| (*a).into()).collect();
+			query = query.filter(transactions::from.ne_all(denied));
+		}
+
+		let db_hashs: Vec<Option<Hash160>> = query.load(conn)?;
+
+		// Safe to use `unwrap_unchecked` because we filtered NOT NULL in the query
+		let addresses =
+			db_hashs.into_iter().map(|h| unsafe { h.unwrap_unchecked().into() }).collect();
+
+		Ok(addresses)
+	}
+
+	/// Return at most `limit` distinct issuers whose address starts with `prefix`, ordered by
+	/// address
+	///
+	/// `prefix` is made of at most 40 hex digits, without '0x'. Nothing is returned otherwise.
+	/// The prefix is turned into a range of addresses, so the lookup can use an index on `from`.
+	pub fn issuers_with_prefix(
+		conn: &DbConnection,
+		prefix: &str,
+		limit: u32,
+	) -> QueryResult<Vec<H160>> {
+		if prefix.len() > 40 {
+			return Ok(vec![])
+		}
+		let padding = 40 - prefix.len();
+		let bounds = (
+			H160::from_str(&format!("{prefix}{}", "0".repeat(padding))),
+			H160::from_str(&format!("{prefix}{}", "f".repeat(padding))),
+		);
+		let (lowest, highest): (Hash160, Hash160) = match bounds {
+			(Ok(lowest), Ok(highest)) => (lowest.into(), highest.into()),
+			_ => return Ok(vec![]),
+		};
+
+		let db_hashs: Vec<Option<Hash160>> = dsl_transactions
+			.select(transactions::from)
+			.filter(transactions::from.between(lowest, highest))
+			.distinct()
+			.order(transactions::from.asc())
+			.limit(limit as i64)
+			.load(conn)?;
+
+		// Safe to use `unwrap_unchecked` because `between` never matches NULL
+		let addresses =
+			db_hashs.into_iter().map(|h| unsafe { h.unwrap_unchecked().into() }).collect();
+
+		Ok(addresses)
+	}
+
+	/// Return the number of contracts deployed by `address`
+	///
+	/// A contract deployment is a transaction without recipient.
+	/// The count can be served directly by the partial index:
+	/// `CREATE INDEX transactions_deployments_idx ON transactions ("from") WHERE "to" IS NULL;`
+	/// Transactions in blocks above `finalized_below_height` are ignored, if set.
+	pub fn count_deployed_contracts_from_address(
+		conn: &DbConnection,
+		address: H160,
+		finalized_below_height: Option<u64>,
+	) -> QueryResult<u64> {
+		let address: Hash160 = address.into();
+
+		let mut query = dsl_transactions
+			.filter(transactions::from.eq(address))
+			.filter(transactions::to.is_null())
+			.into_boxed();
+		if let Some(height) = finalized_below_height {
+			query = query.filter(transactions::block_number.le(height as i64));
+		}
+
+		let count: i64 = query.count().get_result(conn)?;
+
+		Ok(count as u64)
+	}
+
+	/// Return the number of contract deployments stored, whoever issued them
+	///
+	/// Served by the `"to"` index.
+	pub fn total_deployed_contracts(conn: &DbConnection) -> QueryResult<u64> {
+		let count: i64 =
+			dsl_transactions.filter(transactions::to.is_null()).count().get_result(conn)?;
+
+		Ok(count as u64)
+	}
+
+	/// Return the addresses of the contracts deployed by `address`
+	///
+	/// Only deployments whose receipt was indexed are returned.
+	pub fn deployed_contracts_from_address(
+		conn: &DbConnection,
+		address: H160,
+	) -> QueryResult<Vec<H160>> {
+		let address: Hash160 = address.into();
+
+		let db_addresses: Vec<Option<Hash160>> = dsl_transactions
+			.select(transactions::contract_address)
+			.filter(transactions::from.eq(address))
+			.filter(transactions::contract_address.is_not_null())
+			.load(conn)?;
+
+		// Safe to use `unwrap_unchecked` because we filtered NOT NULL in the query
+		let addresses = db_addresses
+			.into_iter()
+			.map(|a| unsafe { a.unwrap_unchecked().into() })
+			.collect();
+
+		Ok(addresses)
+	}
+
+	/// Return the number of transactions issued by `address`, counting no further than `cap`
+	///
+	/// At most `cap` rows are read, however active the address.
+	/// Transactions in blocks above `finalized_below_height` are ignored, if set.
+	pub fn count_from_address_capped(
+		conn: &DbConnection,
+		address: H160,
+		cap: u64,
+		finalized_below_height: Option<u64>,
+	) -> QueryResult<u64> {
+		let address: Hash160 = address.into();
+
+		let mut query = dsl_transactions
+			.select(transactions::hash)
+			.filter(transactions::from.eq(address))
+			.limit(cap as i64)
+			.into_boxed();
+		if let Some(height) = finalized_below_height {
+			query = query.filter(transactions::block_number.le(height as i64));
+		}
+
+		let hashes: Vec<Hash256> = query.load(conn)?;
+
+		Ok(hashes.len() as u64)
+	}
+
+	/// Return the number of transactions issued by `address` for every EIP-2718 type, by type
+	///
+	/// Types are the raw values reported by the node, future ones included. Transactions whose
+	/// type is unknown are counted under `None`.
+	pub fn count_by_type_from_address(
+		conn: &DbConnection,
+		address: H160,
+	) -> QueryResult<Vec<(Option<u64>, u64)>> {
+		let address: Hash160 = address.into();
+
+		let counts: Vec<(Option<i64>, i64)> = dsl_transactions
+			.select((transactions::tx_type, sql::<BigInt>("COUNT(*)")))
+			.filter(transactions::from.eq(address))
+			.group_by(transactions::tx_type)
+			.order(transactions::tx_type.asc())
+			.load(conn)?;
+
+		Ok(counts.into_iter().map(|(t, c)| (t.map(|t| t as u64), c as u64)).collect())
+	}
+
+	/// Return true if `address` issued a transaction of EIP-2718 type `tx_type`
+	///
+	/// Stops at the first one found.
+	/// Transactions in blocks above `finalized_below_height` are ignored, if set.
+	pub fn has_type_from_address(
+		conn: &DbConnection,
+		address: H160,
+		tx_type: u64,
+		finalized_below_height: Option<u64>,
+	) -> QueryResult<bool> {
+		let address: Hash160 = address.into();
+
+		let mut query = dsl_transactions
+			.select(transactions::hash)
+			.filter(transactions::from.eq(address))
+			.filter(transactions::tx_type.eq(tx_type as i64))
+			.into_boxed();
+		if let Some(height) = finalized_below_height {
+			query = query.filter(transactions::block_number.le(height as i64));
+		}
+
+		let hash: Option<Hash256> = query.first(conn).optional()?;
+
+		Ok(hash.is_some())
+	}
+
+	/// Return true if `address` called the contract `target`
+	///
+	/// A contract call is a transaction with an input. Stops at the first one found.
+	/// Transactions in blocks above `finalized_below_height` are ignored, if set.
+	pub fn has_call_to(
+		conn: &DbConnection,
+		address: H160,
+		target: H160,
+		finalized_below_height: Option<u64>,
+	) -> QueryResult<bool> {
+		let address: Hash160 = address.into();
+		let target: Hash160 = target.into();
+
+		let mut query = dsl_transactions
+			.select(transactions::hash)
+			.filter(transactions::from.eq(address))
+			.filter(transactions::to.eq(target))
+			.filter(transactions::input.ne(Vec::<u8>::new()))
+			.into_boxed();
+		if let Some(height) = finalized_below_height {
+			query = query.filter(transactions::block_number.le(height as i64));
+		}
+
+		let hash: Option<Hash256> = query.first(conn).optional()?;
+
+		Ok(hash.is_some())
+	}
+
+	/// Return the number of contracts `address` called at least `min_calls` times, counting no
+	/// further than `cap`
+	///
+	/// A contract call is a transaction with an input.
+	/// Transactions in blocks above `finalized_below_height` are ignored, if set.
+	pub fn count_contracts_called_from_address(
+		conn: &DbConnection,
+		address: H160,
+		min_calls: u64,
+		cap: u64,
+		finalized_below_height: Option<u64>,
+	) -> QueryResult<u64> {
+		let address: Hash160 = address.into();
+
+		let count: DbCount = sql_query(
+			r#"SELECT COUNT(*) AS count FROM (
+				SELECT "to" FROM transactions
+				WHERE "from" = $1
+					AND "to" IS NOT NULL
+					AND LENGTH(input) > 0
+					AND ($2::BIGINT IS NULL OR block_number <= $2)
+				GROUP BY "to"
+				HAVING COUNT(*) >= $3
+				LIMIT $4
+			) AS called"#,
+		)
+		.bind::<Binary, _>(address)
+		.bind::<Nullable<BigInt>, _>(finalized_below_height.map(|h| h as i64))
+		.bind::<BigInt, _>(min_calls as i64)
+		.bind::<BigInt, _>(cap as i64)
+		.get_result(conn)?;
+
+		Ok(count.count as u64)
+	}
+
+	/// Return the aggregates of the transactions issued by each of `addresses`, in a single query
+	///
+	/// Batch counterpart of `count_from_address_capped`, without cap,
+	/// `distinct_active_days_from_address`, `has_type_from_address` for `tx_type`, `has_call_to`
+	/// for every one of `targets`, `max_nonce_from_address` and
+	/// `count_deployed_contracts_from_address`.
+	/// Addresses without transactions are left out.
+	/// Transactions in blocks above `finalized_below_height` are ignored, if set.
+	pub fn activity_for_addresses(
+		conn: &DbConnection,
+		addresses: &[H160],
+		tx_type: u64,
+		targets: &[H160],
+		finalized_below_height: Option<u64>,
+	) -> QueryResult<HashMap<H160, AddressActivity>> {
+		let addresses: Vec<Hash160> = addresses.iter().map(|a| (*a).into()).collect();
+		let targets: Vec<Hash160> = targets.iter().map(|a| (*a).into()).collect();
+
+		let rows: Vec<DbAddressActivity> = sql_query(
+			r#"SELECT t."from" AS address,
+				COUNT(*) AS transactions_count,
+				COUNT(DISTINCT b.timestamp / 86400) AS active_days,
+				COALESCE(BOOL_OR(t.tx_type = $3), FALSE) AS used_type,
+				COALESCE(BOOL_OR(t."to" = ANY($4) AND LENGTH(t.input) > 0), FALSE)
+					AS used_target_contract,
+				MAX(t.nonce) AS max_nonce,
+				COUNT(*) FILTER (WHERE t."to" IS NULL) AS deployed_contracts
+			FROM transactions t
+			LEFT JOIN execution_blocks b ON b.hash = t.block_hash
+			WHERE t."from" = ANY($1)
+				AND ($2::BIGINT IS NULL OR t.block_number <= $2)
+			GROUP BY t."from""#,
+		)
+		.bind::<Array<Binary>, _>(addresses)
+		.bind::<Nullable<BigInt>, _>(finalized_below_height.map(|h| h as i64))
+		.bind::<BigInt, _>(tx_type as i64)
+		.bind::<Array<Binary>, _>(targets)
+		.load(conn)?;
+
+		Ok(rows
+			.into_iter()
+			.map(|row| {
+				(
+					row.address.into(),
+					AddressActivity {
+						transactions_count: row.transactions_count as u64,
+						active_days: row.active_days,
+						used_type: row.used_type,
+						used_target_contract: row.used_target_contract,
+						max_nonce: row.max_nonce.map(|n| n as u64),
+						deployed_contracts: row.deployed_contracts as u64,
+					},
+				)
+			})
+			.collect())
+	}
+
+	/// Return the number of contracts each of `addresses` called at least `min_calls` times, in a
+	/// single query
+	///
+	/// Batch counterpart of `count_contracts_called_from_address`, without cap.
+	/// Addresses which called no contract that many times are left out.
+	/// Transactions in blocks above `finalized_below_height` are ignored, if set.
+	pub fn count_contracts_called_for_addresses(
+		conn: &DbConnection,
+		addresses: &[H160],
+		min_calls: u64,
+		finalized_below_height: Option<u64>,
+	) -> QueryResult<HashMap<H160, u64>> {
+		let addresses: Vec<Hash160> = addresses.iter().map(|a| (*a).into()).collect();
+
+		let rows: Vec<DbAddressCount> = sql_query(
+			r#"SELECT "from" AS address, COUNT(*) AS count FROM (
+				SELECT "from" FROM transactions
+				WHERE "from" = ANY($1)
+					AND "to" IS NOT NULL
+					AND LENGTH(input) > 0
+					AND ($2::BIGINT IS NULL OR block_number <= $2)
+				GROUP BY "from", "to"
+				HAVING COUNT(*) >= $3
+			) AS called
+			GROUP BY "from""#,
+		)
+		.bind::<Array<Binary>, _>(addresses)
+		.bind::<Nullable<BigInt>, _>(finalized_below_height.map(|h| h as i64))
+		.bind::<BigInt, _>(min_calls as i64)
+		.load(conn)?;
+
+		Ok(rows.into_iter().map(|row| (row.address.into(), row.count as u64)).collect())
+	}
+
+	/// Return the number of the highest block including a transaction issued by `address`
+	///
+	/// Return `None` if the address has no transaction in db.
+	/// Transactions in blocks above `finalized_below_height` are ignored, if set.
+	pub fn last_block_number_from_address(
+		conn: &DbConnection,
+		address: H160,
+		finalized_below_height: Option<u64>,
+	) -> QueryResult<Option<u64>> {
+		let address: Hash160 = address.into();
+
+		let mut query = dsl_transactions
+			.select(max(transactions::block_number))
+			.filter(transactions::from.eq(address))
+			.into_boxed();
+		if let Some(height) = finalized_below_height {
+			query = query.filter(transactions::block_number.le(height as i64));
+		}
+
+		let number: Option<i64> = query.first(conn)?;
+
+		Ok(number.map(|n| n as u64))
+	}
+
+	/// Return the highest nonce used by `address`
+	///
+	/// The nonce is the chain's own count of transactions sent by an address, so it remains
+	/// accurate even if some of them were not indexed.
+	/// Return `None` if the address has no transaction in db.
+	/// Transactions in blocks above `finalized_below_height` are ignored, if set.
+	pub fn max_nonce_from_address(
+		conn: &DbConnection,
+		address: H160,
+		finalized_below_height: Option<u64>,
+	) -> QueryResult<Option<u64>> {
+		let address: Hash160 = address.into();
+
+		let mut query = dsl_transactions
+			.select(max(transactions::nonce))
+			.filter(transactions::from.eq(address))
+			.into_boxed();
+		if let Some(height) = finalized_below_height {
+			query = query.filter(transactions::block_number.le(height as i64));
+		}
+
+		let max_nonce: Option<i64> = query.first(conn)?;
+
+		Ok(max_nonce.map(|n| n as u64))
+	}
+
+	/// Return the number of distinct days on which `address` issued a transaction
+	///
+	/// Days are UTC days, bucketed from the timestamp of the block including the transaction.
+	/// Transactions in blocks above `finalized_below_height` are ignored, if set.
+	pub fn distinct_active_days_from_address(
+		conn: &DbConnection,
+		address: H160,
+		finalized_below_height: Option<u64>,
+	) -> QueryResult<i64> {
+		let address: Hash160 = address.into();
+
+		let mut query = dsl_transactions
+			.inner_join(execution_blocks::table)
+			.filter(transactions::from.eq(address))
+			.select(sql::<BigInt>(
+				"COUNT(DISTINCT execution_blocks.timestamp / 86400)",
+			))
+			.into_boxed();
+		if let Some(height) = finalized_below_height {
+			query = query.filter(transactions::block_number.le(height as i64));
+		}
+
+		query.first(conn)
+	}
+
+	/// Return the gas used by the transactions of the slot at `height`
+	///
+	/// The total is the one reported by the slot's execution block, which is the sum of the gas
+	/// used by each of its transactions. Missed slots and slots without transactions used none.
+	/// Return `None` if the slot, or its block gas usage, is not stored.
+	pub fn gas_used_at_height(conn: &DbConnection, height: u64) -> QueryResult<Option<u64>> {
+		let slot: Option<(bool, Option<Hash256>)> = slots::table
+			.find(height as i64)
+			.select((slots::missed, slots::block_hash))
+			.first(conn)
+			.optional()?;
+		let block_hash = match slot {
+			None => return Ok(None),
+			Some((true, _)) | Some((false, None)) => return Ok(Some(0)),
+			Some((false, Some(h))) => h,
+		};
+
+		let gas_used: Option<Option<i64>> = execution_blocks::table
+			.find(block_hash)
+			.select(execution_blocks::gas_used)
+			.first(conn)
+			.optional()?;
+
+		Ok(gas_used.flatten().map(|g| g as u64))
+	}
+
+	/// Return the number of transactions issued by `address` in every range of `bucket_size`
+	/// blocks, by ascending block number
+	///
+	/// Ranges without transactions are omitted. At most `max_buckets` ranges are returned.
+	pub fn activity_histogram_from_address(
+		conn: &DbConnection,
+		address: H160,
+		bucket_size: u64,
+		max_buckets: u32,
+	) -> QueryResult<Vec<ActivityBucket>> {
+		let address: Hash160 = address.into();
+
+		let db_buckets: Vec<DbActivityBucket> = sql_query(
+			r#"SELECT execution_blocks.number / $1 * $1 AS bucket, COUNT(*) AS count
+			FROM transactions
+			INNER JOIN execution_blocks ON execution_blocks.hash = transactions.block_hash
+			WHERE transactions."from" = $2
+			GROUP BY bucket
+			ORDER BY bucket
+			LIMIT $3"#,
+		)
+		.bind::<BigInt, _>(bucket_size as i64)
+		.bind::<Binary, _>(address)
+		.bind::<BigInt, _>(max_buckets as i64)
+		.load(conn)?;
+
+		let buckets = db_buckets
+			.into_iter()
+			.map(|b| ActivityBucket {
+				bucket: b.bucket as u64,
+				count: b.count as u64,
+			})
+			.collect();
+
+		Ok(buckets)
+	}
+
+	/// Return the hash of the transaction
+	pub fn hash(&self) -> H256 {
+		self.hash
+	}
+
 	/// Return the address of the transaction recipient
 	pub fn to(&self) -> Option<H160> {
 		self.to
 	}
 
+	/// Return the number of the block including the transaction
+	pub fn block_number(&self) -> u64 {
+		self.block_number
+	}
+
+	/// Return the address of the contract created, if a deployment
+	pub fn contract_address(&self) -> Option<H160> {
+		self.contract_address
+	}
+
 	/// Return the transaction input
 	pub fn input(&self) -> Vec<u8> {
 		self.input.clone()
 	}
+
+	/// Return the value transferred, in wei
+	pub fn value(&self) -> U256 {
+		self.value
+	}
+
+	/// Return whether the transaction succeeded, if its receipt was fetched
+	pub fn status(&self) -> Option<bool> {
+		self.status
+	}
+
+	/// Return the EIP-2718 type of the transaction, if known
+	pub fn tx_type(&self) -> Option<u64> {
+		self.tx_type
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use diesel::Connection;
+
+	use super::*;
+	use crate::{
+		models::{NewExecBlock, NewStat, NewTransaction, NewTransactions, Stat, StatKey},
+		test_connection,
+	};
+
+	/// Leading byte of the hashes and addresses of this test, to tell them apart from real ones
+	const TAG: u8 = 0xb6;
+
+	/// First block number of this test, far above the Kiln head
+	const FIRST_BLOCK: u64 = 3_100_000_000;
+
+	fn tagged_address(index: u64) -> H160 {
+		let mut address = H160::from_low_u64_be(index);
+		address.0[0] = TAG;
+		address
+	}
+
+	fn tagged_hash(kind: u8, index: u64) -> H256 {
+		let mut hash = H256::from_low_u64_be(index);
+		hash.0[0] = TAG;
+		hash.0[1] = kind;
+		hash
+	}
+
+	fn insert_block(conn: &DbConnection, index: u64) {
+		NewExecBlock::new(
+			tagged_hash(1, index),
+			FIRST_BLOCK + index,
+			tagged_hash(1, index.saturating_sub(1)),
+			H256::zero(),
+			H256::zero(),
+			H256::zero(),
+			0,
+			0,
+		)
+		.insert(conn)
+		.unwrap();
+	}
+
+	// Transactions `(index, sender)` of the block `block`, senders and hashes being tagged
+	fn transactions(block: u64, senders: &[(u64, Option<u64>)]) -> NewTransactions {
+		NewTransactions::new(
+			senders
+				.iter()
+				.enumerate()
+				.map(|(position, (index, sender))| {
+					NewTransaction::new(
+						tagged_hash(2, *index),
+						tagged_hash(1, block),
+						position as u64,
+						sender.map(tagged_address),
+						None,
+						vec![],
+						U256::zero(),
+						0,
+						FIRST_BLOCK + block,
+						Some(2),
+					)
+				})
+				.collect(),
+		)
+	}
+
+	fn assert_count_is_exact(conn: &DbConnection, step: &str) {
+		assert_eq!(
+			Stat::get(conn, StatKey::IssuersCount).unwrap(),
+			Some(Transaction::count_distinct_issuers(conn).unwrap()),
+			"{step}"
+		);
+	}
+
+	#[test]
+	fn maintained_count_matches_distinct_issuers() {
+		let conn = match test_connection() {
+			Some(conn) => conn,
+			None => return,
+		};
+		conn.begin_test_transaction().unwrap();
+
+		NewStat::recount_issuers(&conn).unwrap();
+		insert_block(&conn, 1);
+		insert_block(&conn, 2);
+
+		transactions(1, &[(1, Some(1)), (2, Some(2)), (3, Some(2)), (4, None)])
+			.batch_insert_new_counted(&conn)
+			.unwrap();
+		assert_count_is_exact(&conn, "new issuers");
+
+		transactions(2, &[(5, Some(2)), (6, Some(3))])
+			.batch_insert_new_counted(&conn)
+			.unwrap();
+		assert_count_is_exact(&conn, "overlapping issuers");
+
+		let written = transactions(1, &[(1, Some(1)), (2, Some(2)), (7, Some(4))])
+			.batch_insert_new_counted(&conn)
+			.unwrap();
+		assert_eq!(written, 1);
+		assert_count_is_exact(&conn, "duplicated transactions");
+
+		// Block 2 fetched again, its second transaction issued by another sender: 3 is gone
+		transactions(2, &[(5, Some(2)), (6, Some(5))])
+			.batch_upsert_counted(&conn)
+			.unwrap();
+		assert_count_is_exact(&conn, "re-inserted block");
+		assert!(Transaction::known_issuers(&conn, &[tagged_address(3)]).unwrap().is_empty());
+
+		// And again, by an issuer already known
+		transactions(2, &[(5, Some(2)), (6, Some(1))])
+			.batch_upsert_counted(&conn)
+			.unwrap();
+		assert_count_is_exact(&conn, "re-inserted block, known issuer");
+	}
 }
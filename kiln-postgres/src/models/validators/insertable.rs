@@ -1,12 +1,11 @@
-use diesel::{
-	pg::upsert::excluded, ExpressionMethods, Insertable, PgConnection, QueryResult, RunQueryDsl,
-};
+use diesel::{pg::upsert::excluded, ExpressionMethods, Insertable, QueryResult, RunQueryDsl};
 use eth2::types::ValidatorData;
 use primitive_types::H256;
 
 use crate::{
 	models::Hash256,
 	schema::{validators, validators::dsl::validators as dsl_validators},
+	DbConnection,
 };
 
 #[derive(Insertable)]
@@ -46,7 +45,7 @@ impl From<ValidatorData> for NewValidator {
 
 impl NewValidator {
 	pub fn set_deposit_transaction(
-		conn: &PgConnection,
+		conn: &DbConnection,
 		pubkey: String,
 		transaction: H256,
 	) -> QueryResult<usize> {
@@ -57,6 +56,19 @@ impl NewValidator {
 			.set(validators::deposit_transaction.eq(transaction))
 			.execute(conn)
 	}
+
+	/// Mark the validators `indexes` as slashed
+	///
+	/// Return the number of validators that were not marked as slashed yet
+	pub fn set_slashed(conn: &DbConnection, indexes: &[u64]) -> QueryResult<usize> {
+		let indexes: Vec<i64> = indexes.iter().map(|i| *i as i64).collect();
+
+		diesel::update(dsl_validators)
+			.filter(validators::index.eq_any(indexes))
+			.filter(validators::slashed.eq(false))
+			.set(validators::slashed.eq(true))
+			.execute(conn)
+	}
 }
 
 /// An wrapper around an array fo validators
@@ -67,7 +79,7 @@ impl NewValidators {
 	///
 	/// # Updated fields
 	/// `balance`, `status`, `withdrawal_credentials`, `effective_balance`, `slashed`
-	pub fn batch_upsert(&self, conn: &PgConnection) -> QueryResult<()> {
+	pub fn batch_upsert(&self, conn: &DbConnection) -> QueryResult<()> {
 		for chunk in self.0.chunks(1000) {
 			diesel::insert_into(validators::table)
 				.values(chunk)
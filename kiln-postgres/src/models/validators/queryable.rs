@@ -1,13 +1,16 @@
-use diesel::{ExpressionMethods, Identifiable, PgConnection, QueryDsl, QueryResult, RunQueryDsl};
+use std::collections::HashMap;
+
+use diesel::{ExpressionMethods, Identifiable, QueryDsl, QueryResult, RunQueryDsl};
 use primitive_types::{H160, H256};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-	models::{Hash160, Hash256},
+	models::{Hash160, Hash256, SlashingEvent},
 	schema::{
 		transactions, transactions::dsl::transactions as dsl_transactions, validators,
 		validators::dsl::validators as dsl_validators,
 	},
+	DbConnection,
 };
 
 #[derive(Queryable, Identifiable)]
@@ -64,7 +67,22 @@ impl From<DbValidator> for Validator {
 }
 
 impl Validator {
-	pub fn is_validator_slashed(conn: &PgConnection, address: H160) -> QueryResult<Option<bool>> {
+	/// Return the number of slashed validators
+	pub fn count_slashed(conn: &DbConnection) -> QueryResult<u64> {
+		let count: i64 =
+			dsl_validators.filter(validators::slashed.eq(true)).count().get_result(conn)?;
+
+		Ok(count as u64)
+	}
+
+	/// Return the slashings of the validators deposited by `address`, by ascending slot
+	///
+	/// A validator with a slashing event is always reported slashed by `is_validator_slashed`.
+	pub fn slashing_events(conn: &DbConnection, address: H160) -> QueryResult<Vec<SlashingEvent>> {
+		SlashingEvent::list_from_depositor(conn, address)
+	}
+
+	pub fn is_validator_slashed(conn: &DbConnection, address: H160) -> QueryResult<Option<bool>> {
 		let address: Hash160 = address.into();
 
 		let status: Vec<bool> = dsl_validators
@@ -80,4 +98,31 @@ impl Validator {
 			status.get(0).copied()
 		})
 	}
+
+	/// Return whether one of the validators deposited by each of `addresses` is slashed, in a
+	/// single query
+	///
+	/// Addresses which deposited no validator are left out.
+	pub fn slashed_status_for_addresses(
+		conn: &DbConnection,
+		addresses: &[H160],
+	) -> QueryResult<HashMap<H160, bool>> {
+		let addresses: Vec<Hash160> = addresses.iter().map(|a| (*a).into()).collect();
+
+		let rows: Vec<(Option<Hash160>, bool)> = dsl_validators
+			.filter(validators::deposit_transaction.is_not_null())
+			.inner_join(dsl_transactions)
+			.filter(transactions::from.eq_any(addresses))
+			.select((transactions::from, validators::slashed))
+			.load(conn)?;
+
+		let mut statuses = HashMap::new();
+		for (address, slashed) in rows {
+			// Safe to use `unwrap_unchecked` because `from` matched one of the addresses
+			let address: H160 = unsafe { address.unwrap_unchecked() }.into();
+			*statuses.entry(address).or_insert(false) |= slashed;
+		}
+
+		Ok(statuses)
+	}
 }
@@ -0,0 +1,96 @@
+use diesel::{
+	dsl::{min, not},
+	Connection, ExpressionMethods, NullableExpressionMethods, QueryDsl, QueryResult, RunQueryDsl,
+};
+
+use crate::{
+	models::ReprocessLayer,
+	schema::{
+		execution_blocks, internal_calls, raw_payloads, slots, slots_history, transactions,
+		validators,
+	},
+	DbConnection,
+};
+
+/// Number of rows deleted by `prune_below`, by kind
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PrunedRows {
+	pub slots: usize,
+	pub execution_blocks: usize,
+	pub transactions: usize,
+	pub internal_calls: usize,
+}
+
+/// Delete the slots below `height`, along with the execution data of their blocks
+///
+/// The execution cutoff is the lowest block of the slots kept, so nothing is pruned from the
+/// execution layer while no slot above `height` holds a block. The history and raw payloads of
+/// the pruned heights go with them.
+///
+/// Deposit transactions are kept, with their blocks, as they link the validators to their
+/// addresses. Slashing events are kept too, the validators stay slashed.
+pub fn prune_below(conn: &DbConnection, height: u64) -> QueryResult<PrunedRows> {
+	conn.transaction(|| {
+		let height = height as i64;
+		let mut pruned = PrunedRows::default();
+
+		let block_cutoff: Option<i64> = slots::table
+			.select(min(slots::block_number))
+			.filter(slots::height.ge(height))
+			.first(conn)?;
+
+		pruned.slots =
+			diesel::delete(slots::table.filter(slots::height.lt(height))).execute(conn)?;
+		diesel::delete(slots_history::table.filter(slots_history::height.lt(height)))
+			.execute(conn)?;
+		delete_raw_payloads(conn, ReprocessLayer::Consensus, height)?;
+
+		let block_cutoff = match block_cutoff {
+			Some(c) => c,
+			None => return Ok(pruned),
+		};
+
+		pruned.internal_calls = diesel::delete(
+			internal_calls::table.filter(internal_calls::block_number.lt(block_cutoff)),
+		)
+		.execute(conn)?;
+
+		let deposits = validators::table
+			.select(validators::deposit_transaction)
+			.filter(validators::deposit_transaction.is_not_null());
+		pruned.transactions = diesel::delete(
+			transactions::table
+				.filter(transactions::block_number.lt(block_cutoff))
+				.filter(not(transactions::hash.nullable().eq_any(deposits))),
+		)
+		.execute(conn)?;
+
+		// Only the deposits are left below the cutoff
+		let kept_blocks = transactions::table
+			.select(transactions::block_hash)
+			.filter(transactions::block_number.lt(block_cutoff));
+		pruned.execution_blocks = diesel::delete(
+			execution_blocks::table
+				.filter(execution_blocks::number.lt(block_cutoff))
+				.filter(not(execution_blocks::hash.eq_any(kept_blocks))),
+		)
+		.execute(conn)?;
+		delete_raw_payloads(conn, ReprocessLayer::Execution, block_cutoff)?;
+
+		Ok(pruned)
+	})
+}
+
+// Delete the raw payloads stored for the `layer` entries below `height`
+fn delete_raw_payloads(
+	conn: &DbConnection,
+	layer: ReprocessLayer,
+	height: i64,
+) -> QueryResult<usize> {
+	diesel::delete(
+		raw_payloads::table
+			.filter(raw_payloads::layer.eq(layer.to_string()))
+			.filter(raw_payloads::height.lt(height)),
+	)
+	.execute(conn)
+}
@@ -6,6 +6,79 @@ table! {
 		state_root -> Bytea,
 		transactions_root -> Bytea,
 		receipts_root -> Bytea,
+		timestamp -> Nullable<Int8>,
+		gas_used -> Nullable<Int8>,
+	}
+}
+
+table! {
+	internal_calls (transaction_hash, trace_address) {
+		transaction_hash -> Bytea,
+		trace_address -> Varchar,
+		origin -> Bytea,
+		from -> Bytea,
+		to -> Bytea,
+		block_number -> Int8,
+	}
+}
+
+table! {
+	minted_nfts (address) {
+		address -> Bytea,
+		nft -> Bytea,
+	}
+}
+
+table! {
+	nft_merkle_leaves (tree_id, address) {
+		tree_id -> Int4,
+		address -> Bytea,
+		nft -> Bytea,
+		leaf_index -> Int8,
+		proof -> Bytea,
+	}
+}
+
+table! {
+	nft_merkle_trees (id) {
+		id -> Int4,
+		root -> Bytea,
+		leaves_count -> Int8,
+	}
+}
+
+table! {
+	packed_nfts (address) {
+		address -> Bytea,
+		nft -> Bytea,
+		computed_below_height -> Nullable<Int8>,
+	}
+}
+
+table! {
+	raw_payloads (layer, height) {
+		layer -> Varchar,
+		height -> Int8,
+		payload -> Text,
+	}
+}
+
+table! {
+	reprocess_jobs (id) {
+		id -> Int4,
+		layer -> Varchar,
+		from_height -> Int8,
+		to_height -> Int8,
+		processed_height -> Nullable<Int8>,
+		status -> Varchar,
+	}
+}
+
+table! {
+	slashing_events (slot, validator_index) {
+		slot -> Int8,
+		validator_index -> Int8,
+		kind -> Varchar,
 	}
 }
 
@@ -14,6 +87,36 @@ table! {
 		height -> Int8,
 		block_hash -> Nullable<Bytea>,
 		block_number -> Nullable<Int8>,
+		missed -> Bool,
+		proposer_index -> Nullable<Int8>,
+		validators_count -> Nullable<Int8>,
+		spec -> Nullable<Varchar>,
+		tx_count -> Nullable<Int8>,
+		timestamp -> Nullable<Int8>,
+	}
+}
+
+table! {
+	slots_history (id) {
+		id -> Int4,
+		height -> Int8,
+		block_hash -> Nullable<Bytea>,
+		block_number -> Nullable<Int8>,
+		missed -> Bool,
+		proposer_index -> Nullable<Int8>,
+		validators_count -> Nullable<Int8>,
+		spec -> Nullable<Varchar>,
+		reason -> Varchar,
+		superseded_at -> Int8,
+		tx_count -> Nullable<Int8>,
+		timestamp -> Nullable<Int8>,
+	}
+}
+
+table! {
+	stats (name) {
+		name -> Varchar,
+		value -> Int8,
 	}
 }
 
@@ -27,6 +130,10 @@ table! {
 		input -> Bytea,
 		value -> Bytea,
 		status -> Nullable<Bool>,
+		nonce -> Nullable<Int8>,
+		contract_address -> Nullable<Bytea>,
+		block_number -> Int8,
+		tx_type -> Nullable<Int8>,
 	}
 }
 
@@ -47,7 +154,25 @@ table! {
 	}
 }
 
+joinable!(nft_merkle_leaves -> nft_merkle_trees (tree_id));
+joinable!(slashing_events -> validators (validator_index));
+joinable!(slots -> validators (proposer_index));
 joinable!(transactions -> execution_blocks (block_hash));
 joinable!(validators -> transactions (deposit_transaction));
 
-allow_tables_to_appear_in_same_query!(execution_blocks, slots, transactions, validators,);
+allow_tables_to_appear_in_same_query!(
+	execution_blocks,
+	internal_calls,
+	minted_nfts,
+	nft_merkle_leaves,
+	nft_merkle_trees,
+	packed_nfts,
+	raw_payloads,
+	reprocess_jobs,
+	slashing_events,
+	slots,
+	slots_history,
+	stats,
+	transactions,
+	validators,
+);
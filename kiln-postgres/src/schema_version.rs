@@ -0,0 +1,68 @@
+use std::fmt::Display;
+
+use diesel::{
+	sql_query,
+	sql_types::{Nullable, Text},
+	RunQueryDsl,
+};
+
+use crate::DbConnection;
+
+/// Version of the last migration the queries of this crate rely on
+///
+/// Must be bumped along with every new migration, to the timestamp prefixing its directory.
+pub const SCHEMA_VERSION: &str = "20220507091530";
+
+#[derive(Debug)]
+pub enum SchemaError {
+	/// The applied migrations could not be read, usually because none were ever run
+	Unreadable(diesel::result::Error),
+	/// The last applied migration is older than the expected one
+	Outdated {
+		expected: &'static str,
+		found: Option<String>,
+	},
+}
+
+impl Display for SchemaError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Unreadable(error) => write!(
+				f,
+				"Cannot read the migrations applied to the database ({error}). Run `diesel migration run` from the kiln-postgres directory, with the same DATABASE_URL and DATABASE_SCHEMA"
+			),
+			Self::Outdated { expected, found } => write!(
+				f,
+				"The database schema is at migration {}, this binary expects {expected}. Run `diesel migration run` from the kiln-postgres directory of this release, with the same DATABASE_URL and DATABASE_SCHEMA",
+				found.as_deref().unwrap_or("none"),
+			),
+		}
+	}
+}
+
+#[derive(QueryableByName)]
+struct DbVersion {
+	#[sql_type = "Nullable<Text>"]
+	version: Option<String>,
+}
+
+/// Check the database has every migration up to `SCHEMA_VERSION` applied
+///
+/// Meant to be called on startup, before any other query: a missing column otherwise only shows
+/// up as a cryptic query error later on.
+/// A database migrated further is accepted, migrations only ever add to the schema.
+pub fn check_schema_version(conn: &DbConnection) -> Result<(), SchemaError> {
+	// Table maintained by the diesel cli, versions are fixed length timestamps
+	let found = sql_query("SELECT MAX(version) AS version FROM __diesel_schema_migrations")
+		.get_result::<DbVersion>(conn)
+		.map_err(SchemaError::Unreadable)?
+		.version;
+
+	match &found {
+		Some(version) if version.as_str() >= SCHEMA_VERSION => Ok(()),
+		_ => Err(SchemaError::Outdated {
+			expected: SCHEMA_VERSION,
+			found,
+		}),
+	}
+}
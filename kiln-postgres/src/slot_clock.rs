@@ -0,0 +1,109 @@
+/// Conversion between consensus slots and time
+///
+/// Assumes every slot lasts `seconds_per_slot` since genesis, which holds as long as no fork
+/// changes the slot duration: none did so far.
+/// Missed slots keep their time, they just hold no execution block. To go from a slot to an
+/// execution block, see `Slot::block_at_or_before_slot`.
+#[derive(Debug, Clone, Copy)]
+pub struct SlotClock {
+	/// Unix timestamp of slot 0, in seconds
+	genesis_time: u64,
+	seconds_per_slot: u64,
+}
+
+impl SlotClock {
+	/// Create a new clock
+	///
+	/// # Panics
+	/// If `seconds_per_slot` is 0
+	pub fn new(genesis_time: u64, seconds_per_slot: u64) -> Self {
+		assert!(seconds_per_slot > 0, "slots must last at least one second");
+
+		SlotClock {
+			genesis_time,
+			seconds_per_slot,
+		}
+	}
+
+	/// Return the unix timestamp at which `slot` starts, in seconds
+	///
+	/// Post-merge, it is the timestamp of the execution block proposed in the slot, if any.
+	pub fn slot_to_timestamp(&self, slot: u64) -> u64 {
+		self.genesis_time + slot * self.seconds_per_slot
+	}
+
+	/// Return the slot going on at `timestamp`, or None before genesis
+	pub fn slot_at_timestamp(&self, timestamp: u64) -> Option<u64> {
+		timestamp
+			.checked_sub(self.genesis_time)
+			.map(|elapsed| elapsed / self.seconds_per_slot)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Genesis time of Kiln
+	const GENESIS_TIME: u64 = 1647007500;
+	const SECONDS_PER_SLOT: u64 = 12;
+	/// First slot of Kiln holding an execution block
+	const MERGE_SLOT: u64 = 29151;
+	const SLOTS_PER_EPOCH: u64 = 32;
+
+	fn clock() -> SlotClock {
+		SlotClock::new(GENESIS_TIME, SECONDS_PER_SLOT)
+	}
+
+	#[test]
+	fn genesis_is_slot_zero() {
+		let clock = clock();
+
+		assert_eq!(clock.slot_to_timestamp(0), GENESIS_TIME);
+		assert_eq!(clock.slot_at_timestamp(GENESIS_TIME), Some(0));
+		assert_eq!(clock.slot_at_timestamp(GENESIS_TIME - 1), None);
+		assert_eq!(clock.slot_at_timestamp(0), None);
+	}
+
+	#[test]
+	fn slots_lasting_the_same_across_the_merge() {
+		let clock = clock();
+		let merge_time = clock.slot_to_timestamp(MERGE_SLOT);
+
+		assert_eq!(merge_time, GENESIS_TIME + MERGE_SLOT * SECONDS_PER_SLOT);
+		assert_eq!(
+			merge_time - clock.slot_to_timestamp(MERGE_SLOT - 1),
+			SECONDS_PER_SLOT
+		);
+		assert_eq!(
+			clock.slot_at_timestamp(merge_time - 1),
+			Some(MERGE_SLOT - 1)
+		);
+		assert_eq!(clock.slot_at_timestamp(merge_time), Some(MERGE_SLOT));
+		assert_eq!(
+			clock.slot_at_timestamp(merge_time + SECONDS_PER_SLOT - 1),
+			Some(MERGE_SLOT)
+		);
+	}
+
+	#[test]
+	fn slots_round_trip_across_epoch_boundaries() {
+		let clock = clock();
+
+		for epoch in [0, 1, MERGE_SLOT / SLOTS_PER_EPOCH] {
+			let first = epoch * SLOTS_PER_EPOCH;
+			for slot in first.saturating_sub(1)..=first + 1 {
+				assert_eq!(
+					clock.slot_at_timestamp(clock.slot_to_timestamp(slot)),
+					Some(slot)
+				);
+			}
+		}
+	}
+
+	#[test]
+	#[should_panic]
+	fn slots_cannot_last_zero_seconds() {
+		SlotClock::new(GENESIS_TIME, 0);
+	}
+}
@@ -0,0 +1,27 @@
+use std::env;
+
+use diesel::Connection;
+
+use crate::DbConnection;
+
+/// Return the url of the database to run the tests needing one against, from `DATABASE_URL`
+///
+/// Those tests are skipped when it is not set, unless on CI (`CI` set) where it must be: a
+/// database left out there would otherwise go unnoticed.
+/// The database must be migrated. Tests leave it as they found it.
+pub fn test_database_url() -> Option<String> {
+	match env::var("DATABASE_URL") {
+		Ok(url) => Some(url),
+		Err(_) if env::var_os("CI").is_some() => panic!("DATABASE_URL must be set on CI"),
+		Err(_) => {
+			eprintln!("DATABASE_URL not set, skipping a test needing a database");
+			None
+		},
+	}
+}
+
+/// Return a connection to the test database, see `test_database_url`
+pub fn test_connection() -> Option<DbConnection> {
+	test_database_url()
+		.map(|url| DbConnection::establish(&url).expect("failed to connect to the test database"))
+}
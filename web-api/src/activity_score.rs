@@ -0,0 +1,62 @@
+use crate::routes::ActivityMetrics;
+
+/// Points given by a full share of every signal, summing to 100
+///
+/// Tune them with the saturation points below, every signal gives its points in proportion of
+/// how close it is to its saturation point.
+const TRANSACTIONS_POINTS: u64 = 30;
+const DEPLOYED_CONTRACTS_POINTS: u64 = 20;
+const CALLED_CONTRACTS_POINTS: u64 = 20;
+const ACTIVE_DAYS_POINTS: u64 = 15;
+const NONCE_POINTS: u64 = 10;
+const VALIDATOR_POINTS: u64 = 5;
+
+/// Number of transactions giving all of `TRANSACTIONS_POINTS`
+///
+/// Must not exceed `TRANSACTIONS_COUNT_THRESHOLD`, transactions are counted up to it.
+const TRANSACTIONS_SATURATION: u64 = 100;
+const DEPLOYED_CONTRACTS_SATURATION: u64 = 10;
+/// Must not exceed `CALLED_CONTRACTS_THRESHOLD`, called contracts are counted up to it
+const CALLED_CONTRACTS_SATURATION: u64 = 10;
+const ACTIVE_DAYS_SATURATION: u64 = 7;
+const NONCE_SATURATION: u64 = 100;
+
+/// Return an activity score between 0 and 100 summing up `metrics`
+///
+/// An unslashed validator gives all of `VALIDATOR_POINTS`, a slashed one none.
+/// Fees are not part of it, the gas used by a transaction is not indexed.
+pub fn compute_activity_score(metrics: &ActivityMetrics) -> u8 {
+	let validator = match metrics.validator_slashed {
+		Some(false) => VALIDATOR_POINTS,
+		_ => 0,
+	};
+
+	let score = share(
+		TRANSACTIONS_POINTS,
+		metrics.transactions_count,
+		TRANSACTIONS_SATURATION,
+	) + share(
+		DEPLOYED_CONTRACTS_POINTS,
+		metrics.deployed_contracts,
+		DEPLOYED_CONTRACTS_SATURATION,
+	) + share(
+		CALLED_CONTRACTS_POINTS,
+		metrics.called_contracts,
+		CALLED_CONTRACTS_SATURATION,
+	) + share(
+		ACTIVE_DAYS_POINTS,
+		metrics.active_days.max(0) as u64,
+		ACTIVE_DAYS_SATURATION,
+	) + share(
+		NONCE_POINTS,
+		metrics.max_nonce.unwrap_or(0),
+		NONCE_SATURATION,
+	) + validator;
+
+	score as u8
+}
+
+// Return the share of `points` earned by `value`, all of them from `saturation`
+fn share(points: u64, value: u64, saturation: u64) -> u64 {
+	points * value.min(saturation) / saturation
+}
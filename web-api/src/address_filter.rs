@@ -0,0 +1,180 @@
+use std::{
+	collections::HashSet,
+	fs, io,
+	path::{Path, PathBuf},
+	sync::RwLock,
+	time::SystemTime,
+};
+
+use kiln_postgres::IssuerFilter;
+use log::{info, warn};
+use primitive_types::H160;
+use rocket::request::FromParam;
+
+use crate::{
+	config::Config,
+	params::{Hash160, HashParamError},
+};
+
+#[derive(Debug)]
+pub enum AddressListError {
+	Io(io::Error),
+	/// The line at `line` (1-indexed) is not a valid address
+	InvalidAddress {
+		line: usize,
+		error: HashParamError,
+	},
+}
+
+impl From<io::Error> for AddressListError {
+	fn from(error: io::Error) -> Self {
+		AddressListError::Io(error)
+	}
+}
+
+#[derive(Default)]
+struct Lists {
+	/// When set, only those addresses are eligible
+	allowlist: Option<HashSet<H160>>,
+	/// Those addresses are never eligible
+	denylist: HashSet<H160>,
+	allowlist_modified: Option<SystemTime>,
+	denylist_modified: Option<SystemTime>,
+}
+
+/// Addresses excluded from NFT computation
+///
+/// Lists are text files holding one '0x' prefixed address per line.
+/// Empty lines and lines starting with '#' are ignored.
+pub struct AddressFilter {
+	allowlist_path: Option<PathBuf>,
+	denylist_path: Option<PathBuf>,
+	lists: RwLock<Lists>,
+}
+
+impl AddressFilter {
+	/// Load the lists set in `config`
+	pub fn load(config: &Config) -> Result<Self, AddressListError> {
+		let filter = AddressFilter {
+			allowlist_path: config.address_allowlist.clone(),
+			denylist_path: config.address_denylist.clone(),
+			lists: RwLock::new(Lists::default()),
+		};
+		filter.reload()?;
+
+		Ok(filter)
+	}
+
+	/// Return true if at least one list is configured
+	pub fn is_configured(&self) -> bool {
+		self.allowlist_path.is_some() || self.denylist_path.is_some()
+	}
+
+	/// Read again the lists whose file changed since the last load
+	///
+	/// On error the previous lists are kept.
+	pub fn reload(&self) -> Result<(), AddressListError> {
+		let (allowlist_modified, denylist_modified) = {
+			let lists = self.lists.read().unwrap();
+			(lists.allowlist_modified, lists.denylist_modified)
+		};
+
+		let new_allowlist = match &self.allowlist_path {
+			Some(path) => read_if_modified(path, allowlist_modified)?,
+			None => None,
+		};
+		let new_denylist = match &self.denylist_path {
+			Some(path) => read_if_modified(path, denylist_modified)?,
+			None => None,
+		};
+
+		let mut lists = self.lists.write().unwrap();
+		if let Some((modified, allowlist)) = new_allowlist {
+			info!("loaded {} allowed addresses", allowlist.len());
+			lists.allowlist = Some(allowlist);
+			lists.allowlist_modified = Some(modified);
+		}
+		if let Some((modified, denylist)) = new_denylist {
+			info!("loaded {} denied addresses", denylist.len());
+			lists.denylist = denylist;
+			lists.denylist_modified = Some(modified);
+		}
+
+		Ok(())
+	}
+
+	/// Reload the lists every `interval`, forever
+	pub fn watch(&self, interval: std::time::Duration) {
+		loop {
+			std::thread::sleep(interval);
+			if let Err(err) = self.reload() {
+				warn!("failed to reload address lists: {:?}", err);
+			}
+		}
+	}
+
+	/// Return the issuers kept by the lists, to filter them in database
+	pub fn issuer_filter(&self) -> IssuerFilter {
+		let lists = self.lists.read().unwrap();
+
+		IssuerFilter {
+			allowed: lists.allowlist.as_ref().map(|a| a.iter().copied().collect()),
+			denied: lists.denylist.iter().copied().collect(),
+		}
+	}
+
+	/// Return false if `address` must be excluded from NFT computation
+	pub fn is_allowed(&self, address: &H160) -> bool {
+		let lists = self.lists.read().unwrap();
+
+		if lists.denylist.contains(address) {
+			info!("address {:?} filtered out: denied", address);
+			return false
+		}
+		if let Some(allowlist) = &lists.allowlist {
+			if !allowlist.contains(address) {
+				info!("address {:?} filtered out: not allowed", address);
+				return false
+			}
+		}
+
+		true
+	}
+}
+
+// Return the addresses in the file at `path` and its modification time,
+// or None if it was not modified since `last_modified`
+fn read_if_modified(
+	path: &Path,
+	last_modified: Option<SystemTime>,
+) -> Result<Option<(SystemTime, HashSet<H160>)>, AddressListError> {
+	let modified = fs::metadata(path)?.modified()?;
+	if last_modified == Some(modified) {
+		return Ok(None)
+	}
+
+	Ok(Some((
+		modified,
+		read_addresses(path)?.into_iter().collect(),
+	)))
+}
+
+/// Read the addresses of a list file, in the order of the file
+pub(crate) fn read_addresses(path: &Path) -> Result<Vec<H160>, AddressListError> {
+	let mut addresses = vec![];
+	for (index, line) in fs::read_to_string(path)?.lines().enumerate() {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') {
+			continue
+		}
+
+		let address =
+			Hash160::from_param(line).map_err(|error| AddressListError::InvalidAddress {
+				line: index + 1,
+				error,
+			})?;
+		addresses.push(address.into());
+	}
+
+	Ok(addresses)
+}
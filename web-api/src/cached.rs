@@ -0,0 +1,42 @@
+use rocket::{
+	http::Status,
+	request::Request,
+	response::{self, Responder, Response},
+};
+
+use crate::guards::IfNoneMatch;
+
+/// A response tagged with an `ETag`, answered with a bodyless 304 if the client already has it
+pub enum Cached<R> {
+	Fresh(String, R),
+	NotModified(String),
+}
+
+impl<R> Cached<R> {
+	/// Tag `value` with `etag`, unless `if_none_match` tells the client has it already
+	pub fn new(etag: String, value: R, if_none_match: &IfNoneMatch) -> Self {
+		if if_none_match.matches(&etag) {
+			Cached::NotModified(etag)
+		} else {
+			Cached::Fresh(etag, value)
+		}
+	}
+
+	pub fn map<T>(self, f: impl FnOnce(R) -> T) -> Cached<T> {
+		match self {
+			Cached::Fresh(etag, value) => Cached::Fresh(etag, f(value)),
+			Cached::NotModified(etag) => Cached::NotModified(etag),
+		}
+	}
+}
+
+impl<'r, R: Responder<'r, 'static>> Responder<'r, 'static> for Cached<R> {
+	fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+		match self {
+			Cached::Fresh(etag, value) =>
+				Response::build_from(value.respond_to(req)?).raw_header("ETag", etag).ok(),
+			Cached::NotModified(etag) =>
+				Response::build().status(Status::NotModified).raw_header("ETag", etag).ok(),
+		}
+	}
+}
@@ -0,0 +1,163 @@
+use std::{fs, io};
+
+use kiln_postgres::serialize_checksummed;
+use libsecp256k1::{Message, PublicKey, SecretKey};
+use primitive_types::{H160, U256};
+use serde::Serialize;
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::config::Config;
+
+/// EIP-712 domain name of the claims
+pub const DOMAIN_NAME: &str = "Kiln NFT";
+/// EIP-712 domain version of the claims
+pub const DOMAIN_VERSION: &str = "1";
+
+const DOMAIN_TYPE: &str =
+	"EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)";
+const CLAIM_TYPE: &str = "Claim(address account,uint256 packed,uint64 issuedAt,uint64 expiresAt)";
+
+#[derive(Debug)]
+pub enum ClaimKeyError {
+	Io(io::Error),
+	/// The key file does not hold a hex encoded secp256k1 private key
+	InvalidKey,
+	/// The verifying contract must be set along with the signing key
+	MissingVerifyingContract,
+}
+
+impl From<io::Error> for ClaimKeyError {
+	fn from(error: io::Error) -> Self {
+		ClaimKeyError::Io(error)
+	}
+}
+
+/// EIP-712 domain the claims are signed for
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaimDomain {
+	pub name: &'static str,
+	pub version: &'static str,
+	pub chain_id: u64,
+	#[serde(serialize_with = "serialize_checksummed")]
+	pub verifying_contract: H160,
+}
+
+/// Signs the NFTs an address is eligible to, for a contract to verify them on mint
+///
+/// Claims are EIP-712 typed data
+/// `Claim(address account,uint256 packed,uint64 issuedAt,uint64 expiresAt)`, signed for the
+/// domain `EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)`.
+/// Timestamps are in seconds since the UNIX epoch.
+///
+/// On mint, the verifying contract is expected to:
+/// * recover the signer of the claim digest with `ecrecover` and check it is the trusted signer
+/// * reject the claim if `block.timestamp > expiresAt`
+/// * reject the claim if `issuedAt` is older than its last eligibility rules change, so a claim
+///   signed under former rules, or before the address was denied, cannot be used
+pub struct ClaimSigner {
+	secret: SecretKey,
+	domain: ClaimDomain,
+	domain_separator: [u8; 32],
+}
+
+impl ClaimSigner {
+	/// Load the signing key set in `config`
+	///
+	/// Return `None` if no key is configured, claims are then disabled.
+	/// The key file holds the hex encoded private key, optionally '0x' prefixed. It must only be
+	/// readable by the api.
+	pub fn load(config: &Config) -> Result<Option<Self>, ClaimKeyError> {
+		let path = match &config.claim_signing_key_file {
+			Some(p) => p,
+			None => return Ok(None),
+		};
+		let verifying_contract =
+			config.claim_verifying_contract.ok_or(ClaimKeyError::MissingVerifyingContract)?;
+
+		let content = fs::read_to_string(path)?;
+		let content = content.trim();
+		let bytes = hex::decode(content.strip_prefix("0x").unwrap_or(content))
+			.map_err(|_| ClaimKeyError::InvalidKey)?;
+		let secret = SecretKey::parse_slice(&bytes).map_err(|_| ClaimKeyError::InvalidKey)?;
+
+		let domain = ClaimDomain {
+			name: DOMAIN_NAME,
+			version: DOMAIN_VERSION,
+			chain_id: config.claim_chain_id,
+			verifying_contract,
+		};
+		let domain_separator = keccak(&[
+			&keccak(&[DOMAIN_TYPE.as_bytes()]),
+			&keccak(&[domain.name.as_bytes()]),
+			&keccak(&[domain.version.as_bytes()]),
+			&encode_u256(U256::from(domain.chain_id)),
+			&encode_address(domain.verifying_contract),
+		]);
+
+		Ok(Some(ClaimSigner {
+			secret,
+			domain,
+			domain_separator,
+		}))
+	}
+
+	pub fn domain(&self) -> &ClaimDomain {
+		&self.domain
+	}
+
+	/// Return the address of the signing key
+	pub fn signer(&self) -> H160 {
+		let public_key = PublicKey::from_secret_key(&self.secret).serialize();
+		// The first byte only tags the key as uncompressed
+		let hash = keccak(&[&public_key[1..]]);
+
+		H160::from_slice(&hash[12..])
+	}
+
+	/// Sign that `address` is eligible to the NFTs `packed`, from `issued_at` until `expires_at`
+	///
+	/// Return the 65 bytes `r || s || v` signature, with `v` being 27 or 28, as expected by
+	/// `ecrecover`.
+	pub fn sign(&self, address: H160, packed: U256, issued_at: u64, expires_at: u64) -> Vec<u8> {
+		let struct_hash = keccak(&[
+			&keccak(&[CLAIM_TYPE.as_bytes()]),
+			&encode_address(address),
+			&encode_u256(packed),
+			&encode_u256(U256::from(issued_at)),
+			&encode_u256(U256::from(expires_at)),
+		]);
+		let digest = keccak(&[&[0x19, 0x01], &self.domain_separator, &struct_hash]);
+
+		let (signature, recovery_id) = libsecp256k1::sign(&Message::parse(&digest), &self.secret);
+
+		let mut bytes = signature.serialize().to_vec();
+		bytes.push(recovery_id.serialize() + 27);
+		bytes
+	}
+}
+
+pub(crate) fn keccak(chunks: &[&[u8]]) -> [u8; 32] {
+	let mut hasher = Keccak::v256();
+	for chunk in chunks {
+		hasher.update(chunk);
+	}
+
+	let mut output = [0; 32];
+	hasher.finalize(&mut output);
+	output
+}
+
+// ABI encode an address, left padded to 32 bytes
+fn encode_address(address: H160) -> [u8; 32] {
+	let mut encoded = [0; 32];
+	encoded[12..].copy_from_slice(address.as_bytes());
+	encoded
+}
+
+// ABI encode an uint256, as 32 big endian bytes
+fn encode_u256(value: U256) -> [u8; 32] {
+	let mut encoded = [0; 32];
+	value.to_big_endian(&mut encoded);
+	encoded
+}
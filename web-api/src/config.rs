@@ -0,0 +1,153 @@
+use std::path::PathBuf;
+
+use primitive_types::H160;
+use serde::Deserialize;
+
+/// Application specific configuration
+///
+/// Read from `Rocket.toml` or `ROCKET_*` environment variables, along with Rocket's own
+/// configuration.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+	/// Compress responses if the client accepts it
+	pub compression: bool,
+	/// Size, in bytes, under which responses are not compressed
+	pub compression_threshold: usize,
+	/// File of the only addresses eligible to NFTs
+	pub address_allowlist: Option<PathBuf>,
+	/// File of the addresses never eligible to NFTs
+	pub address_denylist: Option<PathBuf>,
+	/// Interval, in seconds, between two checks for changes in the address lists
+	pub address_lists_reload_interval: u64,
+	/// Bearer token required by the admin routes, which are disabled when unset
+	pub admin_token: Option<String>,
+	/// Maximum number of heights a single reprocess job can cover
+	pub reprocess_max_range: u64,
+	/// Number of precomputed NFTs checked by a reconciliation, 0 to check all of them
+	pub reconcile_sample_size: u32,
+	/// Interval, in seconds, between two checks for a new sync head
+	pub head_poll_interval: u64,
+	/// Maximum number of slots a proposals count can cover
+	pub proposals_max_range: u64,
+	/// Maximum number of buckets returned by an activity histogram
+	pub histogram_max_buckets: u32,
+	/// Only count transactions in blocks finalized by the chain towards NFTs
+	pub finalized_only: bool,
+	/// Number of blocks transactions must be below the highest indexed one to count towards NFTs,
+	/// so activity near the tip does not grant an NFT a reorg takes back
+	pub min_confirmations: u64,
+	/// Contracts of the current campaign, calling any of them grants the `used_target_contract`
+	/// NFT. None is granted while empty
+	pub target_contracts: Vec<H160>,
+	/// Serve the NFTs of an address from the precomputed table, computing them on first request
+	pub precomputed_nfts: bool,
+	/// File listing the addresses whose NFTs are precomputed on launch, most queried first, in
+	/// the format of the address lists. Nothing is precomputed on launch when unset, or without
+	/// `precomputed_nfts`
+	pub warm_start_addresses: Option<PathBuf>,
+	/// Maximum number of addresses of `warm_start_addresses` precomputed on launch
+	pub warm_start_size: usize,
+	/// Minimum number of hex digits of an address search prefix, bounding the scanned range
+	pub search_min_prefix_length: usize,
+	/// Maximum number of addresses returned by an address search
+	pub search_max_results: u32,
+	/// Maximum number of transactions of an address returned at once
+	pub transactions_max_results: u32,
+	/// Maximum number of addresses returned by a lookup by NFTs mask
+	pub mask_max_results: u32,
+	/// Maximum number of items of a page of the full listings, e.g. of every issuer's NFTs
+	///
+	/// Larger pages, including whole lists requested without `limit`, are rejected with a 413
+	/// before any of their items is computed: clients must paginate past it.
+	pub list_max_items: u64,
+	/// Maximum number of slots a present heights lookup can cover
+	pub present_heights_max_range: u64,
+	/// File of the private key signing NFT claims, which are disabled when unset
+	pub claim_signing_key_file: Option<PathBuf>,
+	/// Contract verifying the NFT claims, part of their EIP-712 domain
+	pub claim_verifying_contract: Option<H160>,
+	/// Chain id of the NFT claims EIP-712 domain
+	pub claim_chain_id: u64,
+	/// Number of seconds an NFT claim can be used for after being signed
+	pub claim_validity: u64,
+	/// Maximum number of addresses a validators status lookup can cover
+	pub validators_status_max_addresses: usize,
+	/// Maximum number of distinct addresses whose NFTs can be explained at once
+	///
+	/// Bodies larger than that many addresses take are rejected before being read in full.
+	pub explain_max_addresses: usize,
+	/// Maximum nesting of a GraphQL query
+	pub graphql_max_depth: usize,
+	/// Maximum complexity of a GraphQL query, every field counting 1 and every transaction
+	/// requested counting its fields
+	pub graphql_max_complexity: usize,
+	/// Maximum number of transactions of an address a GraphQL query can request
+	pub graphql_max_transactions: u32,
+	/// Number of validator slashing statuses kept in memory, 0 to disable the cache
+	pub slashed_cache_size: usize,
+	/// Serialize the integers of slot and validator responses as decimal strings instead of
+	/// numbers
+	pub u64_as_string: bool,
+	/// Number of connections of the pool running the issuers listing queries in parallel
+	///
+	/// Each api instance opens up to Rocket's `databases.kiln_pg.pool_size` connections for the
+	/// requests, plus this many for the listing, plus `health_pool_size`. Summed over the api
+	/// instances and the indexer, this must stay below Postgres' `max_connections`, minus a few
+	/// for administration.
+	pub listing_pool_size: u32,
+	/// Number of connections reserved to `/health`, so it answers even when the other pools are
+	/// exhausted by heavy requests
+	pub health_pool_size: u32,
+	/// Number of slots the database can be behind the node while the api is ready
+	pub ready_max_lag: u64,
+	/// Time, in seconds, the database must stay beyond or within `ready_max_lag` for `/ready` to
+	/// change its answer
+	pub ready_hysteresis: u64,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Config {
+			compression: true,
+			compression_threshold: 1024,
+			address_allowlist: None,
+			address_denylist: None,
+			address_lists_reload_interval: 60,
+			admin_token: None,
+			reprocess_max_range: 100_000,
+			reconcile_sample_size: 100,
+			head_poll_interval: 1,
+			proposals_max_range: 100_000,
+			histogram_max_buckets: 1000,
+			finalized_only: false,
+			min_confirmations: 0,
+			target_contracts: vec![],
+			precomputed_nfts: false,
+			warm_start_addresses: None,
+			warm_start_size: 1000,
+			search_min_prefix_length: 4,
+			search_max_results: 100,
+			transactions_max_results: 100,
+			mask_max_results: 1000,
+			list_max_items: 100_000,
+			present_heights_max_range: 100_000,
+			claim_signing_key_file: None,
+			claim_verifying_contract: None,
+			// Kiln
+			claim_chain_id: 1337802,
+			claim_validity: 3600,
+			validators_status_max_addresses: 1000,
+			explain_max_addresses: 100,
+			graphql_max_depth: 5,
+			graphql_max_complexity: 1000,
+			graphql_max_transactions: 100,
+			slashed_cache_size: 0,
+			u64_as_string: false,
+			listing_pool_size: 10,
+			health_pool_size: 1,
+			ready_max_lag: 5,
+			ready_hysteresis: 60,
+		}
+	}
+}
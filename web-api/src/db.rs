@@ -0,0 +1,125 @@
+use std::{convert::Infallible, time::Duration};
+
+use kiln_postgres::{count_statements, metrics::DB_CONNECTION_WAIT_SECONDS, DbConnection};
+use rocket::{
+	fairing::Fairing,
+	outcome::try_outcome,
+	request::{FromRequest, Outcome},
+	Build, Phase, Request, Rocket,
+};
+use rocket_sync_db_pools::{
+	database,
+	diesel::r2d2::{self, ConnectionManager, ManageConnection},
+	Config, PoolResult, Poolable,
+};
+
+use crate::fairings::DbStatements;
+
+/// Connection of Rocket's pool, a `DbConnection` so its statements are counted
+pub struct PooledDbConnection(DbConnection);
+
+/// Manager of Rocket's pool, the one of diesel for a `PooledDbConnection`
+pub struct PooledDbManager(ConnectionManager<DbConnection>);
+
+impl ManageConnection for PooledDbManager {
+	type Connection = PooledDbConnection;
+	type Error = r2d2::Error;
+
+	fn connect(&self) -> Result<PooledDbConnection, r2d2::Error> {
+		self.0.connect().map(PooledDbConnection)
+	}
+
+	fn is_valid(&self, conn: &mut PooledDbConnection) -> Result<(), r2d2::Error> {
+		self.0.is_valid(&mut conn.0)
+	}
+
+	fn has_broken(&self, conn: &mut PooledDbConnection) -> bool {
+		self.0.has_broken(&mut conn.0)
+	}
+}
+
+// Same pool as the one Rocket builds for a `diesel::PgConnection`
+impl Poolable for PooledDbConnection {
+	type Error = Infallible;
+	type Manager = PooledDbManager;
+
+	fn pool(db_name: &str, rocket: &Rocket<Build>) -> PoolResult<Self> {
+		let config = Config::from(db_name, rocket)?;
+		let manager = PooledDbManager(ConnectionManager::new(&config.url));
+
+		Ok(r2d2::Pool::builder()
+			.max_size(config.pool_size)
+			.connection_timeout(Duration::from_secs(config.timeout as u64))
+			.build(manager)?)
+	}
+}
+
+#[database("kiln_pg")]
+struct KilnPg(PooledDbConnection);
+
+/// Connection to the database
+///
+/// When the tables live in a dedicated schema, it must be set in the url, e.g.
+/// `postgres://...?options=-c%20search_path%3D<schema>`, like for `DATABASE_SCHEMA`.
+///
+/// As a request guard, the statements it runs are counted in the request's `DbStatements`.
+pub struct PgConn {
+	db: KilnPg,
+	statements: DbStatements,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for PgConn {
+	type Error = ();
+
+	async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+		let db = try_outcome!(req.guard::<KilnPg>().await);
+
+		Outcome::Success(PgConn {
+			db,
+			statements: DbStatements::of(req),
+		})
+	}
+}
+
+impl PgConn {
+	/// Return the fairing initializing the pool
+	pub fn fairing() -> impl Fairing {
+		KilnPg::fairing()
+	}
+
+	/// Return a connection outside of any request, None if the pool is not initialized
+	pub async fn get_one<P: Phase>(rocket: &Rocket<P>) -> Option<Self> {
+		KilnPg::get_one(rocket).await.map(|db| PgConn {
+			db,
+			statements: DbStatements::default(),
+		})
+	}
+
+	/// Return the count the statements run on behalf of the same request are added to
+	pub fn statements(&self) -> &DbStatements {
+		&self.statements
+	}
+
+	/// Run `f` on the connection
+	///
+	/// The time spent waiting for the connection to be available is recorded in the
+	/// `db_connection_wait_seconds` histogram. The statements run by `f` are counted.
+	pub async fn timed_run<F, R>(&self, f: F) -> R
+	where
+		F: FnOnce(&mut DbConnection) -> R + Send + 'static,
+		R: Send + 'static,
+	{
+		let timer = DB_CONNECTION_WAIT_SECONDS.start_timer();
+		let statements = self.statements.clone();
+
+		self.db
+			.run(move |c| {
+				timer.observe_duration();
+				let (result, count) = count_statements(|| f(&mut c.0));
+				statements.add(count);
+				result
+			})
+			.await
+	}
+}
@@ -1,9 +1,142 @@
-use rocket::response::Responder;
+use kiln_postgres::PoolError;
+use log::error;
+use rocket::{
+	catch,
+	http::{Status, StatusClass},
+	response::{self, Responder, Response},
+	serde::json::Json,
+	Request,
+};
 use rocket_sync_db_pools::diesel;
+use schemars::JsonSchema;
+use serde::Serialize;
 
+use crate::params::HashParamError;
+
+/// Error of a route
+///
+/// Answered with its status and a JSON body holding its `code` and a short `message`.
 #[derive(Debug)]
 pub enum Error {
 	DbPool(diesel::result::Error),
+	/// No database connection could be established, outside of the request's pooled one
+	DbConnection(PoolError),
+	Metrics(prometheus::Error),
+	/// `from` is above `to`, or the range is too large
+	InvalidRange,
+	/// Another reprocess job is already pending or running
+	ReprocessInProgress,
+	/// Buckets must hold at least one block
+	InvalidBucketSize,
+	/// Address prefixes are made of a bounded number of hex digits
+	InvalidPrefix,
+	/// No claim signing key is configured
+	ClaimsDisabled,
+	/// More addresses than allowed are looked up at once
+	TooManyAddresses,
+	/// Transaction kinds are `call`, `transfer` or `deploy`
+	InvalidTransactionKind,
+	/// Masks are '0x' prefixed hex, setting only the bits of NFT types
+	InvalidMask,
+	/// NFTs are not precomputed
+	PrecomputedNftsDisabled,
+	/// A page holds more items than `list_max_items`
+	ResponseTooLarge,
+	/// Addresses are '0x' prefixed hex of 20 bytes
+	InvalidAddress(HashParamError),
+	/// Hashes are '0x' prefixed hex of 32 bytes
+	InvalidHash(HashParamError),
+	/// No transaction is stored with this hash
+	TransactionNotFound,
+	/// The body is not of the expected JSON type
+	InvalidBody,
+}
+
+impl Error {
+	/// Return the identifier of the error, for clients to branch on
+	///
+	/// Codes are part of the api, they must never change:
+	/// * `database`: 500, the database failed
+	/// * `metrics`: 500, the metrics could not be encoded
+	/// * `invalid_range`: 400, `from` is above `to` or the range is too large
+	/// * `reprocess_in_progress`: 409, another reprocess job is already pending or running
+	/// * `invalid_bucket_size`: 400, the bucket size is zero
+	/// * `invalid_prefix`: 400, the address prefix is too short, too long or not hex
+	/// * `claims_disabled`: 404, no claim signing key is configured
+	/// * `too_many_addresses`: 400, more addresses than allowed are looked up at once
+	/// * `invalid_transaction_kind`: 400, the transaction kind is not `call`, `transfer` or
+	///   `deploy`
+	/// * `invalid_mask`: 400, the NFT mask is not hex or sets a bit of no NFT type
+	/// * `precomputed_nfts_disabled`: 404, NFTs are not precomputed
+	/// * `response_too_large`: 413, the page holds more items than allowed, a smaller `limit` is
+	///   required
+	/// * `invalid_address`: 400, the address is not '0x' prefixed hex of 20 bytes
+	/// * `invalid_hash`: 400, the hash is not '0x' prefixed hex of 32 bytes
+	/// * `transaction_not_found`: 404, no transaction is stored with this hash
+	/// * `invalid_body`: 422, the body is malformed
+	///
+	/// Errors raised before reaching a route use the codes of `error_catcher`.
+	pub fn code(&self) -> &'static str {
+		match self {
+			Self::DbPool(_) | Self::DbConnection(_) => "database",
+			Self::Metrics(_) => "metrics",
+			Self::InvalidRange => "invalid_range",
+			Self::ReprocessInProgress => "reprocess_in_progress",
+			Self::InvalidBucketSize => "invalid_bucket_size",
+			Self::InvalidPrefix => "invalid_prefix",
+			Self::ClaimsDisabled => "claims_disabled",
+			Self::TooManyAddresses => "too_many_addresses",
+			Self::InvalidTransactionKind => "invalid_transaction_kind",
+			Self::InvalidMask => "invalid_mask",
+			Self::PrecomputedNftsDisabled => "precomputed_nfts_disabled",
+			Self::ResponseTooLarge => "response_too_large",
+			Self::InvalidAddress(_) => "invalid_address",
+			Self::InvalidHash(_) => "invalid_hash",
+			Self::TransactionNotFound => "transaction_not_found",
+			Self::InvalidBody => "invalid_body",
+		}
+	}
+
+	fn status(&self) -> Status {
+		match self {
+			Self::InvalidRange
+			| Self::InvalidBucketSize
+			| Self::InvalidPrefix
+			| Self::TooManyAddresses
+			| Self::InvalidTransactionKind
+			| Self::InvalidMask
+			| Self::InvalidAddress(_)
+			| Self::InvalidHash(_) => Status::BadRequest,
+			Self::ReprocessInProgress => Status::Conflict,
+			Self::ClaimsDisabled | Self::PrecomputedNftsDisabled | Self::TransactionNotFound =>
+				Status::NotFound,
+			Self::ResponseTooLarge => Status::PayloadTooLarge,
+			Self::InvalidBody => Status::UnprocessableEntity,
+			Self::DbPool(_) | Self::DbConnection(_) | Self::Metrics(_) =>
+				Status::InternalServerError,
+		}
+	}
+
+	pub(crate) fn message(&self) -> &'static str {
+		match self {
+			Self::DbPool(_) | Self::DbConnection(_) => "The database failed",
+			Self::Metrics(_) => "The metrics could not be encoded",
+			Self::InvalidRange => "The range is reversed or too large",
+			Self::ReprocessInProgress => "Another reprocess job is already pending or running",
+			Self::InvalidBucketSize => "Buckets must hold at least one block",
+			Self::InvalidPrefix => "The prefix must be made of a bounded number of hex digits",
+			Self::ClaimsDisabled => "Claims are disabled",
+			Self::TooManyAddresses => "Too many addresses are looked up at once",
+			Self::InvalidTransactionKind => "Transaction kinds are call, transfer or deploy",
+			Self::InvalidMask => "The mask must be '0x' prefixed hex setting only NFT type bits",
+			Self::PrecomputedNftsDisabled => "NFTs are not precomputed",
+			Self::ResponseTooLarge => "The page is too large, request a smaller limit",
+			Self::InvalidAddress(_) => "The address must be '0x' prefixed hex of 20 bytes",
+			Self::InvalidHash(_) => "The hash must be '0x' prefixed hex of 32 bytes",
+			Self::TransactionNotFound => "No transaction is stored with this hash",
+			Self::InvalidBody => "The body is malformed",
+		}
+	}
 }
 
 impl From<diesel::result::Error> for Error {
@@ -12,8 +145,172 @@ impl From<diesel::result::Error> for Error {
 	}
 }
 
+impl From<PoolError> for Error {
+	fn from(error: PoolError) -> Self {
+		Self::DbConnection(error)
+	}
+}
+
+impl From<prometheus::Error> for Error {
+	fn from(error: prometheus::Error) -> Self {
+		Self::Metrics(error)
+	}
+}
+
+/// JSON body of the error responses
+#[derive(Serialize, JsonSchema)]
+pub struct ErrorBody {
+	code: &'static str,
+	message: &'static str,
+}
+
+/// Server errors are logged, the client only being told their code
 impl<'r> Responder<'r, 'static> for Error {
-	fn respond_to(self, _: &rocket::Request) -> rocket::response::Result<'static> {
-		Err(rocket::http::Status::InternalServerError)
+	fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+		let status = self.status();
+		if status.class() == StatusClass::ServerError {
+			error!("{} {}: {self:?}", req.method(), req.uri());
+		}
+
+		let body = ErrorBody {
+			code: self.code(),
+			message: self.message(),
+		};
+
+		Response::build_from(Json(body).respond_to(req)?).status(status).ok()
+	}
+}
+
+/// Answer the errors raised before reaching a route with the same JSON body as `Error`
+///
+/// Codes are part of the api, they must never change:
+/// * `not_found`: 404, no route matches
+/// * `unauthorized`: 401, the admin token is missing or wrong
+/// * `bad_request`: 400 or 422, a parameter or the body is malformed
+/// * `unavailable`: 503, the api is not healthy or not ready
+/// * `internal`: any other status
+#[catch(default)]
+pub fn error_catcher(status: Status, _: &Request) -> (Status, Json<ErrorBody>) {
+	let (code, message) = match status.code {
+		404 => ("not_found", "Nothing matches this path"),
+		401 => ("unauthorized", "A valid admin token is required"),
+		400 | 422 => ("bad_request", "A parameter or the body is malformed"),
+		503 => ("unavailable", "The api is not healthy or not ready"),
+		_ => ("internal", "The request could not be answered"),
+	};
+
+	(status, Json(ErrorBody { code, message }))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashSet;
+
+	use rocket::{get, local::blocking::Client, routes, serde::json::Value};
+
+	use super::*;
+
+	// Every variant but `DbConnection`, whose pool error cannot be built outside of r2d2
+	fn errors() -> Vec<(Error, &'static str, Status)> {
+		vec![
+			(
+				Error::DbPool(diesel::result::Error::NotFound),
+				"database",
+				Status::InternalServerError,
+			),
+			(
+				Error::Metrics(prometheus::Error::Msg("test".to_string())),
+				"metrics",
+				Status::InternalServerError,
+			),
+			(Error::InvalidRange, "invalid_range", Status::BadRequest),
+			(
+				Error::ReprocessInProgress,
+				"reprocess_in_progress",
+				Status::Conflict,
+			),
+			(
+				Error::InvalidBucketSize,
+				"invalid_bucket_size",
+				Status::BadRequest,
+			),
+			(Error::InvalidPrefix, "invalid_prefix", Status::BadRequest),
+			(Error::ClaimsDisabled, "claims_disabled", Status::NotFound),
+			(
+				Error::TooManyAddresses,
+				"too_many_addresses",
+				Status::BadRequest,
+			),
+			(
+				Error::InvalidTransactionKind,
+				"invalid_transaction_kind",
+				Status::BadRequest,
+			),
+			(Error::InvalidMask, "invalid_mask", Status::BadRequest),
+			(
+				Error::PrecomputedNftsDisabled,
+				"precomputed_nfts_disabled",
+				Status::NotFound,
+			),
+			(
+				Error::ResponseTooLarge,
+				"response_too_large",
+				Status::PayloadTooLarge,
+			),
+			(
+				Error::InvalidAddress(HashParamError::InvalidPrefix),
+				"invalid_address",
+				Status::BadRequest,
+			),
+			(
+				Error::InvalidHash(HashParamError::InvalidLength),
+				"invalid_hash",
+				Status::BadRequest,
+			),
+			(
+				Error::TransactionNotFound,
+				"transaction_not_found",
+				Status::NotFound,
+			),
+			(
+				Error::InvalidBody,
+				"invalid_body",
+				Status::UnprocessableEntity,
+			),
+		]
+	}
+
+	#[test]
+	fn codes_and_statuses_are_the_documented_ones() {
+		for (error, code, status) in errors() {
+			assert_eq!(error.code(), code, "{error:?}");
+			assert_eq!(error.status(), status, "{error:?}");
+			assert!(!error.message().is_empty(), "{error:?}");
+		}
+	}
+
+	// `DbConnection` shares `database` with `DbPool`, on purpose
+	#[test]
+	fn codes_are_distinct() {
+		let codes: Vec<&str> = errors().iter().map(|(error, ..)| error.code()).collect();
+		let distinct: HashSet<&str> = codes.iter().copied().collect();
+
+		assert_eq!(codes.len(), distinct.len());
+	}
+
+	#[get("/failing")]
+	fn failing() -> Result<(), Error> {
+		Err(Error::DbPool(diesel::result::Error::NotFound))
+	}
+
+	#[test]
+	fn errors_are_answered_with_their_status_and_code() {
+		let client = Client::tracked(rocket::build().mount("/", routes![failing])).unwrap();
+
+		let response = client.get("/failing").dispatch();
+		assert_eq!(response.status(), Status::InternalServerError);
+		let body: Value = response.into_json().unwrap();
+		assert_eq!(body["code"], "database");
+		assert_eq!(body["message"], "The database failed");
 	}
 }
@@ -0,0 +1,135 @@
+use std::io::{Cursor, Write};
+
+use flate2::{
+	write::{DeflateEncoder, GzEncoder},
+	Compression as Level,
+};
+use log::warn;
+use rocket::{
+	fairing::{Fairing, Info, Kind},
+	http::{ContentType, Header},
+	Request, Response,
+};
+
+use crate::config::Config;
+
+/// Encodings we are able to produce, by order of preference
+#[derive(Debug, Clone, Copy)]
+enum Encoding {
+	Gzip,
+	Deflate,
+}
+
+impl Encoding {
+	fn name(&self) -> &'static str {
+		match self {
+			Encoding::Gzip => "gzip",
+			Encoding::Deflate => "deflate",
+		}
+	}
+
+	fn encode(&self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+		match self {
+			Encoding::Gzip => {
+				let mut encoder = GzEncoder::new(vec![], Level::default());
+				encoder.write_all(data)?;
+				encoder.finish()
+			},
+			Encoding::Deflate => {
+				let mut encoder = DeflateEncoder::new(vec![], Level::default());
+				encoder.write_all(data)?;
+				encoder.finish()
+			},
+		}
+	}
+}
+
+/// Compress responses with gzip or deflate, according to the `Accept-Encoding` request header
+///
+/// Only JSON and plain text bodies bigger than the configured threshold are compressed.
+/// Responses that already have a `Content-Encoding` are left untouched.
+pub struct Compression {
+	threshold: usize,
+}
+
+impl Compression {
+	pub fn new(config: &Config) -> Self {
+		Compression {
+			threshold: config.compression_threshold,
+		}
+	}
+}
+
+#[rocket::async_trait]
+impl Fairing for Compression {
+	fn info(&self) -> Info {
+		Info {
+			name: "Response compression",
+			kind: Kind::Response,
+		}
+	}
+
+	async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+		if res.headers().contains("Content-Encoding") || !is_compressible(res.content_type()) {
+			return
+		}
+
+		// Only sized bodies can be checked against the threshold without being read
+		match res.body().preset_size() {
+			Some(size) if size >= self.threshold => {},
+			_ => return,
+		}
+
+		let encoding = match preferred_encoding(req.headers().get("Accept-Encoding")) {
+			Some(e) => e,
+			None => return,
+		};
+
+		let body = match res.body_mut().to_bytes().await {
+			Ok(b) => b,
+			Err(err) => {
+				warn!("failed to read response body: {err}");
+				return
+			},
+		};
+
+		match encoding.encode(&body) {
+			Ok(compressed) => {
+				res.set_header(Header::new("Content-Encoding", encoding.name()));
+				res.set_sized_body(compressed.len(), Cursor::new(compressed));
+			},
+			Err(err) => {
+				warn!("failed to {} response body: {err}", encoding.name());
+				res.set_sized_body(body.len(), Cursor::new(body));
+			},
+		}
+		res.adjoin_header(Header::new("Vary", "Accept-Encoding"));
+	}
+}
+
+// Compressing already compressed formats is a waste of cpu
+fn is_compressible(content_type: Option<ContentType>) -> bool {
+	match content_type {
+		Some(ct) => ct.is_json() || ct.is_plain(),
+		None => false,
+	}
+}
+
+// Pick the best encoding among the `Accept-Encoding` header values
+fn preferred_encoding<'a>(accept_encoding: impl Iterator<Item = &'a str>) -> Option<Encoding> {
+	let accepted: Vec<&str> = accept_encoding
+		.flat_map(|v| v.split(','))
+		.filter_map(|v| {
+			let mut parts = v.split(';').map(str::trim);
+			let coding = parts.next()?;
+			// `q=0` means "not acceptable"
+			let refused = parts
+				.any(|p| p.strip_prefix("q=").and_then(|q| q.parse::<f32>().ok()) == Some(0.0));
+			(!refused).then(|| coding)
+		})
+		.collect();
+
+	[Encoding::Gzip, Encoding::Deflate]
+		.into_iter()
+		.find(|e| accepted.iter().any(|a| a.eq_ignore_ascii_case(e.name()) || *a == "*"))
+}
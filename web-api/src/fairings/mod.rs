@@ -0,0 +1,5 @@
+mod compression;
+mod request_metrics;
+
+pub use compression::*;
+pub use request_metrics::*;
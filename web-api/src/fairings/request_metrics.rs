@@ -0,0 +1,125 @@
+use std::{
+	convert::Infallible,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc,
+	},
+	time::Instant,
+};
+
+#[cfg(debug_assertions)]
+use rocket::http::Header;
+use rocket::{
+	fairing::{Fairing, Info, Kind},
+	request::{FromRequest, Outcome},
+	Data, Request, Response,
+};
+
+use crate::metrics::{HTTP_REQUESTS_TOTAL, HTTP_REQUEST_DURATION_SECONDS};
+
+/// Route label of the requests no route handled
+const UNMATCHED_ROUTE: &str = "unmatched";
+/// Header holding the number of database statements run by a request, in debug builds
+#[cfg(debug_assertions)]
+pub const DB_STATEMENTS_HEADER: &str = "X-Db-Statements";
+
+// Time at which the request was received, cached in the request
+struct RequestStart(Instant);
+
+/// Number of database statements run on behalf of a request
+///
+/// Cached in the request, every guard of a request shares the same count. `PgConn` adds the
+/// statements of its runs, the routes querying the listing pool add theirs with `add`, counted
+/// with `kiln_postgres::count_statements`.
+#[derive(Clone, Default)]
+pub struct DbStatements(Arc<AtomicU64>);
+
+impl DbStatements {
+	pub fn of(req: &Request<'_>) -> Self {
+		req.local_cache(DbStatements::default).clone()
+	}
+
+	pub fn add(&self, count: u64) {
+		self.0.fetch_add(count, Ordering::Relaxed);
+	}
+
+	pub fn get(&self) -> u64 {
+		self.0.load(Ordering::Relaxed)
+	}
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for DbStatements {
+	type Error = Infallible;
+
+	async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+		Outcome::Success(DbStatements::of(req))
+	}
+}
+
+/// Count requests and record their latency, labeled by the name of the handling route
+///
+/// Labels are the route handler names, never the request paths, so their number stays bounded.
+/// The latency covers the handler and the response fairings attached before this one.
+///
+/// In debug builds, the number of database statements run while handling the request is returned
+/// in the `X-Db-Statements` header, to catch the routes whose statements grow with the items they
+/// return. See `DbStatements`.
+pub struct RequestMetrics;
+
+#[rocket::async_trait]
+impl Fairing for RequestMetrics {
+	fn info(&self) -> Info {
+		Info {
+			name: "Request metrics",
+			kind: Kind::Request | Kind::Response,
+		}
+	}
+
+	async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+		req.local_cache(|| RequestStart(Instant::now()));
+	}
+
+	async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+		let start = req.local_cache(|| RequestStart(Instant::now()));
+		let route = req.route().and_then(|r| r.name.as_deref()).unwrap_or(UNMATCHED_ROUTE);
+
+		HTTP_REQUESTS_TOTAL
+			.with_label_values(&[route, &res.status().code.to_string()])
+			.inc();
+		HTTP_REQUEST_DURATION_SECONDS
+			.with_label_values(&[route])
+			.observe(start.0.elapsed().as_secs_f64());
+
+		#[cfg(debug_assertions)]
+		res.set_header(Header::new(
+			DB_STATEMENTS_HEADER,
+			DbStatements::of(req).get().to_string(),
+		));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use rocket::{get, local::blocking::Client, routes};
+
+	use super::*;
+
+	#[get("/statements?<n>")]
+	fn statements(n: u64, first: DbStatements, second: DbStatements) {
+		first.add(n);
+		second.add(n);
+	}
+
+	#[test]
+	fn db_statements_are_counted_per_request() {
+		let rocket = rocket::build().attach(RequestMetrics).mount("/", routes![statements]);
+		let client = Client::tracked(rocket).unwrap();
+
+		for n in [0, 1, 3] {
+			let response = client.get(format!("/statements?n={n}")).dispatch();
+			let header = response.headers().get_one(DB_STATEMENTS_HEADER).unwrap();
+			assert_eq!(header, (2 * n).to_string());
+		}
+	}
+}
@@ -0,0 +1,173 @@
+use std::sync::Arc;
+
+use async_graphql::{
+	Context, EmptyMutation, EmptySubscription, ErrorExtensions, Object, Schema, SimpleObject,
+};
+use kiln_postgres::{checksum_address, Transaction};
+use primitive_types::H160;
+
+use crate::{
+	address_filter::AddressFilter,
+	config::Config,
+	params::decode_prefixed_hex,
+	routes::{counted_below_height, inner_get_packed_nft, Cutoff},
+	slashed_cache::SlashedCache,
+	Error, PgConn,
+};
+
+pub type ApiSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+/// Return the schema of the GraphQL endpoint
+///
+/// Queries deeper or more complex than configured are rejected before being run. Each
+/// transaction requested counts in the complexity, so it also bounds the rows loaded.
+/// The database connection is added to every request by the route.
+pub fn schema(
+	config: &Config,
+	filter: Arc<AddressFilter>,
+	slashed: Arc<SlashedCache>,
+) -> ApiSchema {
+	Schema::build(Query, EmptyMutation, EmptySubscription)
+		.data(State {
+			cutoff: Cutoff::new(config),
+			targets: config.target_contracts.clone(),
+			max_transactions: config.graphql_max_transactions,
+			filter,
+			slashed,
+		})
+		.limit_depth(config.graphql_max_depth)
+		.limit_complexity(config.graphql_max_complexity)
+		.finish()
+}
+
+// What the resolvers need besides the connection
+struct State {
+	cutoff: Cutoff,
+	targets: Vec<H160>,
+	max_transactions: u32,
+	filter: Arc<AddressFilter>,
+	slashed: Arc<SlashedCache>,
+}
+
+impl From<Error> for async_graphql::Error {
+	fn from(error: Error) -> Self {
+		async_graphql::Error::new(error.message()).extend_with(|_, e| e.set("code", error.code()))
+	}
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+	/// The address `address`, '0x' prefixed hex
+	async fn address(&self, address: String) -> async_graphql::Result<Address> {
+		let bytes = decode_prefixed_hex(&address, 20)
+			.map_err(|_| async_graphql::Error::new("Invalid address"))?;
+
+		Ok(Address(H160::from_slice(&bytes)))
+	}
+}
+
+pub struct Address(H160);
+
+#[Object]
+impl Address {
+	/// EIP-55 checksummed address
+	async fn address(&self) -> String {
+		checksum_address(&self.0)
+	}
+
+	/// Names of the NFTs the address is eligible to
+	///
+	/// Always computed from the indexed data, as the REST route does with `as_of`.
+	async fn nfts(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<&'static str>> {
+		let state = ctx.data::<State>()?;
+		let (cutoff, targets) = (state.cutoff, state.targets.clone());
+		let (filter, slashed) = (state.filter.clone(), state.slashed.clone());
+		let address = self.0;
+		let packed_nft = ctx
+			.data::<PgConn>()?
+			.timed_run(move |c| -> Result<_, Error> {
+				let height = counted_below_height(c, cutoff)?;
+				slashed.refresh(c)?;
+				inner_get_packed_nft(c, &filter, &slashed, &targets, address, height)
+			})
+			.await?;
+
+		Ok(packed_nft.names())
+	}
+
+	/// Latest transactions issued by the address, newest first
+	#[graphql(complexity = "limit as usize * child_complexity")]
+	async fn transactions(
+		&self,
+		ctx: &Context<'_>,
+		#[graphql(default = 10)] limit: u32,
+	) -> async_graphql::Result<Vec<AddressTransaction>> {
+		if limit > ctx.data::<State>()?.max_transactions {
+			return Err(async_graphql::Error::new("Too many transactions requested"))
+		}
+
+		let address = self.0;
+		let transactions = ctx
+			.data::<PgConn>()?
+			.timed_run(move |c| Transaction::recent_from_address(c, address, None, limit))
+			.await
+			.map_err(Error::from)?;
+
+		Ok(transactions.iter().map(AddressTransaction::from).collect())
+	}
+
+	/// Validators deposited by the address, null if none
+	async fn validator(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<ValidatorStatus>> {
+		let slashed = ctx.data::<State>()?.slashed.clone();
+		let address = self.0;
+		let status = ctx
+			.data::<PgConn>()?
+			.timed_run(move |c| -> Result<_, Error> {
+				slashed.refresh(c)?;
+				Ok(slashed.is_validator_slashed(c, address)?)
+			})
+			.await?;
+
+		Ok(status.map(|slashed| ValidatorStatus { slashed }))
+	}
+}
+
+#[derive(SimpleObject)]
+pub struct ValidatorStatus {
+	/// Whether any of the validators was slashed
+	slashed: bool,
+}
+
+/// Transaction issued by an address
+#[derive(SimpleObject)]
+pub struct AddressTransaction {
+	/// '0x' prefixed hex
+	hash: String,
+	block_number: u64,
+	/// EIP-55 checksummed address, null for a deployment
+	to: Option<String>,
+	/// EIP-55 checksummed address of the contract created, if a deployment
+	contract_address: Option<String>,
+	/// '0x' prefixed hex of the value, in wei
+	value: String,
+	/// Null if not known yet
+	status: Option<bool>,
+	/// EIP-2718 type, null if not known
+	tx_type: Option<u64>,
+}
+
+impl From<&Transaction> for AddressTransaction {
+	fn from(transaction: &Transaction) -> Self {
+		AddressTransaction {
+			hash: format!("{:?}", transaction.hash()),
+			block_number: transaction.block_number(),
+			to: transaction.to().as_ref().map(checksum_address),
+			contract_address: transaction.contract_address().as_ref().map(checksum_address),
+			value: format!("{:#x}", transaction.value()),
+			status: transaction.status(),
+			tx_type: transaction.tx_type(),
+		}
+	}
+}
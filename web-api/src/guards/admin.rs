@@ -0,0 +1,104 @@
+use rocket::{
+	http::Status,
+	request::{FromRequest, Outcome},
+	Request,
+};
+
+use crate::config::Config;
+
+/// Request guard for the routes mounted under `/admin`
+///
+/// Succeed if the `Authorization` header holds the configured `admin_token` as a bearer token.
+/// Fail with 401 otherwise, or if no token is configured.
+pub struct Admin;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+	type Error = ();
+
+	async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+		let expected = req.rocket().state::<Config>().and_then(|c| c.admin_token.as_ref());
+
+		let token = req.headers().get_one("Authorization").and_then(|h| h.strip_prefix("Bearer "));
+
+		match (expected, token) {
+			(Some(e), Some(t)) if constant_time_eq(e.as_bytes(), t.as_bytes()) =>
+				Outcome::Success(Admin),
+			_ => Outcome::Failure((Status::Unauthorized, ())),
+		}
+	}
+}
+
+// Compare without short-circuiting, so the response time does not leak the token
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+	if a.len() != b.len() {
+		return false
+	}
+
+	a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+	use rocket::{get, http::Header, local::blocking::Client, routes};
+
+	use super::*;
+
+	#[get("/admin")]
+	fn admin(_admin: Admin) {}
+
+	fn client(admin_token: Option<&str>) -> Client {
+		let config = Config {
+			admin_token: admin_token.map(String::from),
+			..Config::default()
+		};
+
+		Client::tracked(rocket::build().manage(config).mount("/", routes![admin])).unwrap()
+	}
+
+	fn status(client: &Client, authorization: Option<&str>) -> Status {
+		let mut request = client.get("/admin");
+		if let Some(authorization) = authorization {
+			request = request.header(Header::new("Authorization", authorization.to_string()));
+		}
+
+		request.dispatch().status()
+	}
+
+	#[test]
+	fn the_configured_token_is_authorized() {
+		let client = client(Some("secret"));
+
+		assert_eq!(status(&client, Some("Bearer secret")), Status::Ok);
+	}
+
+	#[test]
+	fn a_missing_or_wrong_token_is_unauthorized() {
+		let client = client(Some("secret"));
+
+		for authorization in [
+			None,
+			Some("secret"),
+			Some("Bearer wrong"),
+			Some("Bearer secre"),
+		] {
+			assert_eq!(status(&client, authorization), Status::Unauthorized);
+		}
+	}
+
+	#[test]
+	fn nothing_is_authorized_without_a_configured_token() {
+		let client = client(None);
+
+		assert_eq!(status(&client, Some("Bearer ")), Status::Unauthorized);
+		assert_eq!(status(&client, None), Status::Unauthorized);
+	}
+
+	#[test]
+	fn tokens_are_compared_whole() {
+		assert!(constant_time_eq(b"secret", b"secret"));
+		assert!(!constant_time_eq(b"secret", b"secreT"));
+		assert!(!constant_time_eq(b"secret", b"secrets"));
+		assert!(!constant_time_eq(b"", b"secret"));
+	}
+}
@@ -0,0 +1,191 @@
+use std::{collections::HashSet, io};
+
+use primitive_types::H160;
+use rocket::{
+	data::{self, ByteUnit, Data, FromData},
+	http::Status,
+	serde::json::serde_json,
+	Request,
+};
+
+use crate::{config::Config, Error};
+
+/// Bytes an address can take in the body, '0x' prefixed hex in quotes, its comma and some
+/// whitespace
+const ADDRESS_BYTES: usize = 64;
+
+/// Data guard of the addresses whose NFTs are explained, a JSON array of '0x' prefixed hex
+/// addresses
+///
+/// Holds them deduplicated, in the order they are first given. The body is read up to the size
+/// of `explain_max_addresses` addresses, so an oversized batch is rejected before being read in
+/// full, duplicates counting in that size. More distinct addresses than allowed are rejected too.
+#[derive(Debug)]
+pub struct ExplainedAddresses(pub Vec<H160>);
+
+/// Why the body of `ExplainedAddresses` was rejected
+#[derive(Debug)]
+pub enum ExplainedAddressesError {
+	/// More addresses than `explain_max_addresses`, or a larger body than they can take
+	TooMany,
+	/// The body is not a JSON array of addresses
+	Malformed(serde_json::Error),
+	/// The body could not be read
+	Io(io::Error),
+}
+
+impl From<ExplainedAddressesError> for Error {
+	fn from(error: ExplainedAddressesError) -> Self {
+		match error {
+			ExplainedAddressesError::TooMany => Error::TooManyAddresses,
+			ExplainedAddressesError::Malformed(_) | ExplainedAddressesError::Io(_) =>
+				Error::InvalidBody,
+		}
+	}
+}
+
+// With `max_addresses` allowed, fitting two brackets too
+fn body_limit(max_addresses: usize) -> ByteUnit {
+	ByteUnit::from(max_addresses.saturating_mul(ADDRESS_BYTES).saturating_add(2))
+}
+
+// Return the distinct `addresses`, in the order they are first given
+fn distinct(addresses: Vec<H160>) -> Vec<H160> {
+	let mut seen = HashSet::new();
+
+	addresses.into_iter().filter(|a| seen.insert(*a)).collect()
+}
+
+#[rocket::async_trait]
+impl<'r> FromData<'r> for ExplainedAddresses {
+	type Error = ExplainedAddressesError;
+
+	async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+		let max_addresses = req
+			.rocket()
+			.state::<Config>()
+			.map_or(Config::default().explain_max_addresses, |c| {
+				c.explain_max_addresses
+			});
+
+		let body = match data.open(body_limit(max_addresses)).into_bytes().await {
+			Ok(body) if body.is_complete() => body.into_inner(),
+			Ok(_) =>
+				return data::Outcome::Failure((
+					Status::BadRequest,
+					ExplainedAddressesError::TooMany,
+				)),
+			Err(err) =>
+				return data::Outcome::Failure((
+					Status::BadRequest,
+					ExplainedAddressesError::Io(err),
+				)),
+		};
+
+		let addresses = match serde_json::from_slice(&body) {
+			Ok(addresses) => distinct(addresses),
+			Err(err) =>
+				return data::Outcome::Failure((
+					Status::UnprocessableEntity,
+					ExplainedAddressesError::Malformed(err),
+				)),
+		};
+		if addresses.len() > max_addresses {
+			return data::Outcome::Failure((Status::BadRequest, ExplainedAddressesError::TooMany))
+		}
+
+		data::Outcome::Success(ExplainedAddresses(addresses))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use rocket::{local::blocking::Client, post, routes, serde::json::Value};
+
+	use super::*;
+
+	#[post("/explain", data = "<addresses>")]
+	fn explain(
+		addresses: Result<ExplainedAddresses, ExplainedAddressesError>,
+	) -> Result<String, Error> {
+		Ok(addresses?.0.len().to_string())
+	}
+
+	fn address(index: u64) -> H160 {
+		H160::from_low_u64_be(index)
+	}
+
+	fn client(explain_max_addresses: usize) -> Client {
+		let config = Config {
+			explain_max_addresses,
+			..Config::default()
+		};
+
+		Client::tracked(rocket::build().manage(config).mount("/", routes![explain])).unwrap()
+	}
+
+	// Return the status and the error code of the response, if any, to posting `body`
+	fn explained(client: &Client, body: String) -> (Status, Option<String>) {
+		let response = client.post("/explain").body(body).dispatch();
+		let status = response.status();
+		let code = response
+			.into_json::<Value>()
+			.and_then(|body| body["code"].as_str().map(String::from));
+
+		(status, code)
+	}
+
+	#[test]
+	fn batches_within_the_cap_are_read() {
+		let client = client(2);
+		let body = serde_json::to_string(&[address(1), address(2), address(1)]).unwrap();
+
+		let response = client.post("/explain").body(body).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(response.into_string().unwrap(), "2");
+	}
+
+	#[test]
+	fn bodies_larger_than_the_cap_are_rejected_before_being_read() {
+		// Far larger than the body of 2 addresses, the same address being repeated
+		let body = serde_json::to_string(&vec![address(1); 10_000]).unwrap();
+
+		let (status, code) = explained(&client(2), body);
+		assert_eq!(status, Status::BadRequest);
+		assert_eq!(code.as_deref(), Some("too_many_addresses"));
+	}
+
+	#[test]
+	fn more_distinct_addresses_than_the_cap_are_rejected() {
+		let body = serde_json::to_string(&[address(1), address(2), address(3)]).unwrap();
+
+		let (status, code) = explained(&client(2), body);
+		assert_eq!(status, Status::BadRequest);
+		assert_eq!(code.as_deref(), Some("too_many_addresses"));
+	}
+
+	#[test]
+	fn malformed_bodies_are_rejected() {
+		let (status, code) = explained(&client(2), r#"["0x12"]"#.to_string());
+		assert_eq!(status, Status::UnprocessableEntity);
+		assert_eq!(code.as_deref(), Some("invalid_body"));
+	}
+
+	#[test]
+	fn a_full_batch_fits_the_limit() {
+		let addresses: Vec<H160> = (0..100).map(address).collect();
+		let body = serde_json::to_string_pretty(&addresses).unwrap();
+
+		assert!(body_limit(100) >= ByteUnit::from(body.len()));
+	}
+
+	#[test]
+	fn distinct_addresses_keep_their_first_position() {
+		let addresses = vec![address(2), address(1), address(2), address(3), address(1)];
+
+		assert_eq!(
+			distinct(addresses),
+			vec![address(2), address(1), address(3)]
+		);
+	}
+}
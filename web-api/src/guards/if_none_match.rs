@@ -0,0 +1,74 @@
+use std::convert::Infallible;
+
+use rocket::{
+	request::{FromRequest, Outcome},
+	Request,
+};
+
+/// Entity tags of the `If-None-Match` request header, if any
+///
+/// Tags are compared weakly, ignoring their `W/` prefix, as a `GET` is only conditional on the
+/// content and not on its encoding.
+#[derive(Clone, Debug, Default)]
+pub struct IfNoneMatch(Option<String>);
+
+impl IfNoneMatch {
+	/// Return whether the client already has the content tagged `etag`
+	pub fn matches(&self, etag: &str) -> bool {
+		let header = match &self.0 {
+			Some(h) => h,
+			None => return false,
+		};
+
+		let etag = weak_tag(etag);
+		header.split(',').map(str::trim).any(|tag| tag == "*" || weak_tag(tag) == etag)
+	}
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfNoneMatch {
+	type Error = Infallible;
+
+	async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+		Outcome::Success(IfNoneMatch(
+			req.headers().get_one("If-None-Match").map(String::from),
+		))
+	}
+}
+
+fn weak_tag(tag: &str) -> &str {
+	tag.strip_prefix("W/").unwrap_or(tag)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn header(value: &str) -> IfNoneMatch {
+		IfNoneMatch(Some(value.to_string()))
+	}
+
+	#[test]
+	fn nothing_matches_without_the_header() {
+		assert!(!IfNoneMatch::default().matches(r#""41""#));
+	}
+
+	#[test]
+	fn tags_match_weakly() {
+		assert!(header(r#""41""#).matches(r#""41""#));
+		assert!(header(r#"W/"41""#).matches(r#""41""#));
+		assert!(header(r#""41""#).matches(r#"W/"41""#));
+		assert!(!header(r#""41""#).matches(r#""43""#));
+	}
+
+	#[test]
+	fn any_tag_of_the_list_matches() {
+		assert!(header(r#""1", W/"41" ,"2""#).matches(r#"W/"41""#));
+		assert!(!header(r#""1", "2""#).matches(r#"W/"41""#));
+	}
+
+	#[test]
+	fn star_matches_every_tag() {
+		assert!(header("*").matches(r#"W/"41""#));
+	}
+}
@@ -0,0 +1,7 @@
+mod admin;
+mod explained_addresses;
+mod if_none_match;
+
+pub use admin::*;
+pub use explained_addresses::*;
+pub use if_none_match::*;
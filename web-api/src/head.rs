@@ -0,0 +1,107 @@
+use std::{
+	sync::{Arc, RwLock},
+	time::Duration,
+};
+
+use kiln_postgres::{DbConnection, Stat, StatKey};
+use log::warn;
+use rocket::fairing::AdHoc;
+use rocket_sync_db_pools::diesel::QueryResult;
+use serde::Serialize;
+use tokio::{
+	sync::broadcast,
+	time::{interval, MissedTickBehavior},
+};
+
+use crate::{json::JsonU64, PgConn};
+
+/// Number of heads kept for clients that are late reading them
+const CHANNEL_CAPACITY: usize = 16;
+
+/// Sync heads, as published by the indexer
+#[derive(Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Head {
+	consensus_db_height: Option<JsonU64>,
+	execution_db_height: Option<JsonU64>,
+	consensus_node_height: Option<JsonU64>,
+}
+
+impl Head {
+	fn load(conn: &DbConnection) -> QueryResult<Head> {
+		Ok(Head {
+			consensus_db_height: Stat::get(conn, StatKey::ConsensusDbHeight)?.map(JsonU64),
+			execution_db_height: Stat::get(conn, StatKey::ExecutionDbHeight)?.map(JsonU64),
+			consensus_node_height: Stat::get(conn, StatKey::ConsensusNodeHeight)?.map(JsonU64),
+		})
+	}
+}
+
+/// Broadcast the sync heads to live clients every time they change
+///
+/// The heads are polled from database, where the indexer publishes them.
+pub struct HeadWatcher {
+	sender: broadcast::Sender<Head>,
+	latest: RwLock<Head>,
+}
+
+impl Default for HeadWatcher {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl HeadWatcher {
+	pub fn new() -> Self {
+		let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+
+		HeadWatcher {
+			sender,
+			latest: RwLock::new(Head::default()),
+		}
+	}
+
+	/// Return the latest heads and a receiver for the next ones
+	pub fn subscribe(&self) -> (Head, broadcast::Receiver<Head>) {
+		let receiver = self.sender.subscribe();
+
+		(self.latest.read().unwrap().clone(), receiver)
+	}
+
+	/// Fairing polling the heads every `poll_interval` once Rocket is launched
+	pub fn fairing(watcher: Arc<HeadWatcher>, poll_interval: Duration) -> AdHoc {
+		AdHoc::on_liftoff("Head watcher", move |rocket| {
+			Box::pin(async move {
+				match PgConn::get_one(rocket).await {
+					Some(conn) => {
+						tokio::spawn(watcher.watch(conn, poll_interval));
+					},
+					None => warn!("no database connection, live head updates are disabled"),
+				}
+			})
+		})
+	}
+
+	async fn watch(self: Arc<Self>, conn: PgConn, poll_interval: Duration) {
+		let mut ticker = interval(poll_interval);
+		// A slow poll must not be followed by a burst of polls catching up
+		ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+		loop {
+			ticker.tick().await;
+
+			let head = match conn.timed_run(|c| Head::load(c)).await {
+				Ok(h) => h,
+				Err(err) => {
+					warn!("failed to load the sync head: {err}");
+					continue
+				},
+			};
+
+			if *self.latest.read().unwrap() == head {
+				continue
+			}
+			*self.latest.write().unwrap() = head.clone();
+			// Fails only when no client is listening
+			self.sender.send(head).ok();
+		}
+	}
+}
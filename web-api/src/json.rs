@@ -0,0 +1,182 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use schemars::JsonSchema;
+use serde::{Serialize, Serializer};
+
+use crate::Error;
+
+/// Whether `JsonU64` values are serialized as strings, set once on launch
+static U64_AS_STRING: AtomicBool = AtomicBool::new(false);
+
+/// Serialize every `JsonU64` as a decimal string from now on, if `enabled`
+pub fn set_u64_as_string(enabled: bool) {
+	U64_AS_STRING.store(enabled, Ordering::Relaxed);
+}
+
+/// An integer of a slot or validator response
+///
+/// Serialized as a JSON number by default, e.g. `42`, or as a decimal string, e.g. `"42"`, with the
+/// `u64_as_string` setting. Javascript clients lose precision on numbers above 2^53, strings keep
+/// every value exact. The same setting applies to all of them, so a field never changes type from
+/// one value to another.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct JsonU64(pub u64);
+
+impl From<u64> for JsonU64 {
+	fn from(value: u64) -> Self {
+		JsonU64(value)
+	}
+}
+
+impl Serialize for JsonU64 {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		if U64_AS_STRING.load(Ordering::Relaxed) {
+			serializer.collect_str(&self.0)
+		} else {
+			serializer.serialize_u64(self.0)
+		}
+	}
+}
+
+/// Items of a list response, either bare or enveloped
+///
+/// Bare by default, a JSON array of the items as lists always were, e.g. `[...]`. Enveloped on
+/// request, e.g. `{ "data": [...], "total": 120, "limit": 50, "offset": 100 }`, so the pagination
+/// travels along with the items: `total` counts the items of the whole list, `limit` and
+/// `offset` are the ones applied.
+#[derive(Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum ListResponse<T> {
+	Bare(Vec<T>),
+	Envelope {
+		data: Vec<T>,
+		total: u64,
+		limit: u64,
+		offset: u64,
+	},
+}
+
+/// Return the number of items of the page of a list of `total` items, at most `limit` after the
+/// first `offset`
+///
+/// Every item past `offset` is in the page without `limit`.
+/// Fail with `ResponseTooLarge` if the page would hold more than `max_items`, so that it is
+/// rejected before being built.
+pub fn page_len(
+	total: u64,
+	offset: Option<u64>,
+	limit: Option<u64>,
+	max_items: u64,
+) -> Result<u64, Error> {
+	let len = total.saturating_sub(offset.unwrap_or(0)).min(limit.unwrap_or(u64::MAX));
+	if len > max_items {
+		return Err(Error::ResponseTooLarge)
+	}
+
+	Ok(len)
+}
+
+impl<T> ListResponse<T> {
+	/// Wrap `data`, the page of a list of `total` items selected by `offset` and `limit`
+	pub fn page(
+		data: Vec<T>,
+		total: u64,
+		offset: Option<u64>,
+		limit: Option<u64>,
+		envelope: bool,
+	) -> Self {
+		if envelope {
+			ListResponse::Envelope {
+				data,
+				total,
+				limit: limit.unwrap_or(total),
+				offset: offset.unwrap_or(0),
+			}
+		} else {
+			ListResponse::Bare(data)
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Mutex;
+
+	use lazy_static::lazy_static;
+	use rocket::serde::json::to_string;
+
+	use super::*;
+
+	lazy_static! {
+		/// Held while serializing, the setting is shared by every test
+		static ref U64_SETTING: Mutex<()> = Mutex::new(());
+	}
+
+	fn serialized(value: u64, as_string: bool) -> String {
+		let _setting = U64_SETTING.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+		set_u64_as_string(as_string);
+		let json = to_string(&JsonU64(value)).unwrap();
+		set_u64_as_string(false);
+
+		json
+	}
+
+	#[test]
+	fn u64_are_numbers_by_default() {
+		assert_eq!(serialized(0, false), "0");
+		assert_eq!(serialized(42, false), "42");
+		assert_eq!(serialized(u64::MAX, false), "18446744073709551615");
+	}
+
+	#[test]
+	fn u64_are_decimal_strings_when_set() {
+		assert_eq!(serialized(0, true), r#""0""#);
+		assert_eq!(serialized(42, true), r#""42""#);
+		assert_eq!(serialized(u64::MAX, true), r#""18446744073709551615""#);
+	}
+
+	#[test]
+	fn u64_past_javascript_precision_keep_every_digit() {
+		// 2^53 + 1, the first integer a javascript number cannot hold
+		let unsafe_integer = (1 << 53) + 1;
+
+		assert_eq!(serialized(unsafe_integer, false), "9007199254740993");
+		assert_eq!(serialized(unsafe_integer, true), r#""9007199254740993""#);
+	}
+
+	#[test]
+	fn page_is_bounded_by_limit() {
+		assert_eq!(page_len(100, None, Some(10), 50).unwrap(), 10);
+		assert_eq!(page_len(100, Some(95), Some(10), 50).unwrap(), 5);
+	}
+
+	#[test]
+	fn page_past_the_end_is_empty() {
+		assert_eq!(page_len(100, Some(100), Some(10), 50).unwrap(), 0);
+		assert_eq!(page_len(100, Some(500), None, 50).unwrap(), 0);
+	}
+
+	#[test]
+	fn page_without_limit_holds_every_item_past_offset() {
+		assert_eq!(page_len(40, None, None, 50).unwrap(), 40);
+		assert_eq!(page_len(100, Some(60), None, 50).unwrap(), 40);
+	}
+
+	#[test]
+	fn page_larger_than_max_items_is_rejected() {
+		assert!(matches!(
+			page_len(100, None, None, 50),
+			Err(Error::ResponseTooLarge)
+		));
+		assert!(matches!(
+			page_len(100, None, Some(51), 50),
+			Err(Error::ResponseTooLarge)
+		));
+		assert_eq!(page_len(100, None, Some(50), 50).unwrap(), 50);
+	}
+
+	#[test]
+	fn large_limit_on_a_short_list_is_accepted() {
+		assert_eq!(page_len(10, None, Some(u64::MAX), 50).unwrap(), 10);
+	}
+}
@@ -1,25 +1,158 @@
+mod activity_score;
+mod address_filter;
+mod cached;
+mod claims;
+mod config;
+mod db;
 mod errors;
+mod fairings;
+mod graphql;
+mod guards;
+mod head;
+mod json;
+mod merkle;
+mod metrics;
+mod openapi;
 mod packed_nft_types;
 mod params;
+mod readiness;
 mod routes;
+mod slashed_cache;
+mod warm_start;
 
-use dotenv::dotenv;
-use rocket::{launch, routes};
+use std::{sync::Arc, time::Duration};
 
-use rocket_sync_db_pools::{database, diesel};
+use address_filter::AddressFilter;
+use claims::ClaimSigner;
+use config::Config;
+use dotenv::dotenv;
+use head::HeadWatcher;
+use readiness::Readiness;
+use rocket::{catchers, launch, routes, Route};
+use slashed_cache::SlashedCache;
+use warm_start::WarmStart;
 
+pub use db::PgConn;
 pub use errors::Error;
 
-#[database("kiln_pg")]
-pub struct PgConn(diesel::PgConnection);
-
 #[launch]
 fn rocket() -> _ {
 	dotenv().ok();
 	env_logger::init();
 
-	rocket::build().attach(PgConn::fairing()).mount(
-		"/",
-		routes![routes::nfts_by_address, routes::list_all_eligible_nft],
-	)
+	let rocket = rocket::build();
+	let config: Config = rocket.figment().extract().expect("invalid configuration");
+
+	json::set_u64_as_string(config.u64_as_string);
+
+	let address_filter =
+		Arc::new(AddressFilter::load(&config).expect("failed to load address lists"));
+	if address_filter.is_configured() {
+		let address_filter = address_filter.clone();
+		let interval = Duration::from_secs(config.address_lists_reload_interval);
+		std::thread::spawn(move || address_filter.watch(interval));
+	}
+
+	let claim_signer = ClaimSigner::load(&config).expect("failed to load the claim signing key");
+
+	let slashed_cache = Arc::new(SlashedCache::new(config.slashed_cache_size));
+	let graphql_schema = graphql::schema(&config, address_filter.clone(), slashed_cache.clone());
+	let readiness = Readiness::new(
+		config.ready_max_lag,
+		Duration::from_secs(config.ready_hysteresis),
+	);
+
+	let pool = kiln_postgres::connexion_pool_with_size(config.listing_pool_size);
+	let health_pool = routes::HealthPool(kiln_postgres::connexion_pool_with_size(
+		config.health_pool_size,
+	));
+	let conn = kiln_postgres::get_connection(&pool).expect("failed to connect to the database");
+	kiln_postgres::check_schema_version(&conn).unwrap_or_else(|err| panic!("{err}"));
+
+	if let Some(warm_start) = WarmStart::from_config(&config) {
+		let pool = pool.clone();
+		let address_filter = address_filter.clone();
+		let slashed_cache = slashed_cache.clone();
+		std::thread::spawn(move || warm_start.run(&pool, &address_filter, &slashed_cache));
+	}
+
+	let head_watcher = Arc::new(HeadWatcher::new());
+	let rocket = rocket.attach(HeadWatcher::fairing(
+		head_watcher.clone(),
+		Duration::from_secs(config.head_poll_interval),
+	));
+
+	let rocket = if config.compression {
+		rocket.attach(fairings::Compression::new(&config))
+	} else {
+		rocket
+	};
+
+	rocket
+		.attach(PgConn::fairing())
+		// Last, so the time spent in the other response fairings is recorded
+		.attach(fairings::RequestMetrics)
+		.manage(config)
+		.manage(address_filter)
+		.manage(head_watcher)
+		.manage(claim_signer)
+		.manage(slashed_cache)
+		.manage(graphql_schema)
+		// Pool the issuers listing queries in parallel with, outside of Rocket's own
+		.manage(pool)
+		.manage(health_pool)
+		.manage(readiness)
+		.mount("/", public_routes())
+		.mount("/admin", admin_routes())
+		.register("/", catchers![errors::error_catcher])
+}
+
+// Return the routes mounted on "/"
+fn public_routes() -> Vec<Route> {
+	routes![
+		routes::nfts_by_address,
+		routes::list_all_eligible_nft,
+		routes::metrics,
+		routes::openapi_document,
+		routes::head_events,
+		routes::proposed_count,
+		routes::activity_histogram,
+		routes::slots_by_spec,
+		routes::search_addresses,
+		routes::present_slots,
+		routes::nft_catalog,
+		routes::issuers_count,
+		routes::validator_slashings,
+		routes::validators_status,
+		routes::health,
+		routes::ready,
+		routes::nfts_claim_signature,
+		routes::nfts_merkle_proof,
+		routes::slot,
+		routes::slot_gas,
+		routes::graphql_query,
+		routes::address_activity_score,
+		routes::nfts_claim_state,
+		routes::address_transactions,
+		routes::transaction_by_hash,
+		routes::nft_stats,
+		routes::addresses_by_nft_mask,
+		routes::explain_nfts
+	]
+}
+
+// Return the routes mounted on "/admin"
+//
+// Every one of them must take an `Admin` guard.
+fn admin_routes() -> Vec<Route> {
+	routes![
+		routes::reprocess,
+		routes::reprocess_status,
+		routes::recompute_nfts,
+		routes::reconcile_nfts,
+		routes::mark_nfts_minted,
+		routes::build_nfts_merkle_tree,
+		routes::pause_sync,
+		routes::resume_sync
+	]
 }
@@ -0,0 +1,73 @@
+use primitive_types::{H160, U256};
+
+use crate::claims::keccak;
+
+/// Return the leaf of `address` eligible to the NFTs `packed`
+///
+/// That is `keccak256(abi.encodePacked(address account, uint256 packed))`: the 20 bytes of the
+/// address followed by the 32 big endian bytes of the packed NFTs integer. Being 52 bytes long,
+/// a leaf preimage cannot be mistaken for the 64 bytes one of a node.
+pub fn leaf_hash(address: H160, packed: U256) -> [u8; 32] {
+	let mut encoded_packed = [0; 32];
+	packed.to_big_endian(&mut encoded_packed);
+
+	keccak(&[address.as_bytes(), &encoded_packed])
+}
+
+/// Keccak256 Merkle tree of the packed NFTs, for a contract to verify claims against its root
+///
+/// Nodes are the hash of their two children sorted, `keccak256(min(a, b) ++ max(a, b))`, so
+/// proofs need no left or right flags: this is what OpenZeppelin's `MerkleProof.verify` expects.
+/// A node left without a sibling is carried up to the next level unchanged.
+/// The root of an empty tree is zero.
+pub struct MerkleTree {
+	// From the leaves up to the root
+	levels: Vec<Vec<[u8; 32]>>,
+}
+
+impl MerkleTree {
+	pub fn new(leaves: Vec<[u8; 32]>) -> Self {
+		let mut levels = vec![leaves];
+		while levels.last().map_or(false, |level| level.len() > 1) {
+			// Safe to unwrap because there is at least one level
+			let next = levels
+				.last()
+				.unwrap()
+				.chunks(2)
+				.map(|pair| match pair {
+					[a, b] => hash_pair(a, b),
+					[a] => *a,
+					_ => unreachable!(),
+				})
+				.collect();
+			levels.push(next);
+		}
+
+		MerkleTree { levels }
+	}
+
+	pub fn root(&self) -> [u8; 32] {
+		self.levels.last().and_then(|level| level.first()).copied().unwrap_or_default()
+	}
+
+	/// Return the sibling hashes of the leaf at `index`, from the leaf level up
+	pub fn proof(&self, mut index: usize) -> Vec<[u8; 32]> {
+		let mut proof = vec![];
+		for level in &self.levels[..self.levels.len() - 1] {
+			if let Some(sibling) = level.get(index ^ 1) {
+				proof.push(*sibling);
+			}
+			index /= 2;
+		}
+
+		proof
+	}
+}
+
+fn hash_pair(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+	if a <= b {
+		keccak(&[a, b])
+	} else {
+		keccak(&[b, a])
+	}
+}
@@ -0,0 +1,34 @@
+use lazy_static::lazy_static;
+use prometheus::{
+	register_histogram_vec, register_int_counter, register_int_counter_vec, HistogramVec,
+	IntCounter, IntCounterVec,
+};
+
+lazy_static! {
+	/// Requests answered, by route and status code
+	pub static ref HTTP_REQUESTS_TOTAL: IntCounterVec = register_int_counter_vec!(
+		"http_requests_total",
+		"Number of requests answered, by route and status code",
+		&["route", "status"]
+	)
+	.unwrap();
+	/// Time taken to answer requests, by route
+	pub static ref HTTP_REQUEST_DURATION_SECONDS: HistogramVec = register_histogram_vec!(
+		"http_request_duration_seconds",
+		"Time taken to answer requests, by route",
+		&["route"]
+	)
+	.unwrap();
+	/// Validator slashing statuses served from the cache
+	pub static ref SLASHED_CACHE_HITS: IntCounter = register_int_counter!(
+		"slashed_cache_hits_total",
+		"Number of validator slashing statuses served from the cache"
+	)
+	.unwrap();
+	/// Validator slashing statuses queried from the database
+	pub static ref SLASHED_CACHE_MISSES: IntCounter = register_int_counter!(
+		"slashed_cache_misses_total",
+		"Number of validator slashing statuses missing from the cache"
+	)
+	.unwrap();
+}
@@ -0,0 +1,214 @@
+use rocket::{
+	serde::json::{json, serde_json, Value},
+	Route,
+};
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+
+use crate::{
+	errors::ErrorBody,
+	json::ListResponse,
+	packed_nft_types::{NftType, PackedNftTypes},
+	routes::{ActivityScore, AddressNftPair, NftExplanation, NftStats},
+};
+
+/// Return the OpenAPI 3.0 description of `routes`, every route mounted
+///
+/// Paths and their parameters are read from the routes, so none can be missed. Parameters
+/// are described by name, see `parameter_schema`, and responses by route name, see
+/// `response_schema`: routes not listed there are described without a response schema.
+/// Request bodies are not described.
+pub fn document<'a>(routes: impl Iterator<Item = &'a Route>) -> Value {
+	let mut generator = SchemaSettings::openapi3().into_generator();
+	let error = generator.subschema_for::<ErrorBody>();
+
+	let mut paths = serde_json::Map::new();
+	for route in routes {
+		let uri = route.uri.to_string();
+		let (path, query) = match uri.split_once('?') {
+			Some((path, query)) => (path, Some(query)),
+			None => (uri.as_str(), None),
+		};
+
+		let mut parameters = vec![];
+		let mut openapi_path = vec![];
+		for segment in path.split('/') {
+			match dynamic_name(segment) {
+				Some(name) => {
+					parameters.push(parameter(name, "path", true));
+					openapi_path.push(format!("{{{name}}}"));
+				},
+				None => openapi_path.push(segment.to_string()),
+			}
+		}
+		for segment in query.into_iter().flat_map(|q| q.split('&')) {
+			if let Some(name) = dynamic_name(segment) {
+				parameters.push(parameter(name, "query", false));
+			}
+		}
+
+		let name = route.name.as_deref().unwrap_or_default();
+		let mut success = json!({ "description": "Success" });
+		if let Some(schema) = response_schema(&mut generator, name) {
+			success["content"] = json!({ "application/json": { "schema": schema } });
+		}
+		let mut operation = json!({
+			"operationId": name,
+			"parameters": parameters,
+			"responses": {
+				"200": success,
+				"default": {
+					"description": "Error",
+					"content": { "application/json": { "schema": error } },
+				},
+			},
+		});
+		if path.starts_with("/admin/") {
+			operation["security"] = json!([{ "admin": [] }]);
+		}
+
+		let item = paths.entry(openapi_path.join("/")).or_insert_with(|| json!({}));
+		item[route.method.as_str().to_lowercase()] = operation;
+	}
+
+	json!({
+		"openapi": "3.0.3",
+		"info": {
+			"title": "Kiln NFT api",
+			"version": env!("CARGO_PKG_VERSION"),
+		},
+		"paths": paths,
+		"components": {
+			"schemas": generator.definitions(),
+			"securitySchemes": {
+				"admin": { "type": "http", "scheme": "bearer" },
+			},
+		},
+	})
+}
+
+// Return the name of a dynamic `<name>` segment
+fn dynamic_name(segment: &str) -> Option<&str> {
+	segment.strip_prefix('<')?.strip_suffix('>')
+}
+
+fn parameter(name: &str, location: &str, required: bool) -> Value {
+	json!({
+		"name": name,
+		"in": location,
+		"required": required,
+		"schema": parameter_schema(name),
+	})
+}
+
+// Return the schema of a route parameter, by name
+//
+// Parameters are named after what they hold throughout the routes.
+fn parameter_schema(name: &str) -> Value {
+	match name {
+		"address" => json!({ "type": "string", "pattern": "^0x[0-9a-fA-F]{40}$" }),
+		"hash" => json!({ "type": "string", "pattern": "^0x[0-9a-fA-F]{64}$" }),
+		"prefix" => json!({ "type": "string", "pattern": "^(0x)?[0-9a-fA-F]*$" }),
+		"mask" => json!({ "type": "string", "pattern": "^0x[0-9a-fA-F]{1,64}$" }),
+		"names" | "envelope" => json!({ "type": "boolean" }),
+		"kind" => json!({ "type": "string", "enum": ["call", "transfer", "deploy"] }),
+		"id" => json!({ "type": "integer", "format": "int32" }),
+		_ => json!({ "type": "integer", "format": "int64", "minimum": 0 }),
+	}
+}
+
+// Return the schema of the successful response of a route, by route name, if described
+fn response_schema(generator: &mut SchemaGenerator, route: &str) -> Option<Value> {
+	let schema = match route {
+		"nfts_by_address" | "recompute_nfts" => generator.subschema_for::<PackedNftTypes>(),
+		"list_all_eligible_nft" => generator.subschema_for::<ListResponse<AddressNftPair>>(),
+		"nft_catalog" => generator.subschema_for::<Vec<NftType>>(),
+		"issuers_count" => generator.subschema_for::<u64>(),
+		"nft_stats" => generator.subschema_for::<NftStats>(),
+		"addresses_by_nft_mask" => generator.subschema_for::<Vec<String>>(),
+		"address_activity_score" => generator.subschema_for::<ActivityScore>(),
+		"explain_nfts" => generator.subschema_for::<Vec<NftExplanation>>(),
+		_ => return None,
+	};
+
+	// Safe to unwrap because schemas always serialize
+	Some(serde_json::to_value(schema).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Return the description of the routes mounted on launch, along with their names and paths
+	fn described() -> (Value, Vec<(String, String)>) {
+		let rocket = rocket::build()
+			.mount("/", crate::public_routes())
+			.mount("/admin", crate::admin_routes());
+		let routes = rocket
+			.routes()
+			.map(|route| {
+				(
+					route.name.as_deref().unwrap().to_string(),
+					route.uri.to_string(),
+				)
+			})
+			.collect();
+
+		(document(rocket.routes()), routes)
+	}
+
+	// Return every operation of `document`, by id, along with whether it is secured
+	fn operations(document: &Value) -> Vec<(String, bool)> {
+		document["paths"]
+			.as_object()
+			.unwrap()
+			.values()
+			.flat_map(|item| item.as_object().unwrap().values())
+			.map(|operation| {
+				(
+					operation["operationId"].as_str().unwrap().to_string(),
+					operation.get("security").is_some(),
+				)
+			})
+			.collect()
+	}
+
+	#[test]
+	fn every_mounted_route_is_described_once() {
+		let (document, routes) = described();
+		let operations = operations(&document);
+
+		assert_eq!(operations.len(), routes.len());
+		for (name, uri) in &routes {
+			assert!(
+				operations.iter().any(|(id, _)| id == name),
+				"{name} ({uri}) is not described"
+			);
+		}
+	}
+
+	#[test]
+	fn admin_routes_are_the_secured_ones() {
+		let (document, routes) = described();
+
+		for (id, secured) in operations(&document) {
+			let (_, uri) = routes.iter().find(|(name, _)| *name == id).unwrap();
+			assert_eq!(secured, uri.starts_with("/admin/"), "{id} ({uri})");
+		}
+	}
+
+	#[test]
+	fn paths_describe_their_parameters() {
+		let (document, _) = described();
+		let operation = &document["paths"]["/address/{address}/nfts"]["get"];
+
+		assert_eq!(operation["operationId"], "nfts_by_address");
+		assert_eq!(
+			operation["parameters"],
+			json!([
+				parameter("address", "path", true),
+				parameter("as_of", "query", false)
+			])
+		);
+		assert!(operation["responses"]["200"]["content"]["application/json"]["schema"].is_object());
+	}
+}
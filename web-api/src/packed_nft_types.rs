@@ -1,5 +1,10 @@
 use paste::paste;
 use primitive_types::U256;
+use schemars::{
+	gen::SchemaGenerator,
+	schema::{InstanceType, Schema, SchemaObject, StringValidation},
+	JsonSchema,
+};
 use serde::Serialize;
 
 macro_rules! create_nft_getter_and_setter {
@@ -17,27 +22,162 @@ macro_rules! create_nft_getter_and_setter {
 	};
 }
 
+/// Declare every NFT type along with its position in the packed representation and a short
+/// description
+///
+/// Names are part of the api, they must never change.
+macro_rules! declare_nft_types {
+	($($nft_name:ident = $position: literal => $description: literal),* $(,)?) => {
+		impl PackedNftTypes {
+			$(create_nft_getter_and_setter!($nft_name, $position);)*
+
+			/// Every NFT type, by position
+			pub const NFT_TYPES: &'static [NftType] = &[$(NftType {
+				name: stringify!($nft_name),
+				position: $position,
+				description: $description,
+			}),*];
+		}
+	};
+}
+
+/// A kind of NFT an address can be eligible to
+#[derive(Serialize, JsonSchema)]
+pub struct NftType {
+	pub name: &'static str,
+	/// Position of the NFT bit in the packed representation
+	pub position: usize,
+	pub description: &'static str,
+}
+
+/// NFTs an address is eligible to, packed as one bit per NFT type
+///
+/// Serialized as a '0x' prefixed hex string, e.g. `"0x41"`, never as a JSON number: the packed
+/// value can exceed 2^53, past which JSON consumers such as javascript lose precision.
 #[derive(Serialize)]
 pub struct PackedNftTypes(U256);
 
+declare_nft_types! {
+	do_one_transaction = 0 => "Send a transaction",
+	do_100_tansactions = 1 => "Send 100 transactions",
+	deploy_contract = 2 => "Deploy a contract",
+	deploy_10_contract = 3 => "Deploy 10 contracts",
+	deploy_100_contract = 4 => "Deploy 100 contracts",
+	do_10_transactions_to_10_contracts = 5 => "Call 10 different contracts 10 times each",
+	become_validator = 6 => "Deposit a validator",
+	slashed_validator = 7 => "Deposit a validator that got slashed",
+	active_account = 8 => "Send more than 100 transactions, according to the account nonce",
+	active_streak = 9 => "Send transactions on 7 different days",
+	used_eip1559 = 10 => "Send an EIP-1559 transaction",
+	used_target_contract = 11 => "Call one of the contracts of the current campaign",
+}
+
+impl JsonSchema for PackedNftTypes {
+	fn schema_name() -> String {
+		"PackedNftTypes".to_string()
+	}
+
+	fn json_schema(_: &mut SchemaGenerator) -> Schema {
+		SchemaObject {
+			instance_type: Some(InstanceType::String.into()),
+			string: Some(Box::new(StringValidation {
+				pattern: Some("^0x[0-9a-f]+$".to_string()),
+				..Default::default()
+			})),
+			..Default::default()
+		}
+		.into()
+	}
+}
+
 impl PackedNftTypes {
-	create_nft_getter_and_setter!(do_one_transaction, 0);
+	pub fn zero() -> Self {
+		PackedNftTypes(U256::zero())
+	}
 
-	create_nft_getter_and_setter!(do_100_tansactions, 1);
+	/// Return the NFTs set in a packed representation
+	pub fn from_packed(packed: U256) -> Self {
+		PackedNftTypes(packed)
+	}
 
-	create_nft_getter_and_setter!(deploy_contract, 2);
+	/// Return the NFTs set in `mask`, or None if it sets a bit of no NFT type
+	pub fn from_mask(mask: U256) -> Option<Self> {
+		let known = Self::NFT_TYPES.iter().fold(U256::zero(), |known, nft_type| {
+			known | U256::one() << nft_type.position
+		});
 
-	create_nft_getter_and_setter!(deploy_10_contract, 3);
+		if (mask & !known).is_zero() {
+			Some(PackedNftTypes(mask))
+		} else {
+			None
+		}
+	}
 
-	create_nft_getter_and_setter!(deploy_100_contract, 4);
+	/// Return the packed representation
+	pub fn packed(&self) -> U256 {
+		self.0
+	}
 
-	create_nft_getter_and_setter!(do_10_transactions_to_10_contracts, 5);
+	/// Return the NFTs set here but not in `minted`, the packed NFTs an address already minted
+	pub fn unclaimed(&self, minted: U256) -> Self {
+		PackedNftTypes(self.0 & !minted)
+	}
 
-	create_nft_getter_and_setter!(become_validator, 6);
+	/// Return the names of the NFTs set, by position
+	pub fn names(&self) -> Vec<&'static str> {
+		Self::NFT_TYPES
+			.iter()
+			.filter(|nft_type| self.0.bit(nft_type.position))
+			.map(|nft_type| nft_type.name)
+			.collect()
+	}
+}
 
-	create_nft_getter_and_setter!(slashed_validator, 7);
+#[cfg(test)]
+mod tests {
+	use super::*;
 
-	pub fn zero() -> Self {
-		PackedNftTypes(U256::zero())
+	#[test]
+	fn catalog_lists_distinct_positions_in_order() {
+		let positions: Vec<_> =
+			PackedNftTypes::NFT_TYPES.iter().map(|nft_type| nft_type.position).collect();
+
+		assert!(
+			positions.windows(2).all(|pair| pair[0] < pair[1]),
+			"{positions:?}"
+		);
+	}
+
+	#[test]
+	fn catalog_names_every_flag() {
+		let every_bit = PackedNftTypes::from_packed(U256::max_value());
+		let names: Vec<_> =
+			PackedNftTypes::NFT_TYPES.iter().map(|nft_type| nft_type.name).collect();
+
+		assert_eq!(every_bit.names(), names);
+	}
+
+	#[test]
+	fn catalog_positions_match_the_setters() {
+		let mut nft = PackedNftTypes::zero();
+		nft.set_active_streak();
+		let active_streak = PackedNftTypes::NFT_TYPES
+			.iter()
+			.find(|nft_type| nft_type.name == "active_streak")
+			.unwrap();
+
+		assert_eq!(nft.packed(), U256::one() << active_streak.position);
+		assert_eq!(nft.names(), vec!["active_streak"]);
+	}
+
+	#[test]
+	fn masks_past_the_catalog_are_rejected() {
+		let known = PackedNftTypes::NFT_TYPES.iter().fold(U256::zero(), |known, nft_type| {
+			known | U256::one() << nft_type.position
+		});
+		let past_the_catalog = PackedNftTypes::NFT_TYPES.last().unwrap().position + 1;
+
+		assert_eq!(PackedNftTypes::from_mask(known).unwrap().packed(), known);
+		assert!(PackedNftTypes::from_mask(U256::one() << past_the_catalog).is_none());
 	}
 }
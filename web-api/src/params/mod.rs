@@ -1,5 +1,109 @@
 pub mod hash160;
 pub mod hash256;
 
+use hex::FromHexError;
+
 pub use hash160::*;
 pub use hash256::*;
+
+#[derive(Debug)]
+pub enum HashParamError {
+	/// Invalid string content
+	Hex(FromHexError),
+	/// Must start with '0x'
+	InvalidPrefix,
+	/// Must be 42 (address) or 66 (hash) characters long
+	InvalidLength,
+}
+
+impl From<FromHexError> for HashParamError {
+	fn from(error: FromHexError) -> Self {
+		HashParamError::Hex(error)
+	}
+}
+
+/// Decode a '0x' prefixed hex string of `len` bytes
+pub(crate) fn decode_prefixed_hex(param: &str, len: usize) -> Result<Vec<u8>, HashParamError> {
+	let hex_repr = param.strip_prefix("0x").ok_or(HashParamError::InvalidPrefix)?;
+	if hex_repr.len() != len * 2 {
+		return Err(HashParamError::InvalidLength)
+	}
+
+	let vec_repr = hex::decode(hex_repr)?;
+
+	Ok(vec_repr)
+}
+
+#[cfg(test)]
+mod tests {
+	use rocket::request::FromParam;
+
+	use super::*;
+
+	#[test]
+	fn valid_hex_is_decoded() {
+		let decoded = decode_prefixed_hex("0x00ff10", 3).unwrap();
+
+		assert_eq!(decoded, vec![0x00, 0xff, 0x10]);
+	}
+
+	#[test]
+	fn mixed_case_is_accepted() {
+		let decoded = decode_prefixed_hex("0xaBcDeF", 3).unwrap();
+
+		assert_eq!(decoded, vec![0xab, 0xcd, 0xef]);
+	}
+
+	#[test]
+	fn missing_prefix_is_rejected() {
+		assert!(matches!(
+			decode_prefixed_hex("00ff10", 3),
+			Err(HashParamError::InvalidPrefix)
+		));
+		assert!(matches!(
+			decode_prefixed_hex("0X00ff10", 3),
+			Err(HashParamError::InvalidPrefix)
+		));
+	}
+
+	#[test]
+	fn wrong_length_is_rejected() {
+		assert!(matches!(
+			decode_prefixed_hex("0x00ff", 3),
+			Err(HashParamError::InvalidLength)
+		));
+		assert!(matches!(
+			decode_prefixed_hex("0x00ff1020", 3),
+			Err(HashParamError::InvalidLength)
+		));
+		assert!(matches!(
+			decode_prefixed_hex("0x00ff1", 3),
+			Err(HashParamError::InvalidLength)
+		));
+	}
+
+	#[test]
+	fn non_hex_is_rejected() {
+		assert!(matches!(
+			decode_prefixed_hex("0x00fg10", 3),
+			Err(HashParamError::Hex(_))
+		));
+	}
+
+	#[test]
+	fn addresses_and_hashes_are_sized() {
+		let address = format!("0x{}", "ab".repeat(20));
+		let hash = format!("0x{}", "ab".repeat(32));
+
+		assert!(Hash160::from_param(&address).is_ok());
+		assert!(Hash256::from_param(&hash).is_ok());
+		assert!(matches!(
+			Hash160::from_param(&hash),
+			Err(HashParamError::InvalidLength)
+		));
+		assert!(matches!(
+			Hash256::from_param(&address),
+			Err(HashParamError::InvalidLength)
+		));
+	}
+}
@@ -0,0 +1,59 @@
+use std::{
+	sync::Mutex,
+	time::{Duration, Instant},
+};
+
+// Last decided readiness, and since when the observations disagree with it
+#[derive(Default)]
+struct State {
+	ready: Option<bool>,
+	disagreeing_since: Option<Instant>,
+}
+
+/// Readiness of the api, with hysteresis over how far the database is behind the node
+///
+/// Becomes not ready only once the database has been more than `max_lag` slots behind for
+/// `period`, and ready again once it has been within `max_lag` for `period`. Observations are
+/// only made when readiness is checked, so `period` should be a few times the probe interval.
+/// The first observation is taken as is.
+pub struct Readiness {
+	max_lag: u64,
+	period: Duration,
+	state: Mutex<State>,
+}
+
+impl Readiness {
+	pub fn new(max_lag: u64, period: Duration) -> Self {
+		Readiness {
+			max_lag,
+			period,
+			state: Mutex::new(State::default()),
+		}
+	}
+
+	/// Record that the database is `lag` slots behind the node, and return whether the api is ready
+	pub fn observe(&self, lag: u64) -> bool {
+		let within = lag <= self.max_lag;
+		let mut state = self.state.lock().unwrap();
+
+		let ready = match state.ready {
+			None => within,
+			Some(ready) if ready == within => {
+				state.disagreeing_since = None;
+				ready
+			},
+			Some(ready) => {
+				let since = *state.disagreeing_since.get_or_insert_with(Instant::now);
+				if since.elapsed() >= self.period {
+					state.disagreeing_since = None;
+					within
+				} else {
+					ready
+				}
+			},
+		};
+		state.ready = Some(ready);
+
+		ready
+	}
+}
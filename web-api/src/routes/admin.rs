@@ -0,0 +1,299 @@
+use std::sync::Arc;
+
+use kiln_postgres::{
+	checksum_address, NewMerkleLeaf, NewMerkleTree, NewMintedNft, NewPackedNft, NewReprocessJob,
+	NewStat, PackedNft, PgConnectionPool, QueuedReprocessJob, ReprocessJob, ReprocessLayer,
+	StatKey,
+};
+use log::{info, warn};
+use primitive_types::{H160, H256, U256};
+use rocket::{get, post, serde::json::Json, State};
+use serde::{Deserialize, Serialize};
+
+use super::{
+	compute_packed_nft, computed_below_height, counted_below_height, for_each_eligible_nfts, Cutoff,
+};
+
+use crate::{
+	address_filter::AddressFilter,
+	config::Config,
+	guards::Admin,
+	merkle::{leaf_hash, MerkleTree},
+	packed_nft_types::PackedNftTypes,
+	params::{Hash160, HashParamError},
+	slashed_cache::SlashedCache,
+	Error, PgConn,
+};
+
+#[derive(Deserialize)]
+pub struct ReprocessRequest {
+	from: u64,
+	to: u64,
+	#[serde(default = "default_layer")]
+	layer: ReprocessLayer,
+}
+
+fn default_layer() -> ReprocessLayer {
+	ReprocessLayer::Execution
+}
+
+/// Queue the reprocessing of every height between `from` and `to` included
+///
+/// The indexer picks the job up and overwrites the stored entries.
+/// Only one job can be pending or running at a time. Repeating the request of the active job is
+/// safe: that job is returned as is, so retries do not queue it twice. Any other request is
+/// rejected until it is over.
+#[post("/reprocess", data = "<request>")]
+pub async fn reprocess(
+	_admin: Admin,
+	conn: PgConn,
+	config: &State<Config>,
+	request: Json<ReprocessRequest>,
+) -> Result<Json<ReprocessJob>, Error> {
+	let ReprocessRequest { from, to, layer } = request.into_inner();
+	if from > to || to - from >= config.reprocess_max_range {
+		return Err(Error::InvalidRange)
+	}
+
+	let job = conn
+		.timed_run(move |c| -> Result<_, Error> {
+			match NewReprocessJob::new(layer, from, to).queue(c)? {
+				QueuedReprocessJob::Inserted(id) => {
+					info!("reprocess job #{id} queued: {layer} heights {from} to {to}");
+					// Safe to unwrap because the job was just inserted
+					Ok(ReprocessJob::find(c, id)?.unwrap())
+				},
+				QueuedReprocessJob::Active(job) if job.covers(layer, from, to) => Ok(job),
+				QueuedReprocessJob::Active(_) => Err(Error::ReprocessInProgress),
+			}
+		})
+		.await?;
+
+	Ok(Json(job))
+}
+
+/// Return a reprocess job, along with its progress
+#[get("/reprocess/<id>")]
+pub async fn reprocess_status(
+	_admin: Admin,
+	conn: PgConn,
+	id: i32,
+) -> Result<Option<Json<ReprocessJob>>, Error> {
+	let job = conn.timed_run(move |c| ReprocessJob::find(c, id)).await?;
+
+	Ok(job.map(Json))
+}
+
+/// Recompute the NFTs of `address` and store them in the precomputed table
+///
+/// Return the new value.
+#[post("/nfts/<address>/recompute")]
+pub async fn recompute_nfts(
+	_admin: Admin,
+	conn: PgConn,
+	config: &State<Config>,
+	slashed: &State<Arc<SlashedCache>>,
+	address: Result<Hash160, HashParamError>,
+) -> Result<Json<PackedNftTypes>, Error> {
+	let slashed = slashed.inner().clone();
+	let address: H160 = address.map_err(Error::InvalidAddress)?.into();
+	let cutoff = Cutoff::new(config);
+	let targets = config.target_contracts.clone();
+	let packed_nft = conn
+		.timed_run(move |c| -> Result<_, Error> {
+			slashed.refresh(c)?;
+			let height = counted_below_height(c, cutoff)?;
+			let computed_below = computed_below_height(c, height)?;
+			let packed_nft = compute_packed_nft(c, &slashed, &targets, address, height)?;
+			NewPackedNft::new(address, packed_nft.packed(), computed_below).upsert(c)?;
+			Ok(packed_nft)
+		})
+		.await?;
+	info!("nfts of {address:?} recomputed");
+
+	Ok(Json(packed_nft))
+}
+
+/// Precomputed NFTs of an address that no longer match the computed ones
+#[derive(Serialize)]
+pub struct NftMismatch {
+	/// EIP-55 checksummed
+	address: String,
+	stored: PackedNftTypes,
+	computed: PackedNftTypes,
+}
+
+/// Outcome of a reconciliation of the precomputed NFTs
+#[derive(Serialize)]
+pub struct Reconciliation {
+	/// Number of addresses checked
+	checked: u64,
+	mismatches: Vec<NftMismatch>,
+}
+
+/// Compare the precomputed NFTs of a random sample of addresses with the ones computed now
+///
+/// `sample` addresses are checked, `reconcile_sample_size` by default, every address if 0.
+/// Mismatches are logged and returned, never fixed: `recompute_nfts` does it, address by address.
+#[post("/nfts/reconcile?<sample>")]
+pub async fn reconcile_nfts(
+	_admin: Admin,
+	conn: PgConn,
+	config: &State<Config>,
+	slashed: &State<Arc<SlashedCache>>,
+	sample: Option<u32>,
+) -> Result<Json<Reconciliation>, Error> {
+	let size = match sample.unwrap_or(config.reconcile_sample_size) {
+		0 => None,
+		size => Some(size),
+	};
+	let slashed = slashed.inner().clone();
+	let cutoff = Cutoff::new(config);
+	let targets = config.target_contracts.clone();
+	let reconciliation = conn
+		.timed_run(move |c| -> Result<_, Error> {
+			slashed.refresh(c)?;
+			let height = counted_below_height(c, cutoff)?;
+
+			let stored = PackedNft::sample(c, size)?;
+			let mut mismatches = vec![];
+			for (address, nft) in &stored {
+				let computed = compute_packed_nft(c, &slashed, &targets, *address, height)?;
+				if computed.packed() != *nft {
+					mismatches.push(NftMismatch {
+						address: checksum_address(address),
+						stored: PackedNftTypes::from_packed(*nft),
+						computed,
+					});
+				}
+			}
+
+			Ok(Reconciliation {
+				checked: stored.len() as u64,
+				mismatches,
+			})
+		})
+		.await?;
+
+	for mismatch in &reconciliation.mismatches {
+		warn!(
+			"precomputed nfts of {} are {:#x}, computed {:#x}",
+			mismatch.address,
+			mismatch.stored.packed(),
+			mismatch.computed.packed()
+		);
+	}
+	info!(
+		"reconciled {} precomputed nfts: {} mismatches",
+		reconciliation.checked,
+		reconciliation.mismatches.len()
+	);
+
+	Ok(Json(reconciliation))
+}
+
+/// Record that `address` minted the NFTs of the packed value in the body, a '0x' prefixed hex
+/// string
+///
+/// They are added to the ones already recorded, and masked out of `nfts_by_address` from now on.
+/// Meant to be called by whatever watches the claims on chain. Return every NFT recorded as
+/// minted by the address.
+#[post("/nfts/<address>/minted", data = "<minted>")]
+pub async fn mark_nfts_minted(
+	_admin: Admin,
+	conn: PgConn,
+	address: Result<Hash160, HashParamError>,
+	minted: Json<U256>,
+) -> Result<Json<PackedNftTypes>, Error> {
+	let address: H160 = address.map_err(Error::InvalidAddress)?.into();
+	let minted = minted.into_inner();
+	let all_minted = conn.timed_run(move |c| NewMintedNft::add(c, address, minted)).await?;
+	info!("nfts {minted:#x} of {address:?} marked as minted");
+
+	Ok(Json(PackedNftTypes::from_packed(all_minted)))
+}
+
+/// Root of a Merkle tree of the packed NFTs
+#[derive(Serialize)]
+pub struct MerkleRoot {
+	root: H256,
+	leaves_count: u64,
+}
+
+/// Build the Merkle tree of the NFTs of every issuer, and store it along with the proofs
+///
+/// Issuers eligible to no NFT are left out, and denied ones are not listed. The tree replaces
+/// the stored one at once: its root must then be set in the verifying contract, the proofs of
+/// the former tree are no longer served.
+/// Return the root, see `crate::merkle` for the encoding.
+#[post("/nfts/merkle")]
+pub async fn build_nfts_merkle_tree(
+	_admin: Admin,
+	conn: PgConn,
+	config: &State<Config>,
+	filter: &State<Arc<AddressFilter>>,
+	pool: &State<PgConnectionPool>,
+	slashed: &State<Arc<SlashedCache>>,
+) -> Result<Json<MerkleRoot>, Error> {
+	// Every leaf is needed to build the tree, only the ones eligible to some NFT are kept
+	let mut eligible = vec![];
+	for_each_eligible_nfts(
+		&conn,
+		Cutoff::new(config),
+		filter,
+		pool,
+		slashed,
+		&config.target_contracts,
+		|chunk| eligible.extend(chunk.into_iter().filter(|(_, nft)| !nft.packed().is_zero())),
+	)
+	.await?;
+
+	let tree = MerkleTree::new(
+		eligible
+			.iter()
+			.map(|(address, nft)| leaf_hash(*address, nft.packed()))
+			.collect(),
+	);
+	let root = H256::from(tree.root());
+	let leaves = eligible
+		.iter()
+		.enumerate()
+		.map(|(index, (address, nft))| {
+			let proof: Vec<H256> = tree.proof(index).into_iter().map(H256::from).collect();
+			NewMerkleLeaf::new(*address, nft.packed(), index as u64, &proof)
+		})
+		.collect();
+	let leaves_count = eligible.len() as u64;
+
+	conn.timed_run(move |c| NewMerkleTree::new(root, leaves).replace(c)).await?;
+	info!("nfts merkle tree built: root {root:?}, {leaves_count} leaves");
+
+	Ok(Json(MerkleRoot { root, leaves_count }))
+}
+
+/// Whether the indexer syncs
+#[derive(Serialize)]
+pub struct SyncState {
+	paused: bool,
+}
+
+/// Pause the indexer, e.g. for a maintenance
+///
+/// The indexer finishes the heights it is syncing, then idles until resumed. Reprocess jobs and
+/// backfills are paused too.
+#[post("/sync/pause")]
+pub async fn pause_sync(_admin: Admin, conn: PgConn) -> Result<Json<SyncState>, Error> {
+	conn.timed_run(|c| NewStat::new(StatKey::SyncPaused, 1).upsert(c)).await?;
+	info!("sync paused");
+
+	Ok(Json(SyncState { paused: true }))
+}
+
+/// Resume the indexer paused by `pause_sync`
+#[post("/sync/resume")]
+pub async fn resume_sync(_admin: Admin, conn: PgConn) -> Result<Json<SyncState>, Error> {
+	conn.timed_run(|c| NewStat::new(StatKey::SyncPaused, 0).upsert(c)).await?;
+	info!("sync resumed");
+
+	Ok(Json(SyncState { paused: false }))
+}
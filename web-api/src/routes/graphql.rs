@@ -0,0 +1,17 @@
+use async_graphql_rocket::{GraphQLRequest, GraphQLResponse};
+use rocket::{post, State};
+
+use crate::{graphql::ApiSchema, PgConn};
+
+/// Run a GraphQL query, see `graphql::schema`
+///
+/// Gathers the NFTs, latest transactions and validator status of an address in a single
+/// request. Errors are answered in the GraphQL response, with a 200.
+#[post("/graphql", data = "<request>")]
+pub async fn graphql_query(
+	conn: PgConn,
+	schema: &State<ApiSchema>,
+	request: GraphQLRequest,
+) -> GraphQLResponse {
+	request.data(conn).execute(schema.inner()).await
+}
@@ -0,0 +1,37 @@
+use std::sync::Arc;
+
+use rocket::{
+	get,
+	response::stream::{Event, EventStream},
+	Shutdown, State,
+};
+use tokio::{select, sync::broadcast::error::RecvError};
+
+use crate::head::HeadWatcher;
+
+/// Stream the sync heads as server-sent `head` events, every time they change
+///
+/// The current heads are sent as soon as the client connects.
+/// This version of Rocket has no WebSocket support, server-sent events fill the same role.
+#[get("/head/events")]
+pub fn head_events(watcher: &State<Arc<HeadWatcher>>, mut shutdown: Shutdown) -> EventStream![] {
+	let (current, mut receiver) = watcher.subscribe();
+
+	EventStream! {
+		yield Event::json(&current).event("head");
+
+		loop {
+			let head = select! {
+				message = receiver.recv() => match message {
+					Ok(h) => h,
+					Err(RecvError::Closed) => break,
+					// Slow clients skip the heads they missed and resume with the oldest kept one
+					Err(RecvError::Lagged(_)) => continue,
+				},
+				_ = &mut shutdown => break,
+			};
+
+			yield Event::json(&head).event("head");
+		}
+	}
+}
@@ -0,0 +1,53 @@
+use kiln_postgres::{PgConnectionPool, Stat, StatKey};
+use rocket::{get, http::Status, State};
+use rocket_sync_db_pools::diesel::connection::SimpleConnection;
+
+use crate::readiness::Readiness;
+
+/// Connections reserved to the health check
+pub struct HealthPool(pub PgConnectionPool);
+
+/// Answer 200 if the database answers, 503 otherwise
+///
+/// Uses its own pool, so it is not starved by heavy requests.
+/// The connection is not retried, so an unreachable database is reported right away.
+#[get("/health")]
+pub async fn health(pool: &State<HealthPool>) -> Status {
+	let pool = pool.0.clone();
+	let healthy = rocket::tokio::task::spawn_blocking(move || {
+		pool.get().map_or(false, |c| c.batch_execute("SELECT 1").is_ok())
+	})
+	.await
+	.unwrap_or(false);
+
+	if healthy {
+		Status::Ok
+	} else {
+		Status::ServiceUnavailable
+	}
+}
+
+/// Answer 200 if the database is close enough to the node head, 503 otherwise
+///
+/// The lag is the number of slots between the consensus head of the node and of the database, as
+/// published by the indexer. See `Readiness` for the hysteresis applied.
+/// Uses the health check pool.
+#[get("/ready")]
+pub async fn ready(pool: &State<HealthPool>, readiness: &State<Readiness>) -> Status {
+	let pool = pool.0.clone();
+	let lag = rocket::tokio::task::spawn_blocking(move || {
+		let conn = pool.get().ok()?;
+		let node = Stat::get(&conn, StatKey::ConsensusNodeHeight).ok()??;
+		let db = Stat::get(&conn, StatKey::ConsensusDbHeight).ok()??;
+		Some(node.saturating_sub(db))
+	})
+	.await
+	.ok()
+	.flatten();
+
+	// Without heads, there is nothing to serve yet
+	match lag {
+		Some(lag) if readiness.observe(lag) => Status::Ok,
+		_ => Status::ServiceUnavailable,
+	}
+}
@@ -0,0 +1,38 @@
+use kiln_postgres::{ActivityBucket, Transaction};
+use primitive_types::H160;
+use rocket::{get, serde::json::Json, State};
+
+use crate::{
+	config::Config,
+	params::{Hash160, HashParamError},
+	Error, PgConn,
+};
+
+/// Default number of blocks per bucket
+const DEFAULT_BUCKET_SIZE: u64 = 1000;
+
+/// Return the number of transactions issued by `address` in every range of `bucket_size` blocks
+///
+/// Each bucket is identified by the number of its first block. Empty buckets are omitted.
+#[get("/address/<address>/histogram?<bucket_size>")]
+pub async fn activity_histogram(
+	conn: PgConn,
+	config: &State<Config>,
+	address: Result<Hash160, HashParamError>,
+	bucket_size: Option<u64>,
+) -> Result<Json<Vec<ActivityBucket>>, Error> {
+	let address: H160 = address.map_err(Error::InvalidAddress)?.into();
+	let bucket_size = bucket_size.unwrap_or(DEFAULT_BUCKET_SIZE);
+	if bucket_size == 0 {
+		return Err(Error::InvalidBucketSize)
+	}
+
+	let max_buckets = config.histogram_max_buckets;
+	let buckets = conn
+		.timed_run(move |c| {
+			Transaction::activity_histogram_from_address(c, address, bucket_size, max_buckets)
+		})
+		.await?;
+
+	Ok(Json(buckets))
+}
@@ -0,0 +1,13 @@
+use prometheus::{Encoder, TextEncoder};
+use rocket::get;
+
+use crate::Error;
+
+/// Return the Prometheus metrics in the text exposition format
+#[get("/metrics")]
+pub fn metrics() -> Result<String, Error> {
+	let mut body = vec![];
+	TextEncoder::new().encode(&prometheus::gather(), &mut body)?;
+
+	Ok(String::from_utf8_lossy(&body).into_owned())
+}
@@ -1,3 +1,27 @@
+mod admin;
+mod graphql;
+mod head;
+mod health;
+mod histogram;
+mod metrics;
+mod openapi;
 mod packed_nft;
+mod proposals;
+mod search;
+mod slots;
+mod transactions;
+mod validators;
 
+pub(crate) use admin::*;
+pub(crate) use graphql::*;
+pub(crate) use head::*;
+pub(crate) use health::*;
+pub(crate) use histogram::*;
+pub(crate) use metrics::*;
+pub(crate) use openapi::*;
 pub(crate) use packed_nft::*;
+pub(crate) use proposals::*;
+pub(crate) use search::*;
+pub(crate) use slots::*;
+pub(crate) use transactions::*;
+pub(crate) use validators::*;
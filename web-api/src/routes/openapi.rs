@@ -0,0 +1,15 @@
+use rocket::{
+	get,
+	serde::json::{Json, Value},
+	Orbit, Rocket,
+};
+
+use crate::openapi;
+
+/// Return the OpenAPI 3.0 description of the api
+///
+/// Generated from the mounted routes, see `openapi::document`.
+#[get("/openapi.json")]
+pub fn openapi_document(rocket: &Rocket<Orbit>) -> Json<Value> {
+	Json(openapi::document(rocket.routes()))
+}
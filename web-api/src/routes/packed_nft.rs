@@ -1,125 +1,1320 @@
-use std::{collections::HashMap, ops::Deref};
+use std::{
+	collections::HashMap,
+	ops::Deref,
+	sync::Arc,
+	time::{SystemTime, UNIX_EPOCH},
+};
 
-use kiln_postgres::{Transaction, Validator};
-use log::info;
-use primitive_types::H160;
-use rocket::{get, serde::json::Json};
-use rocket_sync_db_pools::{
-	diesel::{self, r2d2::ConnectionManager, PgConnection},
-	r2d2::Pool,
+use kiln_postgres::{
+	checksum_address, count_statements, serialize_checksummed, DbConnection, MerkleLeaf, MintedNft,
+	NewPackedNft, PackedNft, PgConnectionPool, Stat, StatKey, Transaction, Validator,
 };
+use log::info;
+use primitive_types::{H160, H256, U256};
+use rocket::{get, post, serde::json::Json, State};
+use rocket_sync_db_pools::diesel;
+use schemars::JsonSchema;
 use serde::Serialize;
 
-use crate::{packed_nft_types::PackedNftTypes, params::Hash160, Error, PgConn};
+use crate::{
+	activity_score::compute_activity_score,
+	address_filter::AddressFilter,
+	cached::Cached,
+	claims::{ClaimDomain, ClaimSigner},
+	config::Config,
+	fairings::DbStatements,
+	guards::{ExplainedAddresses, ExplainedAddressesError, IfNoneMatch},
+	json::{page_len, ListResponse},
+	merkle::leaf_hash,
+	packed_nft_types::{NftType, PackedNftTypes},
+	params::{Hash160, HashParamError},
+	slashed_cache::SlashedCache,
+	Error, PgConn,
+};
+
+/// Nonce above which an address is considered an active account
+const ACTIVE_ACCOUNT_NONCE_THRESHOLD: u64 = 100;
+
+/// Number of transactions required for the highest transactions count NFT
+const TRANSACTIONS_COUNT_THRESHOLD: u64 = 100;
+
+/// Number of calls to a contract for it to count as called repeatedly
+const CONTRACT_CALLS_THRESHOLD: u64 = 10;
+
+/// Number of contracts to call repeatedly
+const CALLED_CONTRACTS_THRESHOLD: u64 = 10;
+
+/// Number of distinct days of activity required for the active streak
+const ACTIVE_STREAK_MIN_DAYS: i64 = 7;
+
+/// EIP-2718 type of the EIP-1559 transactions
+const EIP1559_TX_TYPE: u64 = 2;
+
+/// Number of issuers loaded at once when listing all of them
+const ISSUERS_CHUNK_SIZE: u32 = 1000;
 
 /// Return the packed list of NFTs this address is eligible to mint
-#[get("/address/<address>/nfts")]
+///
+/// NFTs the address already minted are masked out, so they are not offered again. See
+/// `nfts_claim_state` for both the eligible and the minted ones.
+///
+/// With `precomputed_nfts`, the stored value is served. An address without one, or which issued
+/// transactions counting now that it was not computed from, gets it computed and stored.
+/// Validator changes are not followed, `recompute_nfts` stores them.
+///
+/// Set `as_of` to only count the transactions in blocks up to this number, e.g. for a snapshot.
+/// Those are always computed. Validators are only known in their current state, so the validator
+/// NFTs are not affected.
+///
+/// The response carries an `ETag`, and is a bodyless 304 if it matches `If-None-Match`.
+/// Computed NFTs are tagged with what they are computed from, so they are not computed again
+/// until the address issues a new transaction, mints or a validator changes. Stored ones are
+/// tagged with their value.
+#[get("/address/<address>/nfts?<as_of>")]
 pub async fn nfts_by_address(
 	conn: PgConn,
-	address: Hash160,
-) -> Result<Json<PackedNftTypes>, Error> {
-	let packed_nft = conn.run(move |c| inner_get_packed_nft(c, address.into())).await?;
+	config: &State<Config>,
+	filter: &State<Arc<AddressFilter>>,
+	slashed: &State<Arc<SlashedCache>>,
+	if_none_match: IfNoneMatch,
+	address: Result<Hash160, HashParamError>,
+	as_of: Option<u64>,
+) -> Result<Cached<Json<PackedNftTypes>>, Error> {
+	let filter = filter.inner().clone();
+	let slashed = slashed.inner().clone();
+	let address: H160 = address.map_err(Error::InvalidAddress)?.into();
+	let cutoff = Cutoff::new(config);
+	let targets = config.target_contracts.clone();
+	let precomputed_nfts = config.precomputed_nfts;
+	let packed_nft = conn
+		.timed_run(move |c| -> Result<_, Error> {
+			let height = lowest_height(counted_below_height(c, cutoff)?, as_of);
+			let minted = MintedNft::get(c, address)?;
+
+			if !precomputed_nfts || as_of.is_some() {
+				let etag = computed_etag(c, &filter, address, height, minted)?;
+				if if_none_match.matches(&etag) {
+					return Ok(Cached::NotModified(etag))
+				}
+
+				slashed.refresh(c)?;
+				let packed_nft =
+					inner_get_packed_nft(c, &filter, &slashed, &targets, address, height)?;
+				return Ok(Cached::Fresh(etag, packed_nft.unclaimed(minted)))
+			}
+
+			let packed_nft = if !filter.is_allowed(&address) {
+				PackedNftTypes::zero()
+			} else if let Some(packed) = fresh_packed_nft(c, address, height)? {
+				PackedNftTypes::from_packed(packed)
+			} else {
+				let computed_below = computed_below_height(c, height)?;
+				slashed.refresh(c)?;
+				let packed_nft = compute_packed_nft(c, &slashed, &targets, address, height)?;
+				NewPackedNft::new(address, packed_nft.packed(), computed_below).upsert(c)?;
+				packed_nft
+			}
+			.unclaimed(minted);
+
+			Ok(Cached::new(
+				stored_etag(&packed_nft),
+				packed_nft,
+				&if_none_match,
+			))
+		})
+		.await?;
+
+	Ok(packed_nft.map(Json))
+}
+
+// Return the highest block whose transactions count towards NFTs computed now
+//
+// That is `finalized_below_height`, or the highest indexed block if every transaction counts.
+// Read before computing, so a block indexed meanwhile is never taken as counted.
+pub(crate) fn computed_below_height(
+	conn: &DbConnection,
+	finalized_below_height: Option<u64>,
+) -> Result<u64, Error> {
+	match finalized_below_height {
+		Some(height) => Ok(height),
+		None => Ok(Stat::get(conn, StatKey::ExecutionDbHeight)?.unwrap_or(0)),
+	}
+}
+
+// Return the NFTs precomputed for `address`, unless stale
+//
+// They are stale if the address issued a transaction counting below `finalized_below_height`
+// that they were not computed from, or if it is unknown what they were computed from.
+pub(crate) fn fresh_packed_nft(
+	conn: &DbConnection,
+	address: H160,
+	finalized_below_height: Option<u64>,
+) -> Result<Option<U256>, Error> {
+	let stored = match PackedNft::get(conn, address)? {
+		Some(stored) => stored,
+		None => return Ok(None),
+	};
+	let computed_below = match stored.computed_below_height {
+		Some(height) => height,
+		None => return Ok(None),
+	};
+
+	let last_block =
+		Transaction::last_block_number_from_address(conn, address, finalized_below_height)?;
+
+	Ok(match last_block {
+		Some(block) if block > computed_below => None,
+		_ => Some(stored.nft),
+	})
+}
+
+// Return a tag of everything the NFTs of `address` are computed from
+//
+// That is its last transaction below `finalized_below_height`, the validators generation,
+// whether the address is allowed, and the NFTs it `minted`.
+fn computed_etag(
+	conn: &DbConnection,
+	filter: &AddressFilter,
+	address: H160,
+	finalized_below_height: Option<u64>,
+	minted: U256,
+) -> Result<String, Error> {
+	let last_block =
+		Transaction::last_block_number_from_address(conn, address, finalized_below_height)?;
+	let generation = Stat::get(conn, StatKey::ValidatorsGeneration)?;
 
-	Ok(Json(packed_nft))
+	Ok(computed_etag_of(
+		last_block,
+		generation,
+		filter.is_allowed(&address),
+		minted,
+	))
 }
 
+fn computed_etag_of(
+	last_block: Option<u64>,
+	generation: Option<u64>,
+	allowed: bool,
+	minted: U256,
+) -> String {
+	format!(
+		r#"W/"{}-{}-{}-{:x}""#,
+		last_block.map_or_else(|| "none".to_string(), |b| b.to_string()),
+		generation.unwrap_or(0),
+		allowed as u8,
+		minted,
+	)
+}
+
+// Return a tag of the stored NFTs `packed_nft`
+fn stored_etag(packed_nft: &PackedNftTypes) -> String {
+	format!(r#"W/"{:x}""#, packed_nft.packed())
+}
+
+/// NFTs an address is eligible to, and which of them it already minted
 #[derive(Serialize)]
+pub struct NftClaimState {
+	eligible: PackedNftTypes,
+	minted: PackedNftTypes,
+	/// Eligible but not minted yet, what `nfts_by_address` returns
+	unclaimed: PackedNftTypes,
+}
+
+/// Return the NFTs `address` is eligible to, the ones it minted, and the ones left to claim
+///
+/// Always computed. Minted NFTs are recorded with `mark_nfts_minted`, they can include some the
+/// address is no longer eligible to.
+#[get("/address/<address>/nfts/claim-state")]
+pub async fn nfts_claim_state(
+	conn: PgConn,
+	config: &State<Config>,
+	filter: &State<Arc<AddressFilter>>,
+	slashed: &State<Arc<SlashedCache>>,
+	address: Result<Hash160, HashParamError>,
+) -> Result<Json<NftClaimState>, Error> {
+	let filter = filter.inner().clone();
+	let slashed = slashed.inner().clone();
+	let address: H160 = address.map_err(Error::InvalidAddress)?.into();
+	let cutoff = Cutoff::new(config);
+	let targets = config.target_contracts.clone();
+	let (eligible, minted) = conn
+		.timed_run(move |c| -> Result<_, Error> {
+			slashed.refresh(c)?;
+			let height = counted_below_height(c, cutoff)?;
+			let eligible = inner_get_packed_nft(c, &filter, &slashed, &targets, address, height)?;
+			Ok((eligible, MintedNft::get(c, address)?))
+		})
+		.await?;
+
+	Ok(Json(NftClaimState {
+		unclaimed: eligible.unclaimed(minted),
+		eligible,
+		minted: PackedNftTypes::from_packed(minted),
+	}))
+}
+
+/// Activity score of an address
+#[derive(Serialize, JsonSchema)]
+pub struct ActivityScore {
+	/// Between 0 and 100, see `activity_score::compute_activity_score`
+	score: u8,
+}
+
+/// Return the activity score of `address`, from what its NFTs are computed from
+///
+/// Always computed, with the same transactions counted as for the NFTs. Denied addresses score
+/// 0.
+#[get("/address/<address>/score")]
+pub async fn address_activity_score(
+	conn: PgConn,
+	config: &State<Config>,
+	filter: &State<Arc<AddressFilter>>,
+	slashed: &State<Arc<SlashedCache>>,
+	address: Result<Hash160, HashParamError>,
+) -> Result<Json<ActivityScore>, Error> {
+	let address: H160 = address.map_err(Error::InvalidAddress)?.into();
+	if !filter.is_allowed(&address) {
+		return Ok(Json(ActivityScore { score: 0 }))
+	}
+
+	let slashed = slashed.inner().clone();
+	let cutoff = Cutoff::new(config);
+	let targets = config.target_contracts.clone();
+	let metrics = conn
+		.timed_run(move |c| -> Result<_, Error> {
+			slashed.refresh(c)?;
+			let height = counted_below_height(c, cutoff)?;
+			activity_metrics(c, &slashed, &targets, address, height)
+		})
+		.await?;
+
+	Ok(Json(ActivityScore {
+		score: compute_activity_score(&metrics),
+	}))
+}
+
+/// NFTs of an address, along with what they are computed from
+#[derive(Serialize, JsonSchema)]
+pub struct NftExplanation {
+	/// '0x' prefixed EIP-55 checksummed address
+	#[serde(serialize_with = "serialize_checksummed")]
+	#[schemars(with = "String")]
+	address: H160,
+	/// NFTs the address is eligible to, minted ones included
+	eligible: PackedNftTypes,
+	/// Activity score, see `ActivityScore`
+	score: u8,
+	/// None for denied addresses, eligible to nothing
+	metrics: Option<ActivityMetrics>,
+}
+
+/// Return the NFTs of every address of the body, a JSON array of '0x' prefixed hex addresses,
+/// along with what they are computed from
+///
+/// Always computed, with the same transactions counted as for the NFTs. Addresses are
+/// deduplicated and answered in the order they are first given. At most
+/// `explain_max_addresses` distinct ones can be looked up at once, see `ExplainedAddresses`.
+#[post("/nfts/explain", data = "<addresses>")]
+pub async fn explain_nfts(
+	conn: PgConn,
+	config: &State<Config>,
+	filter: &State<Arc<AddressFilter>>,
+	addresses: Result<ExplainedAddresses, ExplainedAddressesError>,
+) -> Result<Json<Vec<NftExplanation>>, Error> {
+	let ExplainedAddresses(addresses) = addresses?;
+
+	let allowed: Vec<H160> = addresses.iter().copied().filter(|a| filter.is_allowed(a)).collect();
+	let cutoff = Cutoff::new(config);
+	let targets = config.target_contracts.clone();
+	let queried = allowed.clone();
+	let metrics = conn
+		.timed_run(move |c| -> Result<_, Error> {
+			let height = counted_below_height(c, cutoff)?;
+			activity_metrics_for(c, &targets, &queried, height)
+		})
+		.await?;
+	let mut by_address: HashMap<H160, ActivityMetrics> = allowed.into_iter().zip(metrics).collect();
+
+	Ok(Json(
+		addresses
+			.into_iter()
+			.map(|address| match by_address.remove(&address) {
+				Some(metrics) => NftExplanation {
+					address,
+					eligible: packed_nft_from_metrics(&metrics),
+					score: compute_activity_score(&metrics),
+					metrics: Some(metrics),
+				},
+				None => NftExplanation {
+					address,
+					eligible: PackedNftTypes::zero(),
+					score: 0,
+					metrics: None,
+				},
+			})
+			.collect(),
+	))
+}
+
+/// NFTs an address is eligible to, signed for a contract to verify them on mint
+#[derive(Serialize)]
+pub struct NftClaim {
+	/// '0x' prefixed EIP-55 checksummed address
+	#[serde(serialize_with = "serialize_checksummed")]
+	address: H160,
+	/// '0x' prefixed hex of the packed NFTs integer
+	nft: U256,
+	/// '0x' prefixed hex of the 65 bytes `r || s || v` EIP-712 signature
+	signature: String,
+	/// '0x' prefixed EIP-55 checksummed address of the signing key
+	#[serde(serialize_with = "serialize_checksummed")]
+	signer: H160,
+	/// Seconds since the UNIX epoch at which the claim was signed
+	issued_at: u64,
+	/// Seconds since the UNIX epoch after which the claim must be rejected
+	expires_at: u64,
+	domain: ClaimDomain,
+}
+
+/// Return the packed NFTs `address` is eligible to, along with their EIP-712 signature
+///
+/// The signed typed data is `Claim(address account,uint256 packed,uint64 issuedAt,uint64
+/// expiresAt)`, for the returned domain.
+/// Answer 404 if no signing key is configured.
+#[get("/address/<address>/nfts/signature")]
+pub async fn nfts_claim_signature(
+	conn: PgConn,
+	config: &State<Config>,
+	filter: &State<Arc<AddressFilter>>,
+	slashed: &State<Arc<SlashedCache>>,
+	signer: &State<Option<ClaimSigner>>,
+	address: Result<Hash160, HashParamError>,
+) -> Result<Json<NftClaim>, Error> {
+	let signer = signer.inner().as_ref().ok_or(Error::ClaimsDisabled)?;
+
+	let filter = filter.inner().clone();
+	let slashed = slashed.inner().clone();
+	let address: H160 = address.map_err(Error::InvalidAddress)?.into();
+	let cutoff = Cutoff::new(config);
+	let targets = config.target_contracts.clone();
+	// Always computed, a signature must not be issued for a stale precomputed value
+	let packed_nft = conn
+		.timed_run(move |c| -> Result<_, Error> {
+			slashed.refresh(c)?;
+			let height = counted_below_height(c, cutoff)?;
+			inner_get_packed_nft(c, &filter, &slashed, &targets, address, height)
+		})
+		.await?;
+
+	// Safe to unwrap because the clock is past the UNIX epoch
+	let issued_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+	let expires_at = issued_at + config.claim_validity;
+	let signature = signer.sign(address, packed_nft.packed(), issued_at, expires_at);
+
+	Ok(Json(NftClaim {
+		address,
+		nft: packed_nft.packed(),
+		signature: format!("0x{}", hex::encode(signature)),
+		signer: signer.signer(),
+		issued_at,
+		expires_at,
+		domain: signer.domain().clone(),
+	}))
+}
+
+/// Leaf of an address in the Merkle tree of the packed NFTs, along with its proof
+#[derive(Serialize)]
+pub struct NftProof {
+	/// '0x' prefixed EIP-55 checksummed address
+	#[serde(serialize_with = "serialize_checksummed")]
+	address: H160,
+	/// '0x' prefixed hex of the packed NFTs integer
+	nft: U256,
+	/// `keccak256(abi.encodePacked(address account, uint256 packed))`
+	leaf: H256,
+	/// Position of the leaf in the tree, the issuers being sorted by address
+	leaf_index: u64,
+	/// Sibling hashes, from the leaf level up
+	proof: Vec<H256>,
+	root: H256,
+}
+
+/// Return the leaf of `address` in the last Merkle tree built, along with its proof
+///
+/// Trees are built by `POST /admin/nfts/merkle`, see `crate::merkle` for their encoding. The
+/// proof is the one of the NFTs `address` was eligible to then, not of its current ones.
+/// Answer 404 if no tree was built, or the address is not part of it because it was not eligible
+/// to any NFT.
+#[get("/address/<address>/nfts/proof")]
+pub async fn nfts_merkle_proof(
+	conn: PgConn,
+	address: Result<Hash160, HashParamError>,
+) -> Result<Option<Json<NftProof>>, Error> {
+	let address: H160 = address.map_err(Error::InvalidAddress)?.into();
+	let leaf = conn.timed_run(move |c| MerkleLeaf::get(c, address)).await?;
+
+	Ok(leaf.map(|leaf| {
+		Json(NftProof {
+			address,
+			nft: leaf.nft,
+			leaf: leaf_hash(address, leaf.nft).into(),
+			leaf_index: leaf.leaf_index,
+			proof: leaf.proof,
+			root: leaf.root,
+		})
+	}))
+}
+
+/// Return the number of distinct issuers, whatever the address filter
+///
+/// Read from the count maintained by the indexer. Counted from the transactions if the indexer
+/// did not publish it yet.
+#[get("/nfts/count")]
+pub async fn issuers_count(conn: PgConn) -> Result<Json<u64>, Error> {
+	let count = conn
+		.timed_run(|c| match Stat::get(c, StatKey::IssuersCount)? {
+			Some(count) => Ok(count),
+			None => Transaction::count_distinct_issuers(c),
+		})
+		.await?;
+
+	Ok(Json(count))
+}
+
+/// Figures of the whole indexed dataset
+#[derive(Serialize, JsonSchema)]
+pub struct NftStats {
+	/// Number of distinct issuers, as returned by `/nfts/count`
+	issuers: u64,
+	/// Number of contract deployments
+	deployed_contracts: u64,
+}
+
+/// Return figures of the whole indexed dataset, whatever the address filter
+#[get("/nfts/stats")]
+pub async fn nft_stats(conn: PgConn) -> Result<Json<NftStats>, Error> {
+	let stats = conn
+		.timed_run(|c| -> Result<_, diesel::result::Error> {
+			let issuers = match Stat::get(c, StatKey::IssuersCount)? {
+				Some(count) => count,
+				None => Transaction::count_distinct_issuers(c)?,
+			};
+
+			Ok(NftStats {
+				issuers,
+				deployed_contracts: Transaction::total_deployed_contracts(c)?,
+			})
+		})
+		.await?;
+
+	Ok(Json(stats))
+}
+
+/// Return the addresses eligible to exactly the NFTs of `mask`, ordered by address
+///
+/// `mask` is a packed representation, '0x' prefixed hex. Only the addresses whose NFTs are
+/// precomputed are looked up, so those never requested nor recomputed are missing. Answer 404
+/// unless NFTs are precomputed. `offset` and `limit` select a page.
+#[get("/nfts/by-mask/<mask>?<offset>&<limit>")]
+pub async fn addresses_by_nft_mask(
+	conn: PgConn,
+	config: &State<Config>,
+	filter: &State<Arc<AddressFilter>>,
+	mask: &str,
+	offset: Option<u64>,
+	limit: Option<u32>,
+) -> Result<Json<Vec<String>>, Error> {
+	if !config.precomputed_nfts {
+		return Err(Error::PrecomputedNftsDisabled)
+	}
+	let mask = mask
+		.strip_prefix("0x")
+		.filter(|hex| !hex.is_empty() && hex.len() <= 64)
+		.and_then(|hex| U256::from_str_radix(hex, 16).ok())
+		.and_then(PackedNftTypes::from_mask)
+		.ok_or(Error::InvalidMask)?;
+
+	let (offset, limit) = (
+		offset.unwrap_or(0),
+		limit.unwrap_or(config.mask_max_results).min(config.mask_max_results),
+	);
+	let addresses = conn
+		.timed_run(move |c| PackedNft::addresses_with(c, mask.packed(), offset, limit))
+		.await?;
+
+	// Denied addresses are served no NFTs, they match no mask
+	Ok(Json(
+		addresses
+			.iter()
+			.filter(|address| filter.is_allowed(address))
+			.map(checksum_address)
+			.collect(),
+	))
+}
+
+/// Return every kind of NFT, by position in the packed representation
+#[get("/nfts/catalog")]
+pub fn nft_catalog() -> Json<&'static [NftType]> {
+	Json(PackedNftTypes::NFT_TYPES)
+}
+
+/// An address and the NFTs it is eligible to mint
+///
+/// The JSON shape is part of the api, fields are serialized in this order:
+/// * `address`: '0x' prefixed EIP-55 checksummed address
+/// * `nft`: '0x' prefixed hex of the packed NFTs integer
+/// * `nft_names`: names of the NFTs set, by position. Only present if requested
+#[derive(Serialize, JsonSchema)]
 pub struct AddressNftPair {
+	#[serde(serialize_with = "serialize_checksummed")]
+	#[schemars(with = "String")]
 	address: H160,
-	nft: PackedNftTypes,
+	#[schemars(with = "String")]
+	nft: U256,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	nft_names: Option<Vec<&'static str>>,
 }
 
-#[get("/nfts")]
-pub async fn list_all_eligible_nft(conn: PgConn) -> Result<Json<Vec<AddressNftPair>>, Error> {
-	use rayon::prelude::*;
+impl AddressNftPair {
+	fn new(address: H160, nft: PackedNftTypes, with_names: bool) -> Self {
+		AddressNftPair {
+			address,
+			nft: nft.packed(),
+			nft_names: with_names.then(|| nft.names()),
+		}
+	}
+}
+
+/// Return every issuer along with the NFTs it is eligible to mint
+///
+/// Set `names` to true to also get the name of the NFTs. Issuers are sorted by address, `offset`
+/// and `limit` select a page of them, only its NFTs are computed. Pages are capped to
+/// `list_max_items` issuers, larger ones are rejected before computing any. The list is a bare
+/// array unless `envelope` is true, see `ListResponse`.
+#[get("/nfts?<names>&<envelope>&<offset>&<limit>")]
+pub async fn list_all_eligible_nft(
+	conn: PgConn,
+	config: &State<Config>,
+	filter: &State<Arc<AddressFilter>>,
+	pool: &State<PgConnectionPool>,
+	slashed: &State<Arc<SlashedCache>>,
+	names: Option<bool>,
+	envelope: Option<bool>,
+	offset: Option<u64>,
+	limit: Option<u64>,
+) -> Result<Json<ListResponse<AddressNftPair>>, Error> {
+	let with_names = names.unwrap_or(false);
+	let issuer_filter = Arc::new(filter.issuer_filter());
+
+	let counted = issuer_filter.clone();
+	let total = conn
+		.timed_run(move |c| -> Result<_, diesel::result::Error> {
+			// Denied addresses are not listed at all, the maintained count includes them
+			if counted.is_empty() {
+				if let Some(count) = Stat::get(c, StatKey::IssuersCount)? {
+					return Ok(count)
+				}
+			}
+			Transaction::count_issuers(c, &counted)
+		})
+		.await?;
+	let len = page_len(total, offset, limit, config.list_max_items)?;
+
+	let height = refreshed_counted_below_height(&conn, Cutoff::new(config), slashed).await?;
+	let issuers = conn
+		.timed_run(move |c| {
+			let len = len.min(u32::MAX as u64) as u32;
+			Transaction::issuers_after(c, &issuer_filter, None, offset.unwrap_or(0), len)
+		})
+		.await?;
+	let pairs: Vec<_> = eligible_nfts_of(
+		pool,
+		conn.statements(),
+		filter,
+		slashed,
+		&config.target_contracts,
+		&issuers,
+		height,
+	)?
+	.into_iter()
+	.map(|(issuer, nft)| AddressNftPair::new(issuer, nft, with_names))
+	.collect();
+
+	Ok(Json(ListResponse::page(
+		pairs,
+		total,
+		offset,
+		limit,
+		envelope.unwrap_or(false),
+	)))
+}
+
+// Walk every allowed issuer along with the NFTs it is eligible to, sorted by address
+//
+// Issuers are handed to `on_chunk` `ISSUERS_CHUNK_SIZE` at a time, so they are never all loaded
+// at once.
+pub(crate) async fn for_each_eligible_nfts<F>(
+	conn: &PgConn,
+	cutoff: Cutoff,
+	filter: &AddressFilter,
+	pool: &PgConnectionPool,
+	slashed: &Arc<SlashedCache>,
+	targets: &[H160],
+	mut on_chunk: F,
+) -> Result<(), Error>
+where
+	F: FnMut(Vec<(H160, PackedNftTypes)>),
+{
+	// Same cutoff for every issuer, even if a new block is finalized meanwhile
+	let height = refreshed_counted_below_height(conn, cutoff, slashed).await?;
 
-	let issuers = conn.run(move |c| Transaction::list_all_distinct_issuer(c)).await?;
-	info!("{} issuers to query", issuers.len());
+	let issuer_filter = Arc::new(filter.issuer_filter());
+	let mut after = None;
+	loop {
+		let chunk_filter = issuer_filter.clone();
+		let issuers = conn
+			.timed_run(move |c| {
+				Transaction::issuers_after(c, &chunk_filter, after, 0, ISSUERS_CHUNK_SIZE)
+			})
+			.await?;
+		after = match issuers.last() {
+			Some(last) => Some(*last),
+			None => break,
+		};
+		info!("{} issuers to query", issuers.len());
+
+		on_chunk(eligible_nfts_of(
+			pool,
+			conn.statements(),
+			filter,
+			slashed,
+			targets,
+			&issuers,
+			height,
+		)?);
+	}
+
+	Ok(())
+}
+
+// Refresh `slashed` and return the height transactions must be below to count, if any
+async fn refreshed_counted_below_height(
+	conn: &PgConn,
+	cutoff: Cutoff,
+	slashed: &Arc<SlashedCache>,
+) -> Result<Option<u64>, Error> {
+	let cache = slashed.clone();
+
+	conn.timed_run(move |c| -> Result<_, Error> {
+		cache.refresh(c)?;
+		counted_below_height(c, cutoff)
+	})
+	.await
+}
 
-	let manager = ConnectionManager::<PgConnection>::new(std::env::var("DATABASE_URL").unwrap());
-	let pool = Pool::builder().build(manager).expect("Failed to create pool.");
+// Compute the NFTs of every one of `issuers` in parallel, each on its own connection of `pool`
+//
+// The statements run are added to `statements`.
+fn eligible_nfts_of(
+	pool: &PgConnectionPool,
+	statements: &DbStatements,
+	filter: &AddressFilter,
+	slashed: &SlashedCache,
+	targets: &[H160],
+	issuers: &[H160],
+	height: Option<u64>,
+) -> Result<Vec<(H160, PackedNftTypes)>, Error> {
+	use rayon::prelude::*;
 
-	let pairs = issuers
+	issuers
 		.par_iter()
 		.map(|issuer| {
-			let pool = pool.clone();
-			let connection = pool.get().unwrap();
-			inner_get_packed_nft(connection.deref(), *issuer).map(|r| AddressNftPair {
-				address: *issuer,
-				nft: r,
-			})
+			let connection = kiln_postgres::get_connection(pool)?;
+			let (packed_nft, count) = count_statements(|| {
+				inner_get_packed_nft(
+					connection.deref(),
+					filter,
+					slashed,
+					targets,
+					*issuer,
+					height,
+				)
+			});
+			statements.add(count);
+			packed_nft.map(|r| (*issuer, r))
 		})
-		.collect::<Result<Vec<AddressNftPair>, Error>>()?;
+		.collect()
+}
+
+/// Blocks transactions must be in to count towards NFTs
+#[derive(Clone, Copy)]
+pub(crate) struct Cutoff {
+	finalized_only: bool,
+	min_confirmations: u64,
+}
+
+impl Cutoff {
+	pub(crate) fn new(config: &Config) -> Self {
+		Cutoff {
+			finalized_only: config.finalized_only,
+			min_confirmations: config.min_confirmations,
+		}
+	}
+}
+
+// Return the highest block transactions must be in to count, if any
+//
+// That is the lowest of the last finalized block, if only finalized ones count, and of the
+// highest indexed block minus `min_confirmations`, if some are required.
+// Nothing counts until the indexer publishes the block required.
+pub(crate) fn counted_below_height(
+	conn: &DbConnection,
+	cutoff: Cutoff,
+) -> Result<Option<u64>, Error> {
+	let finalized = if cutoff.finalized_only {
+		Some(Stat::get(conn, StatKey::FinalizedBlockNumber)?.unwrap_or(0))
+	} else {
+		None
+	};
+	let confirmed = if cutoff.min_confirmations > 0 {
+		let head = Stat::get(conn, StatKey::ExecutionDbHeight)?.unwrap_or(0);
+		Some(head.saturating_sub(cutoff.min_confirmations))
+	} else {
+		None
+	};
+
+	Ok(lowest_height(finalized, confirmed))
+}
+
+// Return the lowest of two optional height cutoffs, `None` being no cutoff
+fn lowest_height(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+	match (a, b) {
+		(Some(a), Some(b)) => Some(a.min(b)),
+		(a, b) => a.or(b),
+	}
+}
 
-	Ok(Json(pairs))
+// Compute the NFTs `address` is eligible to, none if the address filter rejects it
+pub(crate) fn inner_get_packed_nft(
+	conn: &DbConnection,
+	filter: &AddressFilter,
+	slashed: &SlashedCache,
+	targets: &[H160],
+	address: H160,
+	finalized_below_height: Option<u64>,
+) -> Result<PackedNftTypes, Error> {
+	if !filter.is_allowed(&address) {
+		return Ok(PackedNftTypes::zero())
+	}
+
+	compute_packed_nft(conn, slashed, targets, address, finalized_below_height)
 }
 
-fn inner_get_packed_nft(
-	conn: &diesel::PgConnection,
+// Compute the NFTs `address` is eligible to from the indexed data, whatever the address filter
+pub(crate) fn compute_packed_nft(
+	conn: &DbConnection,
+	slashed: &SlashedCache,
+	targets: &[H160],
 	address: H160,
+	finalized_below_height: Option<u64>,
 ) -> Result<PackedNftTypes, Error> {
-	let mut packed_nfts = PackedNftTypes::zero();
+	let metrics = activity_metrics(conn, slashed, targets, address, finalized_below_height)?;
 
-	// Get the address transaction
-	let transactions = Transaction::list_all_from_address(conn, address)?;
+	Ok(packed_nft_from_metrics(&metrics))
+}
+
+/// What the NFTs and the activity score of an address are computed from
+///
+/// Counts stop at the highest threshold of their NFT, counting further would not change it.
+#[derive(Serialize, JsonSchema)]
+pub(crate) struct ActivityMetrics {
+	/// Whether a validator deposited by the address got slashed, None if it deposited none
+	pub validator_slashed: Option<bool>,
+	/// Number of transactions, up to `TRANSACTIONS_COUNT_THRESHOLD`
+	pub transactions_count: u64,
+	/// Number of distinct days with a transaction
+	pub active_days: i64,
+	pub used_eip1559: bool,
+	pub used_target_contract: bool,
+	/// Highest nonce, None without transactions
+	pub max_nonce: Option<u64>,
+	pub deployed_contracts: u64,
+	/// Number of contracts called `CONTRACT_CALLS_THRESHOLD` times, up to
+	/// `CALLED_CONTRACTS_THRESHOLD`
+	pub called_contracts: u64,
+}
+
+// Gather the activity of `address` from the indexed data
+pub(crate) fn activity_metrics(
+	conn: &DbConnection,
+	slashed: &SlashedCache,
+	targets: &[H160],
+	address: H160,
+	finalized_below_height: Option<u64>,
+) -> Result<ActivityMetrics, Error> {
+	let validator_slashed = slashed.is_validator_slashed(conn, address)?;
+
+	let transactions_count = Transaction::count_from_address_capped(
+		conn,
+		address,
+		TRANSACTIONS_COUNT_THRESHOLD,
+		finalized_below_height,
+	)?;
+
+	// Pointless to look for specific transactions of an address without any
+	let (mut active_days, mut used_eip1559, mut used_target_contract) = (0, false, false);
+	if transactions_count > 0 {
+		active_days =
+			Transaction::distinct_active_days_from_address(conn, address, finalized_below_height)?;
+		used_eip1559 = Transaction::has_type_from_address(
+			conn,
+			address,
+			EIP1559_TX_TYPE,
+			finalized_below_height,
+		)?;
+		for target in targets {
+			if Transaction::has_call_to(conn, address, *target, finalized_below_height)? {
+				used_target_contract = true;
+				break
+			}
+		}
+	}
+
+	Ok(ActivityMetrics {
+		validator_slashed,
+		transactions_count,
+		active_days,
+		used_eip1559,
+		used_target_contract,
+		max_nonce: Transaction::max_nonce_from_address(conn, address, finalized_below_height)?,
+		deployed_contracts: Transaction::count_deployed_contracts_from_address(
+			conn,
+			address,
+			finalized_below_height,
+		)?,
+		called_contracts: Transaction::count_contracts_called_from_address(
+			conn,
+			address,
+			CONTRACT_CALLS_THRESHOLD,
+			CALLED_CONTRACTS_THRESHOLD,
+			finalized_below_height,
+		)?,
+	})
+}
+
+// Same as `activity_metrics` for every one of `addresses`, in the same order
+//
+// A few aggregate queries cover all of them, instead of a few queries per address.
+fn activity_metrics_for(
+	conn: &DbConnection,
+	targets: &[H160],
+	addresses: &[H160],
+	finalized_below_height: Option<u64>,
+) -> Result<Vec<ActivityMetrics>, Error> {
+	let slashed = Validator::slashed_status_for_addresses(conn, addresses)?;
+	let activities = Transaction::activity_for_addresses(
+		conn,
+		addresses,
+		EIP1559_TX_TYPE,
+		targets,
+		finalized_below_height,
+	)?;
+	let called = Transaction::count_contracts_called_for_addresses(
+		conn,
+		addresses,
+		CONTRACT_CALLS_THRESHOLD,
+		finalized_below_height,
+	)?;
+
+	Ok(addresses
+		.iter()
+		.map(|address| {
+			let activity = activities.get(address);
+			ActivityMetrics {
+				validator_slashed: slashed.get(address).copied(),
+				transactions_count: activity.map_or(0, |a| {
+					a.transactions_count.min(TRANSACTIONS_COUNT_THRESHOLD)
+				}),
+				active_days: activity.map_or(0, |a| a.active_days),
+				used_eip1559: activity.map_or(false, |a| a.used_type),
+				used_target_contract: activity.map_or(false, |a| a.used_target_contract),
+				max_nonce: activity.and_then(|a| a.max_nonce),
+				deployed_contracts: activity.map_or(0, |a| a.deployed_contracts),
+				called_contracts: called
+					.get(address)
+					.map_or(0, |c| (*c).min(CALLED_CONTRACTS_THRESHOLD)),
+			}
+		})
+		.collect())
+}
+
+// Return the NFTs earned by the activity in `metrics`
+fn packed_nft_from_metrics(metrics: &ActivityMetrics) -> PackedNftTypes {
+	let mut packed_nfts = PackedNftTypes::zero();
 
-	let opt_validator_slashed = Validator::is_validator_slashed(conn, address)?;
-	if let Some(slashed) = opt_validator_slashed {
+	if let Some(slashed) = metrics.validator_slashed {
 		// is validator
 		packed_nfts.set_become_validator();
 		// have been slash validator
 		if slashed {
-			info!("get nfts for: {:?}", address);
 			packed_nfts.set_slashed_validator()
 		}
 	}
 
 	// Do at least 100 transactions
-	if transactions.len() >= 100 {
+	if metrics.transactions_count >= TRANSACTIONS_COUNT_THRESHOLD {
 		packed_nfts.set_do_100_tansactions()
 	}
 	// Do at least 1 transaction
-	if !transactions.is_empty() {
+	if metrics.transactions_count > 0 {
 		packed_nfts.set_do_one_transaction();
 	}
+	// Be active on at least 7 distinct days
+	if metrics.active_days >= ACTIVE_STREAK_MIN_DAYS {
+		packed_nfts.set_active_streak();
+	}
+	// Send at least one EIP-1559 transaction
+	if metrics.used_eip1559 {
+		packed_nfts.set_used_eip1559();
+	}
+	// Call one of the campaign's contracts
+	if metrics.used_target_contract {
+		packed_nfts.set_used_target_contract();
+	}
 
-	// Loop over transactions
-	// count deployed contracts and calls to smart contracts
-	let mut deployed_contracts: usize = 0;
-	let mut call_count_by_contract: HashMap<H160, usize> = HashMap::new();
-	for t in transactions.into_iter() {
-		if t.to().is_none() {
-			deployed_contracts += 1;
-			continue
-		}
-
-		if is_smart_contract_call(&t) {
-			let to = t.to().unwrap();
-			*call_count_by_contract.entry(to).or_insert(0) += 1
+	// Sent more than 100 transactions according to the chain
+	// The nonce is authoritative, even if some of those transactions were not indexed
+	if let Some(nonce) = metrics.max_nonce {
+		if nonce > ACTIVE_ACCOUNT_NONCE_THRESHOLD {
+			packed_nfts.set_active_account();
 		}
 	}
 
 	// deploy 1 contract
-	if deployed_contracts > 0 {
+	if metrics.deployed_contracts > 0 {
 		packed_nfts.set_deploy_contract();
 	}
 	// deploy 10 contracts
-	if deployed_contracts >= 10 {
+	if metrics.deployed_contracts >= 10 {
 		packed_nfts.set_deploy_10_contract();
 	}
 	// deploy 100 contracts
-	if deployed_contracts >= 100 {
+	if metrics.deployed_contracts >= 100 {
 		packed_nfts.set_deploy_100_contract();
 	}
 	// called to 10 contracts 10 times each
-	if call_count_by_contract.into_values().filter(|&v| v >= 10).count() >= 10 {
+	if metrics.called_contracts >= CALLED_CONTRACTS_THRESHOLD {
 		packed_nfts.set_do_10_transactions_to_10_contracts()
 	}
 
-	Ok(packed_nfts)
+	packed_nfts
 }
 
-fn is_smart_contract_call(transaction: &Transaction) -> bool {
-	// Non empty transaction input is marker for a call to a smart contract
-	!transaction.input().is_empty()
+#[cfg(test)]
+mod tests {
+	use std::str::FromStr;
+
+	use diesel::{sql_query, sql_types::BigInt, Connection, RunQueryDsl};
+	use kiln_postgres::{
+		test_database_url, NewExecBlock, NewStat, NewTransaction, NewTransactions,
+	};
+	use rocket::{
+		http::{Header, Status},
+		local::blocking::Client,
+		routes,
+		serde::json::{to_string, Value},
+	};
+
+	use super::*;
+	use crate::fairings::{RequestMetrics, DB_STATEMENTS_HEADER};
+
+	/// Number of issuers listed by the statement budget test
+	const LISTED_ISSUERS: u64 = 20;
+	/// Statements of a listing whatever its number of issuers: its total and its fallback count,
+	/// the validators generation, the two cutoff heights and its page of issuers
+	const FIXED_STATEMENTS: u64 = 6;
+	/// Statements computing the NFTs of an issuer with transactions, plus one per target contract
+	///
+	/// Its slashed status, its transactions count, active days, EIP-1559 use, highest nonce,
+	/// deployed and called contracts. They are not batched across issuers, unlike in
+	/// `activity_metrics_for`.
+	const STATEMENTS_PER_ISSUER: u64 = 7;
+	/// Block of the transactions seeded by the statement budget test, far above any real one
+	const SEEDED_BLOCK: u64 = 3_000_000_000;
+	/// Leading byte of the seeded addresses and hashes
+	const SEED_TAG: u8 = 0xb5;
+
+	/// Serve `packed` the way stored NFTs are
+	#[get("/tagged/<packed>")]
+	fn tagged(packed: u64, if_none_match: IfNoneMatch) -> Cached<Json<PackedNftTypes>> {
+		let packed_nft = PackedNftTypes::from_packed(packed.into());
+		Cached::new(stored_etag(&packed_nft), packed_nft, &if_none_match).map(Json)
+	}
+
+	#[test]
+	fn stored_etags_are_stable_for_equal_nfts() {
+		let etag = stored_etag(&PackedNftTypes::from_packed(0x41.into()));
+
+		assert_eq!(etag, r#"W/"41""#);
+		assert_eq!(stored_etag(&PackedNftTypes::from_packed(0x41.into())), etag);
+		assert_ne!(stored_etag(&PackedNftTypes::from_packed(0x43.into())), etag);
+	}
+
+	#[test]
+	fn computed_etags_change_with_what_nfts_are_computed_from() {
+		let etag = computed_etag_of(Some(100), Some(2), true, 0x1.into());
+
+		assert_eq!(computed_etag_of(Some(100), Some(2), true, 0x1.into()), etag);
+		for changed in [
+			computed_etag_of(Some(101), Some(2), true, 0x1.into()),
+			computed_etag_of(None, Some(2), true, 0x1.into()),
+			computed_etag_of(Some(100), Some(3), true, 0x1.into()),
+			computed_etag_of(Some(100), Some(2), false, 0x1.into()),
+			computed_etag_of(Some(100), Some(2), true, 0x3.into()),
+		] {
+			assert_ne!(changed, etag);
+		}
+	}
+
+	#[test]
+	fn nfts_already_held_get_a_bodyless_304() {
+		let client = Client::tracked(rocket::build().mount("/", routes![tagged])).unwrap();
+
+		let response = client.get("/tagged/65").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		let etag = response.headers().get_one("ETag").unwrap().to_string();
+		assert_eq!(response.into_string().unwrap(), r#""0x41""#);
+
+		let response = client
+			.get("/tagged/65")
+			.header(Header::new("If-None-Match", etag.clone()))
+			.dispatch();
+		assert_eq!(response.status(), Status::NotModified);
+		assert_eq!(response.headers().get_one("ETag"), Some(etag.as_str()));
+		assert!(response.into_string().unwrap_or_default().is_empty());
+	}
+
+	#[test]
+	fn changed_nfts_are_served_despite_the_previous_etag() {
+		let client = Client::tracked(rocket::build().mount("/", routes![tagged])).unwrap();
+		let etag = stored_etag(&PackedNftTypes::from_packed(0x41.into()));
+
+		let response =
+			client.get("/tagged/67").header(Header::new("If-None-Match", etag)).dispatch();
+
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(response.headers().get_one("ETag"), Some(r#"W/"43""#));
+		assert_eq!(response.into_string().unwrap(), r#""0x43""#);
+	}
+
+	fn pair(with_names: bool) -> AddressNftPair {
+		// An EIP-55 test vector, parsed without its prefix
+		let address = H160::from_str("5aaeb6053f3e94c9b9a09f33669435e7ef1beaed").unwrap();
+		let mut nft = PackedNftTypes::zero();
+		nft.set_do_one_transaction();
+		nft.set_become_validator();
+
+		AddressNftPair::new(address, nft, with_names)
+	}
+
+	#[test]
+	fn pairs_serialize_a_checksummed_address_and_hex_nfts() {
+		assert_eq!(
+			to_string(&pair(false)).unwrap(),
+			r#"{"address":"0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed","nft":"0x41"}"#
+		);
+	}
+
+	#[test]
+	fn pairs_serialize_the_names_of_the_nfts_set_when_requested() {
+		assert_eq!(
+			to_string(&pair(true)).unwrap(),
+			concat!(
+				r#"{"address":"0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed","nft":"0x41","#,
+				r#""nft_names":["do_one_transaction","become_validator"]}"#
+			)
+		);
+	}
+
+	#[test]
+	fn pairs_serialize_no_nft_as_hex_zero() {
+		let pair = AddressNftPair::new(H160::zero(), PackedNftTypes::zero(), true);
+
+		assert_eq!(
+			to_string(&pair).unwrap(),
+			r#"{"address":"0x0000000000000000000000000000000000000000","nft":"0x0","nft_names":[]}"#
+		);
+	}
+
+	#[test]
+	fn catalog_route_serves_every_nft_type() {
+		let client = Client::tracked(rocket::build().mount("/", routes![nft_catalog])).unwrap();
+
+		let catalog = client.get("/nfts/catalog").dispatch().into_json::<Vec<Value>>().unwrap();
+
+		assert_eq!(catalog.len(), PackedNftTypes::NFT_TYPES.len());
+		assert_eq!(catalog[0]["name"], "do_one_transaction");
+		assert_eq!(catalog[0]["position"], 0);
+	}
+
+	fn seeded_address(index: u64) -> H160 {
+		let mut address = H160::from_low_u64_be(index);
+		address.0[0] = SEED_TAG;
+		address
+	}
+
+	fn seeded_hash(index: u64) -> H256 {
+		let mut hash = H256::from_low_u64_be(index);
+		hash.0[0] = SEED_TAG;
+		hash
+	}
+
+	// Issuers of a transaction each, committed to the test database and deleted once dropped
+	//
+	// The listing runs on connections of its own, so they cannot be seeded in a test transaction.
+	// The issuers count is kept right meanwhile, then restored.
+	struct SeededIssuers {
+		conn: DbConnection,
+		issuers_count: Option<u64>,
+	}
+
+	impl SeededIssuers {
+		fn new(conn: DbConnection, count: u64) -> Self {
+			delete_seeded(&conn);
+			let issuers_count = Stat::get(&conn, StatKey::IssuersCount).unwrap();
+
+			let block = seeded_hash(0);
+			NewExecBlock::new(
+				block,
+				SEEDED_BLOCK,
+				H256::zero(),
+				H256::zero(),
+				H256::zero(),
+				H256::zero(),
+				0,
+				0,
+			)
+			.insert(&conn)
+			.unwrap();
+			let transactions = (0..count)
+				.map(|i| {
+					NewTransaction::new(
+						seeded_hash(i + 1),
+						block,
+						i,
+						Some(seeded_address(i)),
+						Some(seeded_address(i)),
+						vec![],
+						U256::zero(),
+						0,
+						SEEDED_BLOCK,
+						Some(EIP1559_TX_TYPE),
+					)
+				})
+				.collect();
+			NewTransactions::new(transactions).batch_insert(&conn).unwrap();
+			if let Some(issuers_count) = issuers_count {
+				NewStat::new(StatKey::IssuersCount, issuers_count + count)
+					.upsert(&conn)
+					.unwrap();
+			}
+
+			SeededIssuers {
+				conn,
+				issuers_count,
+			}
+		}
+	}
+
+	impl Drop for SeededIssuers {
+		fn drop(&mut self) {
+			delete_seeded(&self.conn);
+			if let Some(issuers_count) = self.issuers_count {
+				NewStat::new(StatKey::IssuersCount, issuers_count).upsert(&self.conn).ok();
+			}
+		}
+	}
+
+	// Delete the seeded rows, including the ones left by an interrupted run
+	fn delete_seeded(conn: &DbConnection) {
+		for statement in [
+			"DELETE FROM transactions WHERE block_number = $1",
+			"DELETE FROM execution_blocks WHERE number = $1",
+		] {
+			sql_query(statement)
+				.bind::<BigInt, _>(SEEDED_BLOCK as i64)
+				.execute(conn)
+				.expect("failed to delete the seeded rows");
+		}
+	}
+
+	#[test]
+	fn listing_stays_within_a_statement_budget_per_issuer() {
+		let url = match test_database_url() {
+			Some(url) => url,
+			None => return,
+		};
+		let conn = DbConnection::establish(&url).expect("failed to connect to the test database");
+		let _seeded = SeededIssuers::new(conn, LISTED_ISSUERS);
+
+		let config = Config::default();
+		let per_issuer = STATEMENTS_PER_ISSUER + config.target_contracts.len() as u64;
+		let figment = rocket::Config::figment().merge(("databases.kiln_pg.url", url));
+		let rocket = rocket::custom(figment)
+			.attach(PgConn::fairing())
+			.attach(RequestMetrics)
+			.manage(Arc::new(AddressFilter::load(&config).unwrap()))
+			.manage(Arc::new(SlashedCache::new(config.slashed_cache_size)))
+			.manage(kiln_postgres::connexion_pool_with_size(4))
+			.manage(config)
+			.mount("/", routes![list_all_eligible_nft]);
+		let client = Client::tracked(rocket).unwrap();
+
+		let response = client.get(format!("/nfts?limit={LISTED_ISSUERS}")).dispatch();
+		let statements: u64 =
+			response.headers().get_one(DB_STATEMENTS_HEADER).unwrap().parse().unwrap();
+		let listed = response.into_json::<Vec<Value>>().unwrap().len() as u64;
+
+		assert_eq!(listed, LISTED_ISSUERS);
+		// Each issuer has its slashed status and its transactions counted at least
+		assert!(
+			statements >= 2 * listed,
+			"{statements} statements are not all counted"
+		);
+		assert!(
+			statements <= FIXED_STATEMENTS + per_issuer * listed,
+			"{statements} statements to list {listed} issuers"
+		);
+	}
+
+	#[test]
+	fn explaining_more_addresses_than_allowed_is_rejected() {
+		let url = match test_database_url() {
+			Some(url) => url,
+			None => return,
+		};
+
+		let config = Config {
+			explain_max_addresses: 2,
+			..Config::default()
+		};
+		let figment = rocket::Config::figment().merge(("databases.kiln_pg.url", url));
+		let rocket = rocket::custom(figment)
+			.attach(PgConn::fairing())
+			.manage(Arc::new(AddressFilter::load(&config).unwrap()))
+			.manage(config)
+			.mount("/", routes![explain_nfts]);
+		let client = Client::tracked(rocket).unwrap();
+
+		// Larger than the body of 2 addresses, even though they are all the same
+		let addresses = vec![H160::repeat_byte(SEED_TAG); 1000];
+		let response = client.post("/nfts/explain").body(to_string(&addresses).unwrap()).dispatch();
+		assert_eq!(response.status(), Status::BadRequest);
+		let body: Value = response.into_json().unwrap();
+		assert_eq!(body["code"], "too_many_addresses");
+
+		let addresses: Vec<H160> = (1..=3).map(H160::from_low_u64_be).collect();
+		let response = client.post("/nfts/explain").body(to_string(&addresses).unwrap()).dispatch();
+		assert_eq!(response.status(), Status::BadRequest);
+	}
 }
@@ -0,0 +1,45 @@
+use kiln_postgres::{serialize_checksummed, Slot};
+use primitive_types::H160;
+use rocket::{get, serde::json::Json, State};
+use serde::Serialize;
+
+use crate::{
+	config::Config,
+	json::JsonU64,
+	params::{Hash160, HashParamError},
+	Error, PgConn,
+};
+
+#[derive(Serialize)]
+pub struct ProposedCount {
+	#[serde(serialize_with = "serialize_checksummed")]
+	address: H160,
+	from: JsonU64,
+	to: JsonU64,
+	proposed: JsonU64,
+}
+
+/// Return the number of blocks proposed between slots `from` and `to` included by the validators
+/// deposited by `address`
+#[get("/address/<address>/proposed?<from>&<to>")]
+pub async fn proposed_count(
+	conn: PgConn,
+	config: &State<Config>,
+	address: Result<Hash160, HashParamError>,
+	from: u64,
+	to: u64,
+) -> Result<Json<ProposedCount>, Error> {
+	let address: H160 = address.map_err(Error::InvalidAddress)?.into();
+	if from > to || to - from >= config.proposals_max_range {
+		return Err(Error::InvalidRange)
+	}
+
+	let proposed = conn.timed_run(move |c| Slot::proposed_count(c, address, from, to)).await?;
+
+	Ok(Json(ProposedCount {
+		address,
+		from: from.into(),
+		to: to.into(),
+		proposed: JsonU64(proposed as u64),
+	}))
+}
@@ -0,0 +1,34 @@
+use kiln_postgres::{checksum_address, Transaction};
+use rocket::{get, serde::json::Json, State};
+
+use crate::{config::Config, Error, PgConn};
+
+/// Number of addresses returned when no limit is requested
+const DEFAULT_LIMIT: u32 = 10;
+
+/// Return the issuers whose address starts with `prefix`, ordered by address
+///
+/// `prefix` is made of hex digits, optionally '0x' prefixed, in any case.
+/// Addresses are EIP-55 checksummed.
+#[get("/search/addresses?<prefix>&<limit>")]
+pub async fn search_addresses(
+	conn: PgConn,
+	config: &State<Config>,
+	prefix: &str,
+	limit: Option<u32>,
+) -> Result<Json<Vec<String>>, Error> {
+	let prefix = prefix.strip_prefix("0x").unwrap_or(prefix).to_lowercase();
+	if prefix.len() < config.search_min_prefix_length
+		|| prefix.len() > 40
+		|| !prefix.chars().all(|c| c.is_ascii_hexdigit())
+	{
+		return Err(Error::InvalidPrefix)
+	}
+
+	let limit = limit.unwrap_or(DEFAULT_LIMIT).min(config.search_max_results);
+	let issuers = conn
+		.timed_run(move |c| Transaction::issuers_with_prefix(c, &prefix, limit))
+		.await?;
+
+	Ok(Json(issuers.iter().map(checksum_address).collect()))
+}
@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+
+use kiln_postgres::{Slot, Transaction};
+use primitive_types::H256;
+use rocket::{get, serde::json::Json, State};
+use rocket_sync_db_pools::diesel::OptionalExtension;
+use serde::Serialize;
+
+use crate::{config::Config, json::JsonU64, Error, PgConn};
+
+/// Return the number of stored slots for every spec, by spec name
+#[get("/slots/specs")]
+pub async fn slots_by_spec(conn: PgConn) -> Result<Json<BTreeMap<String, JsonU64>>, Error> {
+	let counts = conn.timed_run(|c| Slot::count_by_spec(c)).await?;
+
+	Ok(Json(
+		counts
+			.into_iter()
+			.map(|(spec, count)| (spec.to_string(), JsonU64(count as u64)))
+			.collect(),
+	))
+}
+
+/// Return the heights of the slots stored between `from` and `to` included, by ascending height
+///
+/// Lets another deployment check which heights it is missing without fetching every slot.
+#[get("/slots/present?<from>&<to>")]
+pub async fn present_slots(
+	conn: PgConn,
+	config: &State<Config>,
+	from: u64,
+	to: u64,
+) -> Result<Json<Vec<JsonU64>>, Error> {
+	if from > to || to - from >= config.present_heights_max_range {
+		return Err(Error::InvalidRange)
+	}
+
+	let heights = conn.timed_run(move |c| Slot::present_heights_in_range(c, from, to)).await?;
+
+	Ok(Json(heights.into_iter().map(JsonU64).collect()))
+}
+
+#[derive(Serialize)]
+pub struct SlotResponse {
+	height: JsonU64,
+	block_hash: Option<H256>,
+	block_number: Option<JsonU64>,
+	missed: bool,
+	proposer_index: Option<JsonU64>,
+	validators_count: Option<JsonU64>,
+	spec: Option<String>,
+	/// Number of transactions of the slot's execution block, null if unknown
+	tx_count: Option<JsonU64>,
+	/// Seconds since the UNIX epoch of the slot's execution block, null if unknown
+	timestamp: Option<JsonU64>,
+}
+
+impl From<Slot> for SlotResponse {
+	fn from(slot: Slot) -> Self {
+		SlotResponse {
+			height: slot.height().into(),
+			block_hash: slot.block_hash(),
+			block_number: slot.block_number().map(JsonU64),
+			missed: slot.missed(),
+			proposer_index: slot.proposer_index().map(JsonU64),
+			validators_count: slot.validators_count().map(JsonU64),
+			spec: slot.spec().map(ToString::to_string),
+			tx_count: slot.tx_count().map(JsonU64),
+			timestamp: slot.timestamp().map(JsonU64),
+		}
+	}
+}
+
+/// Return the slot at `height`
+///
+/// Answer 404 if it is not stored.
+#[get("/slots/<height>")]
+pub async fn slot(conn: PgConn, height: u64) -> Result<Option<Json<SlotResponse>>, Error> {
+	let slot = conn.timed_run(move |c| Slot::get(c, height).optional()).await?;
+
+	Ok(slot.map(|s| Json(s.into())))
+}
+
+#[derive(Serialize)]
+pub struct SlotGas {
+	height: JsonU64,
+	gas_used: JsonU64,
+}
+
+/// Return the gas used by the transactions of the slot at `height`
+///
+/// Missed slots used none. Answer 404 if the slot, or its gas usage, is not stored.
+#[get("/slots/<height>/gas")]
+pub async fn slot_gas(conn: PgConn, height: u64) -> Result<Option<Json<SlotGas>>, Error> {
+	let gas_used = conn.timed_run(move |c| Transaction::gas_used_at_height(c, height)).await?;
+
+	Ok(gas_used.map(|gas_used| {
+		Json(SlotGas {
+			height: height.into(),
+			gas_used: gas_used.into(),
+		})
+	}))
+}
@@ -0,0 +1,54 @@
+use std::str::FromStr;
+
+use kiln_postgres::{Transaction, TransactionKind};
+use primitive_types::{H160, H256};
+use rocket::{get, serde::json::Json, State};
+
+use crate::{
+	config::Config,
+	params::{Hash160, Hash256, HashParamError},
+	Error, PgConn,
+};
+
+/// Number of transactions returned when no limit is requested
+const DEFAULT_LIMIT: u32 = 10;
+
+/// Return the latest transactions issued by `address`, newest first
+///
+/// `kind` keeps only the contract calls (`call`), the plain transfers (`transfer`) or the
+/// deployments (`deploy`); every transaction is returned when unset.
+#[get("/address/<address>/transactions?<kind>&<limit>")]
+pub async fn address_transactions(
+	conn: PgConn,
+	config: &State<Config>,
+	address: Result<Hash160, HashParamError>,
+	kind: Option<&str>,
+	limit: Option<u32>,
+) -> Result<Json<Vec<Transaction>>, Error> {
+	let address: H160 = address.map_err(Error::InvalidAddress)?.into();
+	let kind = kind
+		.map(TransactionKind::from_str)
+		.transpose()
+		.map_err(|_| Error::InvalidTransactionKind)?;
+
+	let limit = limit.unwrap_or(DEFAULT_LIMIT).min(config.transactions_max_results);
+	let transactions = conn
+		.timed_run(move |c| Transaction::recent_from_address(c, address, kind, limit))
+		.await?;
+
+	Ok(Json(transactions))
+}
+
+/// Return the transaction of `hash`
+///
+/// Answer 404 if it is not stored.
+#[get("/transaction/<hash>")]
+pub async fn transaction_by_hash(
+	conn: PgConn,
+	hash: Result<Hash256, HashParamError>,
+) -> Result<Json<Transaction>, Error> {
+	let hash: H256 = hash.map_err(Error::InvalidHash)?.into();
+	let transaction = conn.timed_run(move |c| Transaction::get_by_hash(c, hash)).await?;
+
+	transaction.map(Json).ok_or(Error::TransactionNotFound)
+}
@@ -0,0 +1,71 @@
+use std::collections::HashSet;
+
+use kiln_postgres::{serialize_checksummed, SlashingEvent, Validator};
+use primitive_types::H160;
+use rocket::{get, post, serde::json::Json, State};
+use serde::Serialize;
+
+use crate::{
+	config::Config,
+	params::{Hash160, HashParamError},
+	Error, PgConn,
+};
+
+/// Return the slashings of the validators deposited by `address`, by ascending slot
+#[get("/validator/<address>/slashings")]
+pub async fn validator_slashings(
+	conn: PgConn,
+	address: Result<Hash160, HashParamError>,
+) -> Result<Json<Vec<SlashingEvent>>, Error> {
+	let address: H160 = address.map_err(Error::InvalidAddress)?.into();
+	let events = conn.timed_run(move |c| Validator::slashing_events(c, address)).await?;
+
+	Ok(Json(events))
+}
+
+/// Validator status of an address
+#[derive(Serialize)]
+pub struct ValidatorStatus {
+	/// '0x' prefixed EIP-55 checksummed address
+	#[serde(serialize_with = "serialize_checksummed")]
+	address: H160,
+	/// Whether the address deposited a validator
+	is_validator: bool,
+	/// Whether one of the validators deposited by the address is slashed
+	slashed: bool,
+}
+
+/// Return the validator status of every address of the body, a JSON array of '0x' prefixed hex
+/// addresses
+///
+/// Addresses are deduplicated and answered in the order they are first given. At most
+/// `validators_status_max_addresses` distinct ones can be looked up at once.
+#[post("/validators/status", data = "<addresses>")]
+pub async fn validators_status(
+	conn: PgConn,
+	config: &State<Config>,
+	addresses: Json<Vec<H160>>,
+) -> Result<Json<Vec<ValidatorStatus>>, Error> {
+	let mut seen = HashSet::new();
+	let addresses: Vec<H160> =
+		addresses.into_inner().into_iter().filter(|a| seen.insert(*a)).collect();
+	if addresses.len() > config.validators_status_max_addresses {
+		return Err(Error::TooManyAddresses)
+	}
+
+	let queried = addresses.clone();
+	let statuses = conn
+		.timed_run(move |c| Validator::slashed_status_for_addresses(c, &queried))
+		.await?;
+
+	Ok(Json(
+		addresses
+			.into_iter()
+			.map(|address| ValidatorStatus {
+				address,
+				is_validator: statuses.contains_key(&address),
+				slashed: statuses.get(&address).copied().unwrap_or(false),
+			})
+			.collect(),
+	))
+}
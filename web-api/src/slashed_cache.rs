@@ -0,0 +1,78 @@
+use std::sync::Mutex;
+
+use kiln_postgres::{DbConnection, Stat, StatKey, Validator};
+use lru::LruCache;
+use primitive_types::H160;
+use rocket_sync_db_pools::diesel::QueryResult;
+
+use crate::metrics::{SLASHED_CACHE_HITS, SLASHED_CACHE_MISSES};
+
+struct Entries {
+	/// Indexer validators generation the entries were read at
+	generation: Option<u64>,
+	statuses: LruCache<H160, Option<bool>>,
+}
+
+/// Bounded cache of `Validator::is_validator_slashed` by address
+///
+/// The indexer bumps the validators generation every time a validator is slashed or linked to its
+/// deposit. Call `refresh` to drop the entries read at a previous generation.
+pub struct SlashedCache {
+	/// None when disabled
+	entries: Option<Mutex<Entries>>,
+}
+
+impl SlashedCache {
+	/// Return a cache of at most `capacity` addresses, disabled if `capacity` is 0
+	pub fn new(capacity: usize) -> Self {
+		let entries = (capacity > 0).then(|| {
+			Mutex::new(Entries {
+				generation: None,
+				statuses: LruCache::new(capacity),
+			})
+		});
+
+		SlashedCache { entries }
+	}
+
+	/// Drop every entry if the validators changed since they were read
+	pub fn refresh(&self, conn: &DbConnection) -> QueryResult<()> {
+		let entries = match &self.entries {
+			Some(e) => e,
+			None => return Ok(()),
+		};
+
+		let generation = Stat::get(conn, StatKey::ValidatorsGeneration)?;
+		let mut entries = entries.lock().unwrap();
+		if entries.generation != generation {
+			entries.statuses.clear();
+			entries.generation = generation;
+		}
+
+		Ok(())
+	}
+
+	/// Same as `Validator::is_validator_slashed`, served from the cache when possible
+	pub fn is_validator_slashed(
+		&self,
+		conn: &DbConnection,
+		address: H160,
+	) -> QueryResult<Option<bool>> {
+		let entries = match &self.entries {
+			Some(e) => e,
+			None => return Validator::is_validator_slashed(conn, address),
+		};
+
+		if let Some(status) = entries.lock().unwrap().statuses.get(&address) {
+			SLASHED_CACHE_HITS.inc();
+			return Ok(*status)
+		}
+		SLASHED_CACHE_MISSES.inc();
+
+		// The lock is not held while querying
+		let status = Validator::is_validator_slashed(conn, address)?;
+		entries.lock().unwrap().statuses.put(address, status);
+
+		Ok(status)
+	}
+}
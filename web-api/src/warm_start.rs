@@ -0,0 +1,99 @@
+use std::path::PathBuf;
+
+use kiln_postgres::{get_connection, DbConnection, NewPackedNft, PgConnectionPool};
+use log::{info, warn};
+use primitive_types::H160;
+
+use crate::{
+	address_filter::{read_addresses, AddressFilter},
+	config::Config,
+	routes::{
+		compute_packed_nft, computed_below_height, counted_below_height, fresh_packed_nft, Cutoff,
+	},
+	slashed_cache::SlashedCache,
+	Error,
+};
+
+/// Precomputation of the NFTs of the most queried addresses on launch
+///
+/// Without it the first request for an address not in the precomputed table yet computes its
+/// NFTs, which is slow. Addresses precomputed from every transaction counting now are left as is,
+/// the others are computed again.
+pub struct WarmStart {
+	addresses: PathBuf,
+	size: usize,
+	cutoff: Cutoff,
+	targets: Vec<H160>,
+}
+
+impl WarmStart {
+	/// Return the warm start set in `config`, or None if it is disabled
+	pub fn from_config(config: &Config) -> Option<Self> {
+		if !config.precomputed_nfts || config.warm_start_size == 0 {
+			return None
+		}
+
+		config.warm_start_addresses.as_ref().map(|addresses| WarmStart {
+			addresses: addresses.clone(),
+			size: config.warm_start_size,
+			cutoff: Cutoff::new(config),
+			targets: config.target_contracts.clone(),
+		})
+	}
+
+	/// Precompute the NFTs of the first addresses of the list, blocking until done
+	///
+	/// Meant to run on a thread of its own while the api serves. An unreadable list is logged and
+	/// skipped, nothing is precomputed.
+	pub fn run(&self, pool: &PgConnectionPool, filter: &AddressFilter, slashed: &SlashedCache) {
+		let addresses = match read_addresses(&self.addresses) {
+			Ok(addresses) => addresses,
+			Err(err) => {
+				warn!(
+					"warm start skipped, failed to read {:?}: {err:?}",
+					self.addresses
+				);
+				return
+			},
+		};
+		let conn = match get_connection(pool) {
+			Ok(conn) => conn,
+			Err(err) => {
+				warn!("warm start skipped, no database connection: {err}");
+				return
+			},
+		};
+
+		match self.precompute(&conn, filter, slashed, &addresses) {
+			Ok(count) => info!("warm start done: nfts of {count} addresses precomputed"),
+			Err(err) => warn!("warm start interrupted: {err:?}"),
+		}
+	}
+
+	// Return the number of addresses precomputed
+	fn precompute(
+		&self,
+		conn: &DbConnection,
+		filter: &AddressFilter,
+		slashed: &SlashedCache,
+		addresses: &[H160],
+	) -> Result<usize, Error> {
+		slashed.refresh(conn)?;
+		let height = counted_below_height(conn, self.cutoff)?;
+		let computed_below = computed_below_height(conn, height)?;
+
+		let mut count = 0;
+		// Denied addresses are served without precomputation
+		for address in addresses.iter().filter(|a| filter.is_allowed(a)).take(self.size) {
+			if fresh_packed_nft(conn, *address, height)?.is_some() {
+				continue
+			}
+
+			let packed_nft = compute_packed_nft(conn, slashed, &self.targets, *address, height)?;
+			NewPackedNft::new(*address, packed_nft.packed(), computed_below).upsert(conn)?;
+			count += 1;
+		}
+
+		Ok(count)
+	}
+}